@@ -1,18 +1,11 @@
-use std::collections::HashMap;
-
 use anyhow::Result;
 use clap::{Parser, ValueEnum};
-use serde_json;
 use smolagents_rs::agents::Step;
 use smolagents_rs::agents::{Agent, CodeAgent, FunctionCallingAgent};
-use smolagents_rs::errors::AgentError;
-use smolagents_rs::models::model_traits::{Model, ModelResponse};
-use smolagents_rs::models::ollama::{OllamaModel, OllamaModelBuilder};
+use smolagents_rs::models::model_traits::Model;
+use smolagents_rs::models::ollama::OllamaModelBuilder;
 use smolagents_rs::models::openai::OpenAIServerModel;
-use smolagents_rs::models::types::Message;
-use smolagents_rs::tools::{
-    AnyTool, DuckDuckGoSearchTool, GoogleSearchTool, ToolInfo, VisitWebsiteTool,
-};
+use smolagents_rs::tools::{AnyTool, DuckDuckGoSearchTool, GoogleSearchTool, VisitWebsiteTool};
 use std::fs::File;
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -34,15 +27,9 @@ enum ModelType {
     Ollama,
 }
 
-#[derive(Debug)]
-enum ModelWrapper {
-    OpenAI(OpenAIServerModel),
-    Ollama(OllamaModel),
-}
-
 enum AgentWrapper {
-    FunctionCalling(FunctionCallingAgent<ModelWrapper>),
-    Code(CodeAgent<ModelWrapper>),
+    FunctionCalling(FunctionCallingAgent<Box<dyn Model>>),
+    Code(CodeAgent<Box<dyn Model>>),
 }
 
 impl AgentWrapper {
@@ -59,21 +46,6 @@ impl AgentWrapper {
         }
     }
 }
-impl Model for ModelWrapper {
-    fn run(
-        &self,
-        messages: Vec<Message>,
-        tools: Vec<ToolInfo>,
-        max_tokens: Option<usize>,
-        args: Option<HashMap<String, Vec<String>>>,
-    ) -> Result<Box<dyn ModelResponse>, AgentError> {
-        match self {
-            ModelWrapper::OpenAI(m) => Ok(m.run(messages, tools, max_tokens, args)?),
-            ModelWrapper::Ollama(m) => Ok(m.run(messages, tools, max_tokens, args)?),
-        }
-    }
-}
-
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -128,14 +100,14 @@ fn main() -> Result<()> {
     let tools: Vec<Box<dyn AnyTool>> = args.tools.iter().map(create_tool).collect();
 
     // Create model based on type
-    let model = match args.model_type {
-        ModelType::OpenAI => ModelWrapper::OpenAI(OpenAIServerModel::new(
+    let model: Box<dyn Model> = match args.model_type {
+        ModelType::OpenAI => Box::new(OpenAIServerModel::new(
             args.base_url.as_deref(),
             Some(&args.model_id),
             None,
             args.api_key,
         )),
-        ModelType::Ollama => ModelWrapper::Ollama(
+        ModelType::Ollama => Box::new(
             OllamaModelBuilder::new()
                 .model_id(&args.model_id)
                 .ctx_length(8000)
@@ -10,28 +10,35 @@
 //!
 //! Planning agent is not implemented yet and will be added in the future.
 //!
+use crate::approval::{review_call, ApprovalPolicy};
 use crate::errors::AgentError;
 use crate::models::model_traits::Model;
-use crate::models::openai::ToolCall;
+use crate::models::openai::{FunctionCall, ToolCall};
 use crate::models::types::Message;
 use crate::models::types::MessageRole;
 use crate::prompts::{
     user_prompt_plan, SYSTEM_PROMPT_FACTS, SYSTEM_PROMPT_PLAN, TOOL_CALLING_SYSTEM_PROMPT,
 };
-use crate::tools::{AnyTool, FinalAnswerTool, ToolGroup, ToolInfo};
-use std::collections::HashMap;
+use crate::tools::{
+    parse_structured_answer, AnyTool, Artifact, FinalAnswerTool, SelectionContext,
+    StructuredFinalAnswerTool, ToolGroup, ToolInfo, ToolOutput, ToolRegistry,
+};
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
 
 use crate::logger::LOGGER;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use colored::Colorize;
 use log::info;
 
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::json;
 #[cfg(feature = "code-agent")]
 use {
     crate::errors::InterpreterError, crate::local_python_interpreter::LocalPythonInterpreter,
-    crate::models::openai::FunctionCall, crate::prompts::CODE_SYSTEM_PROMPT, regex::Regex,
+    crate::prompts::CODE_SYSTEM_PROMPT, regex::Regex,
 };
 
 const DEFAULT_TOOL_DESCRIPTION_TEMPLATE: &str = r#"
@@ -44,7 +51,7 @@ use std::fmt::Debug;
 pub fn get_tool_description_with_args(tool: &ToolInfo) -> String {
     let mut description = DEFAULT_TOOL_DESCRIPTION_TEMPLATE.to_string();
     description = description.replace("{{ tool.name }}", tool.function.name);
-    description = description.replace("{{ tool.description }}", tool.function.description);
+    description = description.replace("{{ tool.description }}", &tool.function.description);
     description = description.replace(
         "{{tool.inputs}}",
         json!(&tool.function.parameters.schema)["properties"]
@@ -117,6 +124,16 @@ pub trait Agent {
     }
     fn model(&self) -> &dyn Model;
     fn step(&mut self, log_entry: &mut Step) -> Result<Option<String>>;
+    /// Replaces the tool the model uses to provide its final answer and returns the previous one.
+    fn set_final_answer_tool(
+        &mut self,
+        _tool: Box<dyn AnyTool>,
+    ) -> Result<Option<Box<dyn AnyTool>>> {
+        Err(anyhow!(
+            "{} does not support replacing the final answer tool",
+            self.name()
+        ))
+    }
     fn direct_run(&mut self, _task: &str) -> Result<String> {
         let mut final_answer: Option<String> = None;
         while final_answer.is_none() && self.get_step_number() < self.get_max_steps() {
@@ -127,6 +144,7 @@ pub trait Agent {
                 tool_call: None,
                 error: None,
                 observations: None,
+                artifacts: Vec::new(),
                 _step: self.get_step_number(),
             });
 
@@ -169,6 +187,49 @@ pub trait Agent {
             false => self.direct_run(task),
         }
     }
+    /// Runs the task and returns the final answer with the images and files the tools produced.
+    fn run_with_artifacts(&mut self, task: &str, reset: bool) -> Result<RunResult> {
+        let answer = self.run(task, false, reset)?;
+        let logs = self.get_logs_mut();
+        let task_start = logs
+            .iter()
+            .rposition(|step| matches!(step, Step::TaskStep(_)))
+            .unwrap_or_default();
+        let artifacts = logs[task_start..]
+            .iter()
+            .filter_map(|step| match step {
+                Step::ActionStep(step) => Some(step.artifacts.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        Ok(RunResult { answer, artifacts })
+    }
+    /// Runs the task and returns the final answer deserialized into `T`.
+    ///
+    /// The final answer tool takes its schema from `T` for the duration of the run. Answers that
+    /// do not match are reported to the model as step errors so it can retry.
+    fn run_typed<T: JsonSchema + DeserializeOwned + 'static>(
+        &mut self,
+        task: &str,
+        reset: bool,
+    ) -> Result<T>
+    where
+        Self: Sized,
+    {
+        let previous =
+            self.set_final_answer_tool(Box::new(StructuredFinalAnswerTool::<T>::new()))?;
+        let answer = self.run(task, false, reset);
+        self.set_final_answer_tool(previous.unwrap_or_else(|| Box::new(FinalAnswerTool::new())))?;
+        let answer = answer?;
+        parse_structured_answer::<T>(&answer).map_err(|e| {
+            anyhow!(
+                "The agent did not provide a final answer matching the expected type: {}. Answer: {}",
+                e,
+                answer
+            )
+        })
+    }
     fn provide_final_answer(&mut self, task: &str) -> Result<Option<String>> {
         let mut input_messages = vec![Message {
             role: MessageRole::System,
@@ -280,6 +341,14 @@ pub trait Agent {
     }
 }
 
+/// The outcome of [`Agent::run_with_artifacts`].
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    pub answer: String,
+    /// The images and files returned by tools during the run, in the order they were produced.
+    pub artifacts: Vec<Artifact>,
+}
+
 #[derive(Debug, Serialize)]
 pub enum Step {
     PlanningStep(String, String),
@@ -310,9 +379,38 @@ pub struct AgentStep {
     tool_call: Option<Vec<ToolCall>>,
     error: Option<AgentError>,
     observations: Option<Vec<String>>,
+    artifacts: Vec<Artifact>,
     _step: usize,
 }
 
+impl AgentStep {
+    /// The messages sent to the model in this step.
+    pub fn agent_memory(&self) -> Option<&[Message]> {
+        self.agent_memory.as_deref()
+    }
+    /// The text returned by the model in this step.
+    pub fn llm_output(&self) -> Option<&str> {
+        self.llm_output.as_deref()
+    }
+    /// The tool calls made in this step. For the code agent this is the executed code.
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        self.tool_call.as_deref().unwrap_or_default()
+    }
+    pub fn error(&self) -> Option<&AgentError> {
+        self.error.as_ref()
+    }
+    pub fn observations(&self) -> &[String] {
+        self.observations.as_deref().unwrap_or_default()
+    }
+    /// The images and files returned by tools in this step.
+    pub fn artifacts(&self) -> &[Artifact] {
+        &self.artifacts
+    }
+    pub fn step_number(&self) -> usize {
+        self._step
+    }
+}
+
 impl std::fmt::Display for AgentStep {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "AgentStep({:?})", self)
@@ -325,6 +423,8 @@ pub struct MultiStepAgent<M: Model> {
     pub model: M,
     pub tools: Vec<Box<dyn AnyTool>>,
     pub system_prompt_template: String,
+    /// The system prompt before the tool descriptions were filled in.
+    raw_system_prompt_template: String,
     pub name: &'static str,
     pub managed_agents: Option<HashMap<String, Box<dyn Agent>>>,
     pub description: String,
@@ -333,6 +433,8 @@ pub struct MultiStepAgent<M: Model> {
     pub task: String,
     pub input_messages: Option<Vec<Message>>,
     pub logs: Vec<Step>,
+    /// Tools that can change during the run, offered next to `tools`.
+    pub tool_registry: Option<ToolRegistry>,
 }
 
 impl<M: Model + Debug> Agent for MultiStepAgent<M> {
@@ -347,6 +449,13 @@ impl<M: Model + Debug> Agent for MultiStepAgent<M> {
     }
     fn set_task(&mut self, task: &str) {
         self.task = task.to_string();
+        // A new task starts a new run, so tools such as circuit breakers start afresh.
+        for tool in &self.tools {
+            tool.reset();
+        }
+        if let Some(registry) = &self.tool_registry {
+            registry.reset();
+        }
     }
     fn get_system_prompt(&self) -> &str {
         &self.system_prompt_template
@@ -366,6 +475,12 @@ impl<M: Model + Debug> Agent for MultiStepAgent<M> {
     fn model(&self) -> &dyn Model {
         &self.model
     }
+    fn set_final_answer_tool(
+        &mut self,
+        tool: Box<dyn AnyTool>,
+    ) -> Result<Option<Box<dyn AnyTool>>> {
+        MultiStepAgent::set_final_answer_tool(self, tool)
+    }
 
     /// Perform one step in the ReAct framework: the agent thinks, acts, and observes the result.
     ///
@@ -384,8 +499,8 @@ impl<M: Model> MultiStepAgent<M> {
         description: Option<&str>,
         max_steps: Option<usize>,
     ) -> Result<Self> {
-        // Initialize logger
-        log::set_logger(&LOGGER).unwrap();
+        // Initialize logger. It can only be set once per process, so later agents reuse it.
+        let _ = log::set_logger(&LOGGER);
         log::set_max_level(log::LevelFilter::Info);
 
         let name = "MultiStepAgent";
@@ -399,12 +514,18 @@ impl<M: Model> MultiStepAgent<M> {
             None => "A multi-step agent that can solve tasks using a series of tools".to_string(),
         };
 
-        let final_answer_tool = FinalAnswerTool::new();
-        tools.push(Box::new(final_answer_tool));
+        if !tools.iter().any(|tool| tool.name() == "final_answer") {
+            tools.push(Box::new(FinalAnswerTool::new()));
+        }
+        let mut names = HashSet::new();
+        if let Some(tool) = tools.iter().find(|tool| !names.insert(tool.name())) {
+            return Err(anyhow!("Two tools are named '{}'", tool.name()));
+        }
 
         let mut agent = MultiStepAgent {
             model,
             tools,
+            raw_system_prompt_template: system_prompt_template.clone(),
             system_prompt_template,
             name,
             managed_agents,
@@ -414,14 +535,129 @@ impl<M: Model> MultiStepAgent<M> {
             task: "".to_string(),
             logs: Vec::new(),
             input_messages: None,
+            tool_registry: None,
         };
 
         agent.initialize_system_prompt()?;
         Ok(agent)
     }
 
+    /// Replaces the final answer tool, e.g. with a [`StructuredFinalAnswerTool`], and renders the system prompt again.
+    ///
+    /// Returns the previous final answer tool so that it can be put back.
+    pub fn set_final_answer_tool(
+        &mut self,
+        tool: Box<dyn AnyTool>,
+    ) -> Result<Option<Box<dyn AnyTool>>> {
+        if tool.name() != "final_answer" {
+            return Err(anyhow!(
+                "The final answer tool must be named 'final_answer', got '{}'",
+                tool.name()
+            ));
+        }
+        let previous = self
+            .tools
+            .iter()
+            .position(|tool| tool.name() == "final_answer")
+            .map(|index| self.tools.remove(index));
+        self.tools.push(tool);
+        self.system_prompt_template = self.raw_system_prompt_template.clone();
+        self.initialize_system_prompt()?;
+        Ok(previous)
+    }
+
+    /// Offers the tools of the registry next to the agent's own tools, and renders the system prompt again.
+    pub fn set_tool_registry(&mut self, registry: ToolRegistry) -> Result<()> {
+        if let Some(tool) = self
+            .tools
+            .iter()
+            .find(|tool| registry.contains(tool.name()))
+        {
+            return Err(anyhow!(
+                "The registry has a tool named '{}', like one of the agent's tools",
+                tool.name()
+            ));
+        }
+        self.tool_registry = Some(registry);
+        self.system_prompt_template = self.raw_system_prompt_template.clone();
+        self.initialize_system_prompt()?;
+        Ok(())
+    }
+
+    /// The agent's own tools and the tools of the registry that its selector picks for this step. Registry tools
+    /// registered later under the name of an own tool are left out.
+    pub fn step_tool_info(&self) -> Vec<ToolInfo> {
+        let mut tools = self.tools.tool_info();
+        if let Some(registry) = &self.tool_registry {
+            let messages = self.input_messages.as_deref().unwrap_or_default();
+            let context = SelectionContext {
+                task: &self.task,
+                step: self.step_number,
+                messages,
+            };
+            let names = tools
+                .iter()
+                .map(|tool| tool.function.name)
+                .collect::<Vec<_>>();
+            let selected = registry
+                .select(&context)
+                .into_iter()
+                .filter(|tool| !names.contains(&tool.function.name))
+                .collect::<Vec<_>>();
+            tools.extend(selected);
+        }
+        tools
+    }
+
+    /// Calls one of the agent's own tools, or else a tool of the registry.
+    pub fn call_tool(&self, call: &FunctionCall) -> Result<ToolOutput, AgentError> {
+        match &self.tool_registry {
+            Some(registry) if !self.tools.iter().any(|tool| tool.name() == call.name) => {
+                registry.call_output(call)
+            }
+            _ => self.tools.call_output(call),
+        }
+    }
+
+    /// Checks an answer given in code with the final answer tool. Structured answers are passed as JSON.
+    pub fn check_final_answer(&self, answer: &str) -> Result<String, AgentError> {
+        let call = |answer: serde_json::Value| {
+            self.tools.call(&FunctionCall {
+                name: "final_answer".to_string(),
+                arguments: json!({ "answer": answer }),
+            })
+        };
+        call(serde_json::Value::String(answer.to_string())).or_else(
+            |e| match serde_json::from_str::<serde_json::Value>(answer) {
+                Ok(value) if !value.is_string() => call(value),
+                _ => Err(e),
+            },
+        )
+    }
+
+    /// Renders the system prompt of the run again with the tools offered in this step, so that it does not list
+    /// registry tools that were disabled or left out by the selector. Does nothing without a registry.
+    pub fn refresh_system_prompt(&mut self, tools: &[ToolInfo]) -> Result<()> {
+        if self.tool_registry.is_none() {
+            return Ok(());
+        }
+        self.system_prompt_template = self.raw_system_prompt_template.clone();
+        self.render_system_prompt(tools.to_vec())?;
+        if let Some(Step::SystemPromptStep(prompt)) = self.logs.first_mut() {
+            prompt.clone_from(&self.system_prompt_template);
+        }
+        Ok(())
+    }
+
     fn initialize_system_prompt(&mut self) -> Result<String> {
-        let tools = self.tools.tool_info();
+        let mut tools = self.tools.tool_info();
+        if let Some(registry) = &self.tool_registry {
+            tools.extend(registry.tool_info());
+        }
+        self.render_system_prompt(tools)
+    }
+
+    fn render_system_prompt(&mut self, tools: Vec<ToolInfo>) -> Result<String> {
         self.system_prompt_template = format_prompt_with_tools(tools, &self.system_prompt_template);
         match &self.managed_agents {
             Some(managed_agents) => {
@@ -528,6 +764,7 @@ impl<M: Model> MultiStepAgent<M> {
 
 pub struct FunctionCallingAgent<M: Model> {
     base_agent: MultiStepAgent<M>,
+    approval_policy: Option<Arc<dyn ApprovalPolicy>>,
 }
 
 impl<M: Model + Debug> FunctionCallingAgent<M> {
@@ -548,7 +785,23 @@ impl<M: Model + Debug> FunctionCallingAgent<M> {
             description,
             max_steps,
         )?;
-        Ok(Self { base_agent })
+        Ok(Self {
+            base_agent,
+            approval_policy: None,
+        })
+    }
+
+    /// Asks the policy before running any tool other than the final answer.
+    pub fn with_approval_policy(mut self, policy: impl ApprovalPolicy + 'static) -> Self {
+        self.approval_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Offers the enabled tools of the registry, as picked by its selector in each step. Keep a clone of the
+    /// registry to add, remove, enable or disable tools while the agent runs.
+    pub fn with_tool_registry(mut self, registry: ToolRegistry) -> Result<Self> {
+        self.base_agent.set_tool_registry(registry)?;
+        Ok(self)
     }
 }
 
@@ -580,6 +833,12 @@ impl<M: Model + Debug> Agent for FunctionCallingAgent<M> {
     fn model(&self) -> &dyn Model {
         self.base_agent.model()
     }
+    fn set_final_answer_tool(
+        &mut self,
+        tool: Box<dyn AnyTool>,
+    ) -> Result<Option<Box<dyn AnyTool>>> {
+        self.base_agent.set_final_answer_tool(tool)
+    }
 
     /// Perform one step in the ReAct framework: the agent thinks, acts, and observes the result.
     ///
@@ -587,27 +846,24 @@ impl<M: Model + Debug> Agent for FunctionCallingAgent<M> {
     fn step(&mut self, log_entry: &mut Step) -> Result<Option<String>> {
         match log_entry {
             Step::ActionStep(step_log) => {
-                let agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
+                let mut agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
                 self.base_agent.input_messages = Some(agent_memory.clone());
+                let tools = self.base_agent.step_tool_info();
+                if self.base_agent.tool_registry.is_some() {
+                    self.base_agent.refresh_system_prompt(&tools)?;
+                    agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
+                    self.base_agent.input_messages = Some(agent_memory.clone());
+                }
                 step_log.agent_memory = Some(agent_memory.clone());
-                let tools = self
-                    .base_agent
-                    .tools
-                    .iter()
-                    .map(|tool| tool.tool_info())
-                    .collect::<Vec<_>>();
-                let model_message = self
-                    .base_agent
-                    .model
-                    .run(
-                        self.base_agent.input_messages.as_ref().unwrap().clone(),
-                        tools,
-                        None,
-                        Some(HashMap::from([(
-                            "stop".to_string(),
-                            vec!["Observation:".to_string()],
-                        )])),
-                    )?;
+                let model_message = self.base_agent.model.run(
+                    self.base_agent.input_messages.as_ref().unwrap().clone(),
+                    tools,
+                    None,
+                    Some(HashMap::from([(
+                        "stop".to_string(),
+                        vec!["Observation:".to_string()],
+                    )])),
+                )?;
 
                 let mut observations = Vec::new();
                 let tools = model_message.get_tools_used()?;
@@ -627,23 +883,53 @@ impl<M: Model + Debug> Agent for FunctionCallingAgent<M> {
                     match function_name.as_str() {
                         "final_answer" => {
                             info!("Executing tool call: {}", function_name);
-                            let answer = self.base_agent.tools.call(&tool.function)?;
-                            self.base_agent.write_inner_memory_from_logs(None)?;
-                            return Ok(Some(answer));
+                            match self.base_agent.tools.call(&tool.function) {
+                                Ok(answer) => {
+                                    self.base_agent.write_inner_memory_from_logs(None)?;
+                                    return Ok(Some(answer));
+                                }
+                                Err(e) => {
+                                    observations.push(e.to_string());
+                                    info!("Error: {}", e);
+                                    step_log.error = Some(e);
+                                }
+                            }
                         }
                         _ => {
                             info!(
                                 "Executing tool call: {} with arguments: {:?}",
                                 function_name, tool.function.arguments
                             );
-                            let observation = self.base_agent.tools.call(&tool.function);
+                            let call = match &self.approval_policy {
+                                Some(policy) => review_call(policy.as_ref(), &tool.function),
+                                None => Ok(tool.function.clone()),
+                            };
+                            let call = match call {
+                                Ok(call) => call,
+                                Err(reason) => {
+                                    info!("{}", reason);
+                                    observations.push(format!(
+                                        "Observation from {}: {}",
+                                        function_name, reason
+                                    ));
+                                    continue;
+                                }
+                            };
+                            let observation = self.base_agent.call_tool(&call);
                             match observation {
                                 Ok(observation) => {
                                     observations.push(format!(
                                         "Observation from {}: {}",
                                         function_name,
-                                        observation.chars().take(30000).collect::<String>()
+                                        observation
+                                            .render()
+                                            .chars()
+                                            .take(30000)
+                                            .collect::<String>()
                                     ));
+                                    if let Some(artifact) = observation.artifact() {
+                                        step_log.artifacts.push(artifact.clone());
+                                    }
                                 }
                                 Err(e) => {
                                     observations.push(e.to_string());
@@ -707,6 +993,85 @@ impl<M: Model> CodeAgent<M> {
             local_python_interpreter,
         })
     }
+
+    /// Asks the policy before the generated code runs any of the agent's tools.
+    pub fn with_approval_policy(mut self, policy: impl ApprovalPolicy + 'static) -> Self {
+        self.local_python_interpreter
+            .set_approval_policy(Arc::new(policy));
+        self
+    }
+
+    /// Offers the enabled tools of the registry to the generated code, as picked by its selector in each step.
+    ///
+    /// Python can only call tools whose names are identifiers, so build the registry with
+    /// [`ToolRegistry::with_separator`]`("_")` when it uses namespaces. Tools registered later under another name are
+    /// left out with a warning.
+    pub fn with_tool_registry(mut self, registry: ToolRegistry) -> Result<Self> {
+        if let Some(name) = registry
+            .names()
+            .into_iter()
+            .find(|name| !is_python_identifier(name))
+        {
+            return Err(anyhow!(
+                "The tool '{}' can not be called from python, register it with the '_' separator",
+                name
+            ));
+        }
+        self.base_agent.set_tool_registry(registry)?;
+        Ok(self)
+    }
+
+    /// Offers the agent's tools and the registry tools selected for this step to the system prompt and the
+    /// interpreter.
+    fn update_step_tools(&mut self) -> Result<()> {
+        let Some(registry) = self.base_agent.tool_registry.clone() else {
+            return Ok(());
+        };
+        let tools = self
+            .base_agent
+            .step_tool_info()
+            .into_iter()
+            .filter(|tool| {
+                let callable = is_python_identifier(tool.function.name);
+                if !callable {
+                    log::warn!(
+                        "The tool '{}' can not be called from python and is left out",
+                        tool.function.name
+                    );
+                }
+                callable
+            })
+            .collect::<Vec<_>>();
+        self.base_agent.refresh_system_prompt(&tools)?;
+        let names = tools
+            .iter()
+            .map(|tool| tool.function.name)
+            .collect::<Vec<_>>();
+        let mut custom_tools = self
+            .base_agent
+            .tools
+            .iter()
+            .map(|tool| tool.clone_box())
+            .collect::<Vec<_>>();
+        custom_tools.extend(registry.enabled_tools().into_iter().filter(|tool| {
+            names.contains(&tool.name())
+                && !self
+                    .base_agent
+                    .tools
+                    .iter()
+                    .any(|own| own.name() == tool.name())
+        }));
+        self.local_python_interpreter.set_custom_tools(custom_tools);
+        Ok(())
+    }
+}
+
+/// Whether python code can call a function with this name.
+#[cfg(feature = "code-agent")]
+fn is_python_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
 }
 
 #[cfg(feature = "code-agent")]
@@ -738,11 +1103,22 @@ impl<M: Model + Debug> Agent for CodeAgent<M> {
     fn model(&self) -> &dyn Model {
         self.base_agent.model()
     }
+    fn set_final_answer_tool(
+        &mut self,
+        tool: Box<dyn AnyTool>,
+    ) -> Result<Option<Box<dyn AnyTool>>> {
+        self.base_agent.set_final_answer_tool(tool)
+    }
     fn step(&mut self, log_entry: &mut Step) -> Result<Option<String>> {
         match log_entry {
             Step::ActionStep(step_log) => {
-                let agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
+                let mut agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
                 self.base_agent.input_messages = Some(agent_memory.clone());
+                if self.base_agent.tool_registry.is_some() {
+                    self.update_step_tools()?;
+                    agent_memory = self.base_agent.write_inner_memory_from_logs(None)?;
+                    self.base_agent.input_messages = Some(agent_memory.clone());
+                }
                 step_log.agent_memory = Some(agent_memory);
 
                 let llm_output = self.base_agent.model.run(
@@ -777,6 +1153,7 @@ impl<M: Model + Debug> Agent for CodeAgent<M> {
                     },
                 }]);
                 let result = self.local_python_interpreter.forward(&code);
+                step_log.artifacts = self.local_python_interpreter.take_artifacts();
                 match result {
                     Ok(result) => {
                         let (result, execution_logs) = result;
@@ -795,7 +1172,13 @@ impl<M: Model + Debug> Agent for CodeAgent<M> {
                     }
                     Err(e) => match e {
                         InterpreterError::FinalAnswer(answer) => {
-                            return Ok(Some(answer));
+                            match self.base_agent.check_final_answer(&answer) {
+                                Ok(answer) => return Ok(Some(answer)),
+                                Err(e) => {
+                                    info!("Error: {}", e);
+                                    step_log.error = Some(e);
+                                }
+                            }
                         }
                         _ => {
                             step_log.error = Some(AgentError::Execution(e.to_string()));
@@ -847,3 +1230,404 @@ pub fn parse_code_blobs(code_blob: &str) -> Result<String, AgentError> {
 
     Ok(matches.join("\n\n"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::ApprovalDecision;
+    use crate::models::scripted::ScriptedModel;
+    use crate::testing::{
+        assert_no_errors, assert_tool_called, called_tools, errors, observations,
+    };
+    use crate::tools::{Tool, ToolOutput};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct EchoToolParams {
+        text: String,
+    }
+
+    #[derive(Debug, Clone)]
+    struct EchoTool;
+
+    impl Tool for EchoTool {
+        type Params = EchoToolParams;
+        fn name(&self) -> &'static str {
+            "echo"
+        }
+        fn description(&self) -> &'static str {
+            "Echoes the given text."
+        }
+        fn forward(&self, arguments: EchoToolParams) -> Result<String> {
+            Ok(arguments.text)
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct PlotToolParams {
+        title: String,
+    }
+
+    /// Returns a fake chart image named after the title.
+    #[derive(Debug, Clone)]
+    struct PlotTool;
+
+    impl Tool for PlotTool {
+        type Params = PlotToolParams;
+        fn name(&self) -> &'static str {
+            "plot"
+        }
+        fn description(&self) -> &'static str {
+            "Plots a chart."
+        }
+        fn forward(&self, arguments: PlotToolParams) -> Result<String> {
+            Ok(self.forward_output(arguments)?.render())
+        }
+        fn forward_output(&self, arguments: PlotToolParams) -> Result<ToolOutput> {
+            Ok(ToolOutput::from(Artifact::new(
+                format!("{}.png", arguments.title),
+                "image/png",
+                vec![0; 4],
+            )))
+        }
+    }
+
+    #[test]
+    fn test_function_calling_agent_loop() {
+        let model = ScriptedModel::new()
+            .with_tool_call("echo", json!({"text": "hello"}))
+            .with_tool_call("final_answer", json!({"answer": "The tool said hello"}));
+        let mut agent = FunctionCallingAgent::new(
+            model.clone(),
+            vec![Box::new(EchoTool)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let answer = agent.run("Call the echo tool", false, true).unwrap();
+        assert_eq!(answer, "The tool said hello");
+
+        let logs = agent.get_logs_mut();
+        assert_no_errors(logs);
+        assert_tool_called(logs, "echo");
+        assert_eq!(called_tools(logs), vec!["echo"]);
+        assert_eq!(observations(logs), vec!["Observation from echo: hello"]);
+
+        let calls = model.calls();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].tools, vec!["echo", "final_answer"]);
+        assert!(calls[1]
+            .messages
+            .iter()
+            .any(|message| message.content.contains("Observation from echo: hello")));
+        assert_eq!(model.remaining(), 0);
+    }
+
+    #[test]
+    fn test_function_calling_agent_artifacts() {
+        let model = ScriptedModel::new()
+            .with_tool_call("plot", json!({"title": "sales"}))
+            .with_tool_call("final_answer", json!({"answer": "See the chart"}));
+        let mut agent =
+            FunctionCallingAgent::new(model, vec![Box::new(PlotTool)], None, None, None, None)
+                .unwrap();
+        let result = agent.run_with_artifacts("Plot the sales", true).unwrap();
+        assert_eq!(result.answer, "See the chart");
+        assert_eq!(
+            result.artifacts,
+            vec![Artifact::new("sales.png", "image/png", vec![0; 4])]
+        );
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["Observation from plot: [Image artifact: sales.png (image/png, 4 bytes)]"]
+        );
+
+        // A new run only returns its own artifacts.
+        let model = ScriptedModel::new().with_tool_call("final_answer", json!({"answer": "none"}));
+        let mut agent =
+            FunctionCallingAgent::new(model, vec![Box::new(PlotTool)], None, None, None, None)
+                .unwrap();
+        assert!(agent
+            .run_with_artifacts("Answer", true)
+            .unwrap()
+            .artifacts
+            .is_empty());
+    }
+
+    #[test]
+    fn test_function_calling_agent_tool_registry() {
+        let registry = ToolRegistry::new();
+        registry.register_in("util", Box::new(EchoTool)).unwrap();
+        registry.register(Box::new(PlotTool)).unwrap();
+        let model = ScriptedModel::new()
+            .with_tool_call("util.echo", json!({"text": "hello"}))
+            .with_tool_call("final_answer", json!({"answer": "hello"}))
+            .with_tool_call("util.echo", json!({"text": "again"}))
+            .with_tool_call("final_answer", json!({"answer": "done"}));
+        let mut agent = FunctionCallingAgent::new(model.clone(), vec![], None, None, None, None)
+            .unwrap()
+            .with_tool_registry(registry.clone())
+            .unwrap();
+        assert!(agent.get_system_prompt().contains("util.echo, plot"));
+        assert_eq!(agent.run("Echo hello", false, true).unwrap(), "hello");
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["Observation from util.echo: hello"]
+        );
+        assert_eq!(
+            model.calls()[0].tools,
+            vec!["final_answer", "util.echo", "plot"]
+        );
+
+        // Tools disabled between runs are no longer offered, and calling them fails.
+        registry.disable("util.echo").unwrap();
+        assert_eq!(agent.run("Echo again", false, true).unwrap(), "done");
+        assert_eq!(model.calls()[2].tools, vec!["final_answer", "plot"]);
+        let system_prompt = &model.calls()[2].messages[0].content;
+        assert!(system_prompt.contains("following tools: final_answer, plot\n"));
+        assert!(!system_prompt.contains("util.echo"));
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["The tool util.echo is disabled. Use another tool."]
+        );
+
+        let registry = ToolRegistry::new();
+        registry.register(Box::new(EchoTool)).unwrap();
+        assert!(FunctionCallingAgent::new(
+            ScriptedModel::new(),
+            vec![Box::new(EchoTool)],
+            None,
+            None,
+            None,
+            None
+        )
+        .unwrap()
+        .with_tool_registry(registry)
+        .is_err());
+        assert!(FunctionCallingAgent::new(
+            ScriptedModel::new(),
+            vec![Box::new(EchoTool), Box::new(EchoTool)],
+            None,
+            None,
+            None,
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_function_calling_agent_approval_policy() {
+        let model = ScriptedModel::new()
+            .with_tool_call("echo", json!({"text": "secret"}))
+            .with_tool_call("echo", json!({"text": "hello"}))
+            .with_tool_call("final_answer", json!({"answer": "done"}));
+        let policy = |call: &FunctionCall| {
+            if call.arguments["text"] == "secret" {
+                ApprovalDecision::Deny("no secrets".to_string())
+            } else {
+                ApprovalDecision::Edit(json!({"text": "edited"}))
+            }
+        };
+        let mut agent =
+            FunctionCallingAgent::new(model, vec![Box::new(EchoTool)], None, None, None, None)
+                .unwrap()
+                .with_approval_policy(policy);
+        assert_eq!(agent.run("Echo something", false, true).unwrap(), "done");
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec![
+                "Observation from echo: The call to echo was denied: no secrets",
+                "Observation from echo: edited",
+            ]
+        );
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_code_agent_approval_policy() {
+        let model = ScriptedModel::new()
+            .with_text("Code:\n```py\nprint(echo(text='hello'))\n```")
+            .with_text("Code:\n```py\nfinal_answer('done')\n```");
+        let policy = |_: &FunctionCall| ApprovalDecision::Deny("read only".to_string());
+        let mut agent = CodeAgent::new(model, vec![Box::new(EchoTool)], None, None, None, None)
+            .unwrap()
+            .with_approval_policy(policy);
+        assert_eq!(agent.run("Echo hello", false, true).unwrap(), "done");
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["Execution logs: Error: The call to echo was denied: read only"]
+        );
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_code_agent_tool_registry() {
+        let registry = ToolRegistry::new().with_separator("_");
+        registry.register_in("util", Box::new(EchoTool)).unwrap();
+        let model = ScriptedModel::new()
+            .with_text("Code:\n```py\nprint(util_echo(text='hello'))\n```")
+            .with_text("Code:\n```py\nfinal_answer('done')\n```")
+            .with_text("Code:\n```py\nprint(util_echo(text='again'))\n```")
+            .with_text("Code:\n```py\nfinal_answer('done')\n```");
+        let mut agent = CodeAgent::new(model.clone(), vec![], None, None, None, None)
+            .unwrap()
+            .with_tool_registry(registry.clone())
+            .unwrap();
+        assert_eq!(agent.run("Echo hello", false, true).unwrap(), "done");
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["Execution logs: hello"]
+        );
+        assert!(model.calls()[0].messages[0].content.contains("util_echo"));
+
+        // Tools disabled between runs are no longer offered to the code.
+        registry.disable("util_echo").unwrap();
+        assert_eq!(agent.run("Echo again", false, true).unwrap(), "done");
+        assert!(!model.calls()[2].messages[0].content.contains("util_echo"));
+        assert_eq!(errors(agent.get_logs_mut()).len(), 1);
+
+        let registry = ToolRegistry::new();
+        registry.register_in("util", Box::new(EchoTool)).unwrap();
+        assert!(
+            CodeAgent::new(ScriptedModel::new(), vec![], None, None, None, None)
+                .unwrap()
+                .with_tool_registry(registry)
+                .is_err()
+        );
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_code_agent_artifacts() {
+        let model = ScriptedModel::new()
+            .with_text("Code:\n```py\nchart = plot(title='sales')\nprint(chart['name'])\n```")
+            .with_text("Code:\n```py\nfinal_answer('done')\n```");
+        let mut agent =
+            CodeAgent::new(model, vec![Box::new(PlotTool)], None, None, None, None).unwrap();
+        let result = agent.run_with_artifacts("Plot the sales", true).unwrap();
+        assert_eq!(result.answer, "done");
+        assert_eq!(result.artifacts.len(), 1);
+        assert_eq!(result.artifacts[0].name, "sales.png");
+        assert_eq!(
+            observations(agent.get_logs_mut()),
+            vec!["Execution logs: sales.png"]
+        );
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct City {
+        name: String,
+        population: u64,
+    }
+
+    #[test]
+    fn test_function_calling_agent_run_typed() {
+        let model = ScriptedModel::new()
+            .with_tool_call("final_answer", json!({"answer": {"name": "Paris"}}))
+            .with_tool_call(
+                "final_answer",
+                json!({"answer": {"name": "Paris", "population": 2100000}}),
+            );
+        let mut agent =
+            FunctionCallingAgent::new(model.clone(), vec![], None, None, None, None).unwrap();
+        let city = agent
+            .run_typed::<City>("What is the capital of France?", true)
+            .unwrap();
+        assert_eq!(
+            city,
+            City {
+                name: "Paris".to_string(),
+                population: 2100000,
+            }
+        );
+
+        let logs = agent.get_logs_mut();
+        let errors = errors(logs);
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .message()
+            .contains("`answer.population` is required"));
+        assert!(model.calls()[1]
+            .messages
+            .iter()
+            .any(|message| message.content.contains("`answer.population` is required")));
+
+        // The final answer schema is only shown in the system prompt during the typed run.
+        assert!(model.calls()[0].messages[0].content.contains("uint64"));
+        assert!(!agent.get_system_prompt().contains("uint64"));
+        let model = ScriptedModel::new().with_tool_call("final_answer", json!({"answer": "Paris"}));
+        let mut agent = FunctionCallingAgent::new(model, vec![], None, None, None, None).unwrap();
+        assert_eq!(agent.run("Capital?", false, true).unwrap(), "Paris");
+    }
+
+    #[derive(Debug, Clone)]
+    struct SignedFinalAnswerTool;
+
+    impl Tool for SignedFinalAnswerTool {
+        type Params = EchoToolParams;
+        fn name(&self) -> &'static str {
+            "final_answer"
+        }
+        fn description(&self) -> &'static str {
+            "Provides a signed final answer."
+        }
+        fn forward(&self, arguments: EchoToolParams) -> Result<String> {
+            Ok(format!("{} (signed)", arguments.text))
+        }
+    }
+
+    #[test]
+    fn test_run_typed_restores_a_custom_final_answer_tool() {
+        let model = ScriptedModel::new().with_tool_call(
+            "final_answer",
+            json!({"answer": {"name": "Paris", "population": 2100000}}),
+        );
+        let mut agent = FunctionCallingAgent::new(
+            model,
+            vec![Box::new(SignedFinalAnswerTool)],
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        assert!(agent.get_system_prompt().contains("signed final answer"));
+        agent
+            .run_typed::<City>("What is the capital of France?", true)
+            .unwrap();
+        assert!(agent.get_system_prompt().contains("signed final answer"));
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_code_agent_run_typed() {
+        let model = ScriptedModel::new()
+            .with_text("Code:\n```py\nfinal_answer('Paris')\n```")
+            .with_text("Code:\n```py\nfinal_answer({'name': 'Paris', 'population': 2100000})\n```");
+        let mut agent = CodeAgent::new(model, vec![], None, None, None, None).unwrap();
+        let city = agent
+            .run_typed::<City>("What is the capital of France?", true)
+            .unwrap();
+        assert_eq!(city.population, 2100000);
+        assert_eq!(errors(agent.get_logs_mut()).len(), 1);
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_code_agent_loop() {
+        let model = ScriptedModel::new()
+            .with_text("Thought: Compute it.\nCode:\n```py\nx = 20 + 22\nprint(x)\n```")
+            .with_text("Thought: Done.\nCode:\n```py\nfinal_answer('42')\n```");
+        let mut agent = CodeAgent::new(model.clone(), vec![], None, None, None, None).unwrap();
+        let answer = agent.run("What is 20 + 22?", false, true).unwrap();
+        assert_eq!(answer, "42");
+
+        let logs = agent.get_logs_mut();
+        assert_no_errors(logs);
+        assert_eq!(observations(logs), vec!["Execution logs: 42"]);
+        assert_eq!(model.calls().len(), 2);
+    }
+}
@@ -0,0 +1,207 @@
+//! This module contains approval policies. A policy is consulted before an agent runs a tool and can
+//! approve the call, deny it with a reason that is fed back to the model, or replace its arguments.
+//!
+//! Use [`CliApprovalPolicy`] to ask a human on the terminal before sensitive tools run.
+
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::sync::Mutex;
+
+use serde_json::Value;
+
+use crate::models::openai::FunctionCall;
+
+/// The outcome of reviewing a tool call.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApprovalDecision {
+    /// Run the tool with the proposed arguments.
+    Approve,
+    /// Do not run the tool. The reason is returned to the model as the observation.
+    Deny(String),
+    /// Run the tool with these arguments instead.
+    Edit(Value),
+}
+
+/// A policy that reviews tool calls before they are executed.
+pub trait ApprovalPolicy {
+    fn review(&self, call: &FunctionCall) -> ApprovalDecision;
+}
+
+impl<F: Fn(&FunctionCall) -> ApprovalDecision> ApprovalPolicy for F {
+    fn review(&self, call: &FunctionCall) -> ApprovalDecision {
+        self(call)
+    }
+}
+
+/// Reviews the call with the policy. Returns the call to execute, or the reason it was denied.
+pub fn review_call(
+    policy: &dyn ApprovalPolicy,
+    call: &FunctionCall,
+) -> std::result::Result<FunctionCall, String> {
+    match policy.review(call) {
+        ApprovalDecision::Approve => Ok(call.clone()),
+        ApprovalDecision::Deny(reason) => {
+            Err(format!("The call to {} was denied: {}", call.name, reason))
+        }
+        ApprovalDecision::Edit(arguments) => Ok(FunctionCall {
+            name: call.name.clone(),
+            arguments,
+        }),
+    }
+}
+
+/// Asks on the terminal before tools run.
+///
+/// Answer `y` to approve, `n` to deny with an optional reason, or `e` to enter new arguments as JSON.
+pub struct CliApprovalPolicy {
+    tools: Option<HashSet<String>>,
+    io: Mutex<(Box<dyn BufRead + Send>, Box<dyn Write + Send>)>,
+}
+
+impl Default for CliApprovalPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CliApprovalPolicy {
+    /// Prompts on stdin for every tool call.
+    pub fn new() -> Self {
+        Self::with_io(io::BufReader::new(io::stdin()), io::stdout())
+    }
+
+    /// Prompts using the given reader and writer instead of stdin and stdout.
+    pub fn with_io(
+        reader: impl BufRead + Send + 'static,
+        writer: impl Write + Send + 'static,
+    ) -> Self {
+        Self {
+            tools: None,
+            io: Mutex::new((Box::new(reader), Box::new(writer))),
+        }
+    }
+
+    /// Only prompts for the named tools. Calls to other tools are approved.
+    pub fn only_for(mut self, tools: &[&str]) -> Self {
+        self.tools = Some(tools.iter().map(|tool| tool.to_string()).collect());
+        self
+    }
+
+    fn prompt(
+        &self,
+        reader: &mut dyn BufRead,
+        writer: &mut dyn Write,
+        call: &FunctionCall,
+    ) -> io::Result<ApprovalDecision> {
+        writeln!(
+            writer,
+            "The agent wants to call {} with arguments:\n{}",
+            call.name,
+            serde_json::to_string_pretty(&call.arguments).unwrap_or_default()
+        )?;
+        loop {
+            write!(writer, "Approve? [y]es / [n]o / [e]dit: ")?;
+            writer.flush()?;
+            let Some(answer) = read_line(reader)? else {
+                return Ok(ApprovalDecision::Deny(
+                    "No answer was given by the user".to_string(),
+                ));
+            };
+            match answer.to_lowercase().as_str() {
+                "y" | "yes" => return Ok(ApprovalDecision::Approve),
+                "n" | "no" => {
+                    write!(writer, "Reason (optional): ")?;
+                    writer.flush()?;
+                    let reason = read_line(reader)?.unwrap_or_default();
+                    return Ok(ApprovalDecision::Deny(if reason.is_empty() {
+                        "The user did not approve this call".to_string()
+                    } else {
+                        reason
+                    }));
+                }
+                "e" | "edit" => {
+                    write!(writer, "New arguments as JSON: ")?;
+                    writer.flush()?;
+                    let arguments = read_line(reader)?.unwrap_or_default();
+                    match serde_json::from_str::<Value>(&arguments) {
+                        Ok(arguments) => return Ok(ApprovalDecision::Edit(arguments)),
+                        Err(e) => writeln!(writer, "Invalid JSON: {}", e)?,
+                    }
+                }
+                _ => writeln!(writer, "Please answer y, n or e.")?,
+            }
+        }
+    }
+}
+
+fn read_line(reader: &mut dyn BufRead) -> io::Result<Option<String>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim().to_string()))
+}
+
+impl ApprovalPolicy for CliApprovalPolicy {
+    fn review(&self, call: &FunctionCall) -> ApprovalDecision {
+        if let Some(tools) = &self.tools {
+            if !tools.contains(&call.name) {
+                return ApprovalDecision::Approve;
+            }
+        }
+        let mut io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        let (reader, writer) = &mut *io;
+        self.prompt(reader.as_mut(), writer.as_mut(), call)
+            .unwrap_or_else(|e| {
+                ApprovalDecision::Deny(format!("Could not ask for approval: {}", e))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn call() -> FunctionCall {
+        FunctionCall {
+            name: "write_file".to_string(),
+            arguments: json!({"path": "notes.txt"}),
+        }
+    }
+
+    fn cli(input: &str) -> CliApprovalPolicy {
+        CliApprovalPolicy::with_io(io::Cursor::new(input.to_string()), io::sink())
+    }
+
+    #[test]
+    fn test_cli_approval_policy() {
+        assert_eq!(cli("y\n").review(&call()), ApprovalDecision::Approve);
+        assert_eq!(
+            cli("maybe\nn\ntoo risky\n").review(&call()),
+            ApprovalDecision::Deny("too risky".to_string())
+        );
+        assert_eq!(
+            cli("e\nnot json\ne\n{\"path\": \"draft.txt\"}\n").review(&call()),
+            ApprovalDecision::Edit(json!({"path": "draft.txt"}))
+        );
+        assert!(matches!(cli("").review(&call()), ApprovalDecision::Deny(_)));
+        assert_eq!(
+            cli("").only_for(&["delete_file"]).review(&call()),
+            ApprovalDecision::Approve
+        );
+    }
+
+    #[test]
+    fn test_review_call() {
+        let deny = |_: &FunctionCall| ApprovalDecision::Deny("read only".to_string());
+        assert_eq!(
+            review_call(&deny, &call()).unwrap_err(),
+            "The call to write_file was denied: read only"
+        );
+        let edit = |_: &FunctionCall| ApprovalDecision::Edit(json!({"path": "draft.txt"}));
+        let edited = review_call(&edit, &call()).unwrap();
+        assert_eq!(edited.name, "write_file");
+        assert_eq!(edited.arguments, json!({"path": "draft.txt"}));
+    }
+}
@@ -3,15 +3,15 @@ use clap::{Parser, ValueEnum};
 use colored::*;
 use smolagents_rs::agents::Step;
 use smolagents_rs::agents::{Agent, CodeAgent, FunctionCallingAgent};
-use smolagents_rs::errors::AgentError;
-use smolagents_rs::models::model_traits::{Model, ModelResponse};
-use smolagents_rs::models::ollama::{OllamaModel, OllamaModelBuilder};
+use smolagents_rs::approval::CliApprovalPolicy;
+use smolagents_rs::models::fallback::FallbackModel;
+use smolagents_rs::models::model_traits::Model;
+use smolagents_rs::models::ollama::OllamaModelBuilder;
 use smolagents_rs::models::openai::OpenAIServerModel;
-use smolagents_rs::models::types::Message;
 use smolagents_rs::tools::{
-    AnyTool, DuckDuckGoSearchTool, GoogleSearchTool, ToolInfo, VisitWebsiteTool,
+    AnyTool, DuckDuckGoSearchTool, GoogleSearchTool, VisitWebsiteTool, WebClient, WebClientConfig,
+    WikipediaTool,
 };
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self, Write};
 
@@ -26,6 +26,7 @@ enum ToolType {
     DuckDuckGo,
     VisitWebsite,
     GoogleSearchTool,
+    Wikipedia,
 }
 
 #[derive(Debug, Clone, ValueEnum)]
@@ -34,15 +35,9 @@ enum ModelType {
     Ollama,
 }
 
-#[derive(Debug)]
-enum ModelWrapper {
-    OpenAI(OpenAIServerModel),
-    Ollama(OllamaModel),
-}
-
 enum AgentWrapper {
-    FunctionCalling(FunctionCallingAgent<ModelWrapper>),
-    Code(CodeAgent<ModelWrapper>),
+    FunctionCalling(FunctionCallingAgent<Box<dyn Model>>),
+    Code(CodeAgent<Box<dyn Model>>),
 }
 
 impl AgentWrapper {
@@ -59,21 +54,6 @@ impl AgentWrapper {
         }
     }
 }
-impl Model for ModelWrapper {
-    fn run(
-        &self,
-        messages: Vec<Message>,
-        tools: Vec<ToolInfo>,
-        max_tokens: Option<usize>,
-        args: Option<HashMap<String, Vec<String>>>,
-    ) -> Result<Box<dyn ModelResponse>, AgentError> {
-        match self {
-            ModelWrapper::OpenAI(m) => Ok(m.run(messages, tools, max_tokens, args)?),
-            ModelWrapper::Ollama(m) => Ok(m.run(messages, tools, max_tokens, args)?),
-        }
-    }
-}
-
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
@@ -104,55 +84,114 @@ struct Args {
     /// Base URL for the API
     #[arg(short, long)]
     base_url: Option<String>,
+
+    /// Model IDs to fall back to, in order, when the primary model fails
+    #[arg(long, value_delimiter = ',')]
+    fallback_model_ids: Vec<String>,
+
+    /// Ask for approval before running tools. Without tool names every tool call needs approval
+    #[arg(long, value_delimiter = ',', num_args = 0..)]
+    require_approval: Option<Vec<String>>,
+
+    /// Proxy for the web tools, e.g. "http://proxy:8080" or "socks5://127.0.0.1:1080"
+    #[arg(long)]
+    proxy: Option<String>,
+
+    /// Only let the web tools access these domains and their subdomains
+    #[arg(long, value_delimiter = ',')]
+    allow_domains: Vec<String>,
+
+    /// Never let the web tools access these domains and their subdomains
+    #[arg(long, value_delimiter = ',')]
+    deny_domains: Vec<String>,
+
+    /// Make the web tools respect robots.txt
+    #[arg(long, default_value = "false")]
+    respect_robots_txt: bool,
 }
 
-fn create_tool(tool_type: &ToolType) -> Box<dyn AnyTool> {
+fn create_tool(tool_type: &ToolType, client: &WebClient) -> Box<dyn AnyTool> {
     match tool_type {
-        ToolType::DuckDuckGo => Box::new(DuckDuckGoSearchTool::new()),
-        ToolType::VisitWebsite => Box::new(VisitWebsiteTool::new()),
-        ToolType::GoogleSearchTool => Box::new(GoogleSearchTool::new(None)),
+        ToolType::DuckDuckGo => {
+            Box::new(DuckDuckGoSearchTool::new().with_web_client(client.clone()))
+        }
+        ToolType::VisitWebsite => Box::new(VisitWebsiteTool::new().with_web_client(client.clone())),
+        ToolType::GoogleSearchTool => {
+            Box::new(GoogleSearchTool::new(None).with_web_client(client.clone()))
+        }
+        ToolType::Wikipedia => Box::new(WikipediaTool::new().with_web_client(client.clone())),
     }
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
-    let tools: Vec<Box<dyn AnyTool>> = args.tools.iter().map(create_tool).collect();
+    let web_client = WebClient::new(WebClientConfig {
+        proxy: args.proxy.clone(),
+        allowed_domains: args.allow_domains.clone(),
+        denied_domains: args.deny_domains.clone(),
+        respect_robots_txt: args.respect_robots_txt,
+        ..Default::default()
+    })?;
+
+    let tools: Vec<Box<dyn AnyTool>> = args
+        .tools
+        .iter()
+        .map(|tool_type| create_tool(tool_type, &web_client))
+        .collect();
 
     // Create model based on type
-    let model = match args.model_type {
-        ModelType::OpenAI => ModelWrapper::OpenAI(OpenAIServerModel::new(
-            args.base_url.as_deref(),
-            Some(&args.model_id),
-            None,
-            args.api_key,
-        )),
-        ModelType::Ollama => ModelWrapper::Ollama(
-            OllamaModelBuilder::new()
-                .model_id(&args.model_id)
-                .ctx_length(8000)
-                .build(),
-        ),
+    let create_model = |model_id: &str| -> Box<dyn Model> {
+        match args.model_type {
+            ModelType::OpenAI => Box::new(OpenAIServerModel::new(
+                args.base_url.as_deref(),
+                Some(model_id),
+                None,
+                args.api_key.clone(),
+            )),
+            ModelType::Ollama => Box::new(
+                OllamaModelBuilder::new()
+                    .model_id(model_id)
+                    .ctx_length(8000)
+                    .build(),
+            ),
+        }
     };
+    let model = if args.fallback_model_ids.is_empty() {
+        create_model(&args.model_id)
+    } else {
+        let models = std::iter::once(&args.model_id)
+            .chain(&args.fallback_model_ids)
+            .map(|model_id| create_model(model_id))
+            .collect();
+        Box::new(FallbackModel::new(models))
+    };
+
+    let approval_policy = args.require_approval.as_ref().map(|tools| {
+        if tools.is_empty() {
+            CliApprovalPolicy::new()
+        } else {
+            CliApprovalPolicy::new().only_for(&tools.iter().map(String::as_str).collect::<Vec<_>>())
+        }
+    });
 
     // Create agent based on type
     let mut agent = match args.agent_type {
-        AgentType::FunctionCalling => AgentWrapper::FunctionCalling(FunctionCallingAgent::new(
-            model,
-            tools,
-            None,
-            None,
-            Some("CLI Agent"),
-            None,
-        )?),
-        AgentType::Code => AgentWrapper::Code(CodeAgent::new(
-            model,
-            tools,
-            None,
-            None,
-            Some("CLI Agent"),
-            None,
-        )?),
+        AgentType::FunctionCalling => {
+            let agent =
+                FunctionCallingAgent::new(model, tools, None, None, Some("CLI Agent"), None)?;
+            AgentWrapper::FunctionCalling(match approval_policy {
+                Some(policy) => agent.with_approval_policy(policy),
+                None => agent,
+            })
+        }
+        AgentType::Code => {
+            let agent = CodeAgent::new(model, tools, None, None, Some("CLI Agent"), None)?;
+            AgentWrapper::Code(match approval_policy {
+                Some(policy) => agent.with_approval_policy(policy),
+                None => agent,
+            })
+        }
     };
 
     let mut file: File = File::create("logs.txt")?;
@@ -0,0 +1,572 @@
+//! This module contains a record/replay layer for model and tool traffic.
+//!
+//! A [`Cassette`] wraps any [`Model`] and any [`AnyTool`]. In record mode every request/response pair is
+//! written to a JSON cassette file. In replay mode the responses are served from the file, keyed by a hash
+//! of the request, so a real session can be captured once and replayed in regression tests forever.
+//!
+//! ```rust,no_run
+//! use smolagents_rs::agents::{Agent, FunctionCallingAgent};
+//! use smolagents_rs::cassette::{Cassette, CassetteMode};
+//! use smolagents_rs::models::openai::OpenAIServerModel;
+//! use smolagents_rs::tools::{AnyTool, DuckDuckGoSearchTool};
+//!
+//! // Records on the first run and replays on every run after that.
+//! let cassette = Cassette::open("tests/cassettes/search.json", CassetteMode::Auto).unwrap();
+//! let model = if cassette.mode() == CassetteMode::Record {
+//!     cassette.wrap_model(OpenAIServerModel::new(None, None, None, None))
+//! } else {
+//!     cassette.replay_model()
+//! };
+//! let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(DuckDuckGoSearchTool::new())];
+//! let mut agent =
+//!     FunctionCallingAgent::new(model, cassette.wrap_tools(tools), None, None, None, None).unwrap();
+//! agent.run("What is the capital of France?", false, true).unwrap();
+//! ```
+//!
+//! The system prompt contains the current time, so replay falls back to serving the recorded interactions
+//! in order when no key matches. Use [`Cassette::strict`] to disable the fallback.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use crate::errors::AgentError;
+use crate::models::model_traits::{Model, ModelResponse};
+use crate::models::openai::ToolCall;
+use crate::models::types::Message;
+use crate::tools::{AnyTool, ToolInfo, ToolOutput};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Forward every call and record the request/response pairs.
+    Record,
+    /// Serve every call from the cassette file.
+    Replay,
+    /// Replay if the cassette file exists, record otherwise.
+    Auto,
+}
+
+/// A recorded model call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ModelInteraction {
+    pub key: String,
+    pub messages: Vec<Message>,
+    pub tools: Vec<String>,
+    pub response: Option<String>,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+/// A recorded tool call.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolInteraction {
+    pub key: String,
+    pub tool: String,
+    pub arguments: Value,
+    pub output: Result<ToolOutput, AgentError>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    model: Vec<ModelInteraction>,
+    tools: Vec<ToolInteraction>,
+}
+
+#[derive(Debug)]
+struct CassetteState {
+    path: PathBuf,
+    mode: CassetteMode,
+    strict: bool,
+    file: CassetteFile,
+    used_model: Vec<bool>,
+    used_tools: Vec<bool>,
+}
+
+/// A handle to a cassette file. Clones share the same recordings.
+#[derive(Debug, Clone)]
+pub struct Cassette {
+    state: Arc<Mutex<CassetteState>>,
+}
+
+impl Cassette {
+    /// Opens a cassette. Record mode starts from an empty cassette, replay mode requires the file to exist.
+    pub fn open(path: impl AsRef<Path>, mode: CassetteMode) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mode = match mode {
+            CassetteMode::Auto if path.exists() => CassetteMode::Replay,
+            CassetteMode::Auto => CassetteMode::Record,
+            mode => mode,
+        };
+        let file = match mode {
+            CassetteMode::Replay => {
+                let content = std::fs::read_to_string(&path).map_err(|e| {
+                    anyhow::anyhow!("Failed to read cassette {}: {}", path.display(), e)
+                })?;
+                serde_json::from_str(&content).map_err(|e| {
+                    anyhow::anyhow!("Failed to parse cassette {}: {}", path.display(), e)
+                })?
+            }
+            _ => CassetteFile::default(),
+        };
+        Ok(Self {
+            state: Arc::new(Mutex::new(CassetteState {
+                path,
+                mode,
+                strict: false,
+                used_model: vec![false; file.model.len()],
+                used_tools: vec![false; file.tools.len()],
+                file,
+            })),
+        })
+    }
+
+    pub fn record(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, CassetteMode::Record)
+    }
+
+    pub fn replay(path: impl AsRef<Path>) -> Result<Self> {
+        Self::open(path, CassetteMode::Replay)
+    }
+
+    /// Only serve interactions whose key matches exactly, instead of falling back to the recorded order.
+    pub fn strict(self) -> Self {
+        self.state.lock().unwrap().strict = true;
+        self
+    }
+
+    /// The resolved mode, never [`CassetteMode::Auto`].
+    pub fn mode(&self) -> CassetteMode {
+        self.state.lock().unwrap().mode
+    }
+
+    /// Wraps a model. In replay mode the model is never called.
+    pub fn wrap_model(&self, model: impl Model + 'static) -> CassetteModel {
+        CassetteModel {
+            cassette: self.clone(),
+            inner: Some(Box::new(model)),
+        }
+    }
+
+    /// A model that only replays. It fails if the cassette is in record mode.
+    pub fn replay_model(&self) -> CassetteModel {
+        CassetteModel {
+            cassette: self.clone(),
+            inner: None,
+        }
+    }
+
+    /// Wraps a tool. In replay mode the tool is never called.
+    pub fn wrap_tool(&self, tool: Box<dyn AnyTool>) -> Box<dyn AnyTool> {
+        Box::new(CassetteTool {
+            cassette: self.clone(),
+            inner: tool,
+        })
+    }
+
+    pub fn wrap_tools(&self, tools: Vec<Box<dyn AnyTool>>) -> Vec<Box<dyn AnyTool>> {
+        tools.into_iter().map(|tool| self.wrap_tool(tool)).collect()
+    }
+
+    /// Writes the cassette to disk. Recording saves after every interaction, so this is rarely needed.
+    pub fn save(&self) -> Result<()> {
+        self.state.lock().unwrap().save()
+    }
+
+    fn record_model(&self, interaction: ModelInteraction) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        state.file.model.push(interaction);
+        state.used_model.push(true);
+        state
+            .save()
+            .map_err(|e| AgentError::Generation(e.to_string()))
+    }
+
+    fn record_tool(&self, interaction: ToolInteraction) -> Result<(), AgentError> {
+        let mut state = self.state.lock().unwrap();
+        state.file.tools.push(interaction);
+        state.used_tools.push(true);
+        state
+            .save()
+            .map_err(|e| AgentError::Execution(e.to_string()))
+    }
+
+    fn replay_model_interaction(&self, key: &str) -> Option<ModelInteraction> {
+        let mut state = self.state.lock().unwrap();
+        let keys = state
+            .file
+            .model
+            .iter()
+            .map(|interaction| interaction.key.as_str())
+            .collect::<Vec<_>>();
+        let index = find_interaction(&keys, &state.used_model, key, state.strict)?;
+        state.used_model[index] = true;
+        Some(state.file.model[index].clone())
+    }
+
+    fn replay_tool_interaction(&self, key: &str) -> Option<ToolInteraction> {
+        let mut state = self.state.lock().unwrap();
+        let keys = state
+            .file
+            .tools
+            .iter()
+            .map(|interaction| interaction.key.as_str())
+            .collect::<Vec<_>>();
+        let index = find_interaction(&keys, &state.used_tools, key, state.strict)?;
+        state.used_tools[index] = true;
+        Some(state.file.tools[index].clone())
+    }
+}
+
+impl CassetteState {
+    fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            if !parent.as_os_str().is_empty() {
+                std::fs::create_dir_all(parent)?;
+            }
+        }
+        std::fs::write(&self.path, serde_json::to_string_pretty(&self.file)?)?;
+        Ok(())
+    }
+}
+
+/// Picks the first unused interaction with a matching key, then a used one with a matching key
+/// (identical requests get identical responses), and finally the next unused one in recorded order.
+fn find_interaction(keys: &[&str], used: &[bool], key: &str, strict: bool) -> Option<usize> {
+    let unused_match = (0..keys.len()).find(|&i| keys[i] == key && !used[i]);
+    let any_match = || (0..keys.len()).rev().find(|&i| keys[i] == key);
+    let next_unused = || {
+        if strict {
+            None
+        } else {
+            let index = used.iter().position(|used| !used)?;
+            warn!("No recorded interaction matches the request, replaying them in order");
+            Some(index)
+        }
+    };
+    unused_match.or_else(any_match).or_else(next_unused)
+}
+
+fn hash(value: &Value) -> String {
+    let digest = Sha256::digest(value.to_string().as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The key of a model call: a hash of the messages and the names of the offered tools.
+pub fn model_key(messages: &[Message], tools: &[String]) -> String {
+    hash(&json!({ "messages": messages, "tools": tools }))
+}
+
+/// The key of a tool call: a hash of the tool name and its arguments.
+pub fn tool_key(tool: &str, arguments: &Value) -> String {
+    hash(&json!({ "tool": tool, "arguments": arguments }))
+}
+
+#[derive(Debug)]
+pub struct CassetteModel {
+    cassette: Cassette,
+    inner: Option<Box<dyn Model>>,
+}
+
+#[derive(Debug)]
+struct ReplayedResponse {
+    response: Option<String>,
+    tool_calls: Vec<ToolCall>,
+}
+
+impl ModelResponse for ReplayedResponse {
+    fn get_response(&self) -> Result<String, AgentError> {
+        self.response.clone().ok_or(AgentError::Generation(
+            "The recorded response has no message".to_string(),
+        ))
+    }
+
+    fn get_tools_used(&self) -> Result<Vec<ToolCall>, AgentError> {
+        Ok(self.tool_calls.clone())
+    }
+}
+
+impl Model for CassetteModel {
+    fn run(
+        &self,
+        input_messages: Vec<Message>,
+        tools: Vec<ToolInfo>,
+        max_tokens: Option<usize>,
+        args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        let tool_names = tools
+            .iter()
+            .map(|tool| tool.function.name.to_string())
+            .collect::<Vec<_>>();
+        let key = model_key(&input_messages, &tool_names);
+        if self.cassette.mode() == CassetteMode::Replay {
+            let interaction = self
+                .cassette
+                .replay_model_interaction(&key)
+                .ok_or_else(|| {
+                    AgentError::Generation(format!(
+                        "No recorded model response matches the request (key {})",
+                        key
+                    ))
+                })?;
+            return Ok(Box::new(ReplayedResponse {
+                response: interaction.response,
+                tool_calls: interaction.tool_calls,
+            }));
+        }
+
+        let inner = self.inner.as_ref().ok_or_else(|| {
+            AgentError::Generation("A replay-only model cannot record".to_string())
+        })?;
+        let response = inner.run(input_messages.clone(), tools, max_tokens, args)?;
+        let interaction = ModelInteraction {
+            key,
+            messages: input_messages,
+            tools: tool_names,
+            response: response.get_response().ok(),
+            tool_calls: response.get_tools_used().unwrap_or_default(),
+        };
+        self.cassette.record_model(interaction)?;
+        Ok(response)
+    }
+}
+
+#[derive(Debug)]
+pub struct CassetteTool {
+    cassette: Cassette,
+    inner: Box<dyn AnyTool>,
+}
+
+impl AnyTool for CassetteTool {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &'static str {
+        self.inner.description()
+    }
+
+    fn forward_json(&self, json_args: Value) -> Result<String, AgentError> {
+        self.forward_json_output(json_args)
+            .map(|output| output.render())
+    }
+
+    fn forward_json_output(&self, json_args: Value) -> Result<ToolOutput, AgentError> {
+        let key = tool_key(self.name(), &json_args);
+        if self.cassette.mode() == CassetteMode::Replay {
+            let interaction = self.cassette.replay_tool_interaction(&key).ok_or_else(|| {
+                AgentError::Execution(format!(
+                    "No recorded output of {} matches the arguments {}",
+                    self.name(),
+                    json_args
+                ))
+            })?;
+            return interaction.output;
+        }
+
+        let output = self.inner.forward_json_output(json_args.clone());
+        self.cassette.record_tool(ToolInteraction {
+            key,
+            tool: self.name().to_string(),
+            arguments: json_args,
+            output: output.clone(),
+        })?;
+        output
+    }
+
+    fn tool_info(&self) -> ToolInfo {
+        self.inner.tool_info()
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTool> {
+        Box::new(CassetteTool {
+            cassette: self.cassette.clone(),
+            inner: self.inner.clone_box(),
+        })
+    }
+
+    fn reset(&self) {
+        self.inner.reset()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.inner.cacheable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::scripted::ScriptedModel;
+    use crate::models::types::MessageRole;
+    use crate::tools::{FinalAnswerTool, Tool};
+    use schemars::JsonSchema;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct CounterToolParams {
+        label: String,
+    }
+
+    /// Returns a different output on every call, so replayed outputs are easy to tell apart.
+    #[derive(Debug, Clone, Default)]
+    struct CounterTool {
+        count: Arc<Mutex<usize>>,
+    }
+
+    impl Tool for CounterTool {
+        type Params = CounterToolParams;
+        fn name(&self) -> &'static str {
+            "counter"
+        }
+        fn description(&self) -> &'static str {
+            "Counts calls."
+        }
+        fn forward(&self, arguments: CounterToolParams) -> Result<String> {
+            let mut count = self.count.lock().unwrap();
+            *count += 1;
+            Ok(format!("{} {}", arguments.label, count))
+        }
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct ListToolParams {
+        count: usize,
+    }
+
+    /// Returns a JSON list of numbers.
+    #[derive(Debug, Clone)]
+    struct ListTool;
+
+    impl Tool for ListTool {
+        type Params = ListToolParams;
+        fn name(&self) -> &'static str {
+            "list"
+        }
+        fn description(&self) -> &'static str {
+            "Lists numbers."
+        }
+        fn forward(&self, arguments: ListToolParams) -> Result<String> {
+            self.forward_output(arguments).map(|output| output.render())
+        }
+        fn forward_output(&self, arguments: ListToolParams) -> Result<ToolOutput> {
+            Ok(ToolOutput::Json(json!(
+                (1..=arguments.count).collect::<Vec<_>>()
+            )))
+        }
+    }
+
+    fn messages(content: &str) -> Vec<Message> {
+        vec![Message {
+            role: MessageRole::User,
+            content: content.to_string(),
+        }]
+    }
+
+    #[test]
+    fn test_record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cassettes/session.json");
+
+        let cassette = Cassette::open(&path, CassetteMode::Auto).unwrap();
+        assert_eq!(cassette.mode(), CassetteMode::Record);
+        let model = cassette.wrap_model(
+            ScriptedModel::new()
+                .with_text("first")
+                .with_tool_call("final_answer", json!({"answer": "42"})),
+        );
+        let tool = cassette.wrap_tool(Box::new(CounterTool::default()));
+        let final_answer = cassette.wrap_tool(Box::new(FinalAnswerTool::new()));
+        assert_eq!(
+            model
+                .run(messages("a"), vec![], None, None)
+                .unwrap()
+                .get_response()
+                .unwrap(),
+            "first"
+        );
+        model
+            .run(messages("b"), vec![final_answer.tool_info()], None, None)
+            .unwrap();
+        assert_eq!(tool.forward_json(json!({"label": "x"})).unwrap(), "x 1");
+        assert_eq!(tool.forward_json(json!({"label": "y"})).unwrap(), "y 2");
+        assert!(tool.forward_json(json!({})).is_err());
+
+        let cassette = Cassette::open(&path, CassetteMode::Auto).unwrap().strict();
+        assert_eq!(cassette.mode(), CassetteMode::Replay);
+        let model = cassette.replay_model();
+        let tool = cassette.wrap_tool(Box::new(CounterTool::default()));
+        // Served by key, not by order.
+        assert_eq!(tool.forward_json(json!({"label": "y"})).unwrap(), "y 2");
+        assert_eq!(tool.forward_json(json!({"label": "x"})).unwrap(), "x 1");
+        assert!(matches!(
+            tool.forward_json(json!({})),
+            Err(AgentError::Parsing(_))
+        ));
+        let response = model
+            .run(messages("b"), vec![final_answer.tool_info()], None, None)
+            .unwrap();
+        assert_eq!(
+            response.get_tools_used().unwrap()[0].function.arguments,
+            json!({"answer": "42"})
+        );
+        assert!(model.run(messages("unknown"), vec![], None, None).is_err());
+    }
+
+    #[test]
+    fn test_replay_keeps_structured_outputs() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let tool = Cassette::record(&path)
+            .unwrap()
+            .wrap_tool(Box::new(ListTool));
+        let recorded = tool.forward_json_output(json!({"count": 3})).unwrap();
+        assert_eq!(recorded, ToolOutput::Json(json!([1, 2, 3])));
+
+        let tool = Cassette::replay(&path)
+            .unwrap()
+            .wrap_tool(Box::new(ListTool));
+        assert_eq!(
+            tool.forward_json_output(json!({"count": 3})).unwrap(),
+            recorded
+        );
+        assert_eq!(
+            tool.forward_json(json!({"count": 3})).unwrap(),
+            recorded.render()
+        );
+    }
+
+    #[test]
+    fn test_replay_falls_back_to_recorded_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+        let cassette = Cassette::record(&path).unwrap();
+        let model = cassette.wrap_model(ScriptedModel::new().with_text("one").with_text("two"));
+        model
+            .run(messages("time is 10:00"), vec![], None, None)
+            .unwrap();
+        model
+            .run(messages("time is 10:01"), vec![], None, None)
+            .unwrap();
+
+        let model = Cassette::replay(&path).unwrap().replay_model();
+        let first = model
+            .run(messages("time is 11:00"), vec![], None, None)
+            .unwrap();
+        let second = model
+            .run(messages("time is 11:01"), vec![], None, None)
+            .unwrap();
+        assert_eq!(first.get_response().unwrap(), "one");
+        assert_eq!(second.get_response().unwrap(), "two");
+        assert!(model.run(messages("more"), vec![], None, None).is_err());
+    }
+
+    #[test]
+    fn test_replay_requires_cassette() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(Cassette::replay(dir.path().join("missing.json")).is_err());
+    }
+}
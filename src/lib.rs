@@ -42,6 +42,8 @@
 
 //! ```
 pub mod agents;
+pub mod approval;
+pub mod cassette;
 pub mod errors;
 
 #[cfg(feature = "code-agent")]
@@ -49,6 +51,10 @@ pub mod local_python_interpreter;
 pub(crate) mod logger;
 pub mod models;
 pub mod prompts;
+pub mod testing;
 pub mod tools;
 
+#[cfg(test)]
+mod test_utils;
+
 pub use agents::*;
@@ -1,8 +1,10 @@
+use crate::approval::{review_call, ApprovalPolicy};
 use crate::errors::InterpreterError;
-use crate::tools::AnyTool;
+use crate::models::openai::FunctionCall;
+use crate::tools::{AnyTool, Artifact, ToolOutput};
 use anyhow::Result;
 use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyModule, PyTuple};
+use pyo3::types::{PyBool, PyDict, PyLong, PyModule, PySet, PyTuple};
 use rustpython_parser::{
     ast::{
         self,
@@ -12,7 +14,13 @@ use rustpython_parser::{
     Parse,
 };
 use serde_json::{self, json};
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    cell::RefCell,
+    collections::HashMap,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 pub fn get_base_python_tools() -> HashMap<&'static str, &'static str> {
     [
@@ -98,6 +106,20 @@ impl CustomConstant {
             _ => None,
         }
     }
+    /// Python's `None`, which is falsy unlike the string `"None"`.
+    pub fn none() -> Self {
+        Python::with_gil(|py| CustomConstant::PyObj(py.None()))
+    }
+    pub fn is_none(&self) -> bool {
+        match self {
+            CustomConstant::PyObj(obj) => Python::with_gil(|py| obj.is_none(py)),
+            _ => false,
+        }
+    }
+    /// Whether python considers the value true, e.g. in `if` and `not`.
+    pub fn is_truthy(&self) -> Result<bool, InterpreterError> {
+        Python::with_gil(|py| Ok(self.clone().into_py(py).as_ref(py).is_true()?))
+    }
     pub fn str(&self) -> String {
         match self {
             CustomConstant::Str(s) => s.clone(),
@@ -145,6 +167,69 @@ impl CustomConstant {
             _ => None,
         }
     }
+
+    /// Converts the value to JSON. Lists and tuples become arrays and dicts become objects.
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            CustomConstant::Int(i) => json!(convert_bigint_to_i64(i)),
+            CustomConstant::Float(f) => json!(f),
+            CustomConstant::Str(s) => json!(s),
+            CustomConstant::Bool(b) => json!(b),
+            CustomConstant::Tuple(t) => t.iter().map(|item| item.to_json()).collect(),
+            CustomConstant::Dict(keys, values) => keys
+                .iter()
+                .cloned()
+                .zip(values.iter().map(|value| value.to_json()))
+                .collect::<serde_json::Map<_, _>>()
+                .into(),
+            CustomConstant::PyObj(_) if self.is_none() => serde_json::Value::Null,
+            CustomConstant::PyObj(obj) => json!(obj.to_string()),
+        }
+    }
+
+    /// Converts JSON to the value python code sees. Arrays become lists, objects become dicts and null becomes None.
+    pub fn from_json(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => CustomConstant::none(),
+            serde_json::Value::Bool(b) => CustomConstant::Bool(*b),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(i) => CustomConstant::Int(BigInt::from(i)),
+                None => CustomConstant::Float(n.as_f64().unwrap_or_default()),
+            },
+            serde_json::Value::String(s) => CustomConstant::Str(s.clone()),
+            serde_json::Value::Array(values) => {
+                CustomConstant::Tuple(values.iter().map(CustomConstant::from_json).collect())
+            }
+            serde_json::Value::Object(object) => CustomConstant::Dict(
+                object.keys().cloned().collect(),
+                object.values().map(CustomConstant::from_json).collect(),
+            ),
+        }
+    }
+}
+
+/// Images and files become a dict describing the artifact, their content is kept with the step.
+impl From<ToolOutput> for CustomConstant {
+    fn from(output: ToolOutput) -> Self {
+        match output {
+            ToolOutput::Text(text) => CustomConstant::Str(text),
+            ToolOutput::Json(value) | ToolOutput::JsonWithText { json: value, .. } => {
+                CustomConstant::from_json(&value)
+            }
+            ToolOutput::Image(artifact) | ToolOutput::File(artifact) => CustomConstant::Dict(
+                vec![
+                    "name".to_string(),
+                    "mime_type".to_string(),
+                    "size".to_string(),
+                ],
+                vec![
+                    CustomConstant::Str(artifact.name),
+                    CustomConstant::Str(artifact.mime_type),
+                    CustomConstant::Int(BigInt::from(artifact.data.len())),
+                ],
+            ),
+        }
+    }
 }
 
 impl From<CustomConstant> for Constant {
@@ -183,7 +268,7 @@ impl From<Constant> for CustomConstant {
             Constant::Float(f) => CustomConstant::Float(f),
             Constant::Str(s) => CustomConstant::Str(s),
             Constant::Bool(b) => CustomConstant::Bool(b),
-            Constant::None => CustomConstant::Str("None".to_string()),
+            Constant::None => CustomConstant::none(),
             Constant::Tuple(t) => {
                 CustomConstant::Tuple(t.iter().map(|c| c.clone().into()).collect())
             }
@@ -223,10 +308,19 @@ type ToolFunction = Box<dyn Fn(Vec<Constant>) -> Result<CustomConstant, Interpre
 type CustomToolFunction =
     Box<dyn Fn(Vec<Constant>, HashMap<String, String>) -> Result<CustomConstant, InterpreterError>>;
 
-fn setup_custom_tools(tools: Vec<Box<dyn AnyTool>>) -> HashMap<String, CustomToolFunction> {
+/// The approval policy shared by the custom tools, so that it can be changed without setting the tools up again.
+type SharedApprovalPolicy = Rc<RefCell<Option<Arc<dyn ApprovalPolicy>>>>;
+
+fn setup_custom_tools(
+    tools: Vec<Box<dyn AnyTool>>,
+    approval_policy: SharedApprovalPolicy,
+    artifacts: Arc<Mutex<Vec<Artifact>>>,
+) -> HashMap<String, CustomToolFunction> {
     let mut tools_map = HashMap::new();
     for tool in tools {
         let tool_info = tool.tool_info();
+        let approval_policy = approval_policy.clone();
+        let artifacts = artifacts.clone();
         tools_map.insert(
             tool.name().to_string(),
             Box::new(
@@ -234,16 +328,44 @@ fn setup_custom_tools(tools: Vec<Box<dyn AnyTool>>) -> HashMap<String, CustomToo
                     //merge args and kwargs
                     let tool_parameter_names = tool_info.get_parameter_names();
 
+                    if args.len() > tool_parameter_names.len() {
+                        return Err(InterpreterError::RuntimeError(format!(
+                            "{} takes at most {} positional arguments but {} were given",
+                            tool.name(),
+                            tool_parameter_names.len(),
+                            args.len()
+                        )));
+                    }
+
+                    // The arguments are strings here, the tool call converts them to the types of its schema.
                     let mut new_args = HashMap::new();
                     for (i, arg) in args.iter().enumerate() {
-                        new_args
-                            .insert(tool_parameter_names[i].clone(), arg.clone().str().unwrap());
+                        let value = CustomConstant::from(arg.clone()).str();
+                        new_args.insert(tool_parameter_names[i].clone(), value);
                     }
                     for (key, value) in kwargs {
                         new_args.insert(key, value);
                     }
-                    match tool.forward_json(json!(new_args)) {
-                        Ok(results) => Ok(CustomConstant::Str(results)),
+                    let mut call = FunctionCall {
+                        name: tool.name().to_string(),
+                        arguments: json!(new_args),
+                    };
+                    let policy = approval_policy.borrow().clone();
+                    if let Some(policy) = policy {
+                        match review_call(policy.as_ref(), &call) {
+                            Ok(approved) => call = approved,
+                            Err(reason) => {
+                                return Ok(CustomConstant::Str(format!("Error: {}", reason)))
+                            }
+                        }
+                    }
+                    match tool.forward_json_output(call.arguments) {
+                        Ok(output) => {
+                            if let Some(artifact) = output.artifact() {
+                                artifacts.lock().unwrap().push(artifact.clone());
+                            }
+                            Ok(CustomConstant::from(output))
+                        }
                         Err(e) => Ok(CustomConstant::Str(format!("Error: {}", e))),
                     }
                 },
@@ -274,12 +396,12 @@ pub fn setup_static_tools(
                     }
                     Constant::Str(s) => locals.set_item(format!("arg{}", i), s)?,
                     Constant::Tuple(t) => {
-                        let py_list: Vec<f64> = t
+                        let py_list: Vec<PyObject> = t
                             .iter()
                             .map(|x| match x {
-                                Constant::Float(f) => *f,
-                                Constant::Int(i) => convert_bigint_to_f64(i),
-                                _ => 0.0,
+                                Constant::Float(f) => f.into_py(py),
+                                Constant::Int(i) => convert_bigint_to_i64(i).into_py(py),
+                                _ => 0.0.into_py(py),
                             })
                             .collect();
                         locals.set_item(format!("arg{}", i), py_list)?
@@ -293,8 +415,14 @@ pub fn setup_static_tools(
             let expr = format!("{}({})", func_path, arg_names.join(","));
 
             let result = py.eval(&expr, None, Some(locals))?;
-            // Handle different return types
-            if let Ok(float_val) = result.extract::<f64>() {
+            // Handle different return types. Bools and ints also extract as floats, so they are checked first.
+            if let Ok(bool_val) = result.downcast::<PyBool>() {
+                Ok(CustomConstant::Bool(bool_val.is_true()))
+            } else if let (Ok(_), Ok(int_val)) =
+                (result.downcast::<PyLong>(), result.extract::<i64>())
+            {
+                Ok(CustomConstant::Int(BigInt::from(int_val)))
+            } else if let Ok(float_val) = result.extract::<f64>() {
                 Ok(CustomConstant::Float(float_val))
             } else if let Ok(list_val) = result.extract::<Vec<String>>() {
                 Ok(CustomConstant::Tuple(
@@ -393,6 +521,19 @@ fn evaluate_stmt(
             Ok(for_loop_result)
         }
 
+        Stmt::If(if_stmt) => {
+            let test = evaluate_expr(&if_stmt.test, state, static_tools, custom_tools)?;
+            let body = match test.is_truthy()? {
+                true => &if_stmt.body,
+                false => &if_stmt.orelse,
+            };
+            let mut if_result = CustomConstant::Str(String::new());
+            for stmt in body {
+                if_result = evaluate_stmt(stmt, state, static_tools, custom_tools)?;
+            }
+            Ok(if_result)
+        }
+
         Stmt::Assign(assign) => {
             for target in assign.targets.iter() {
                 // let target = evaluate_expr(&Box::new(target.clone()), state, static_tools)?;
@@ -472,6 +613,9 @@ fn convert_bigint_to_i64(i: &BigInt) -> i64 {
 type StaticTool = Box<dyn Fn(Vec<Constant>) -> Result<CustomConstant, InterpreterError>>;
 type CustomTool =
     Box<dyn Fn(Vec<Constant>, HashMap<String, String>) -> Result<CustomConstant, InterpreterError>>;
+/// Evaluates the element of a comprehension once its loop variables are bound.
+type ComprehensionElement<'a> =
+    dyn FnMut(&mut HashMap<String, Box<dyn Any>>) -> Result<(), InterpreterError> + 'a;
 
 fn evaluate_expr(
     expr: &Expr,
@@ -506,33 +650,90 @@ fn evaluate_expr(
             Ok(CustomConstant::Dict(keys, values))
         }
         ast::Expr::ListComp(list_comp) => {
-            let iter = evaluate_expr(
-                &list_comp.generators[0].iter,
+            let mut result = Vec::new();
+            evaluate_comprehension(
+                &list_comp.generators,
                 state,
                 static_tools,
                 custom_tools,
+                &mut |state| {
+                    result.push(evaluate_expr(
+                        &list_comp.elt,
+                        state,
+                        static_tools,
+                        custom_tools,
+                    )?);
+                    Ok(())
+                },
             )?;
-            let result = Python::with_gil(|py| -> Result<Vec<CustomConstant>, InterpreterError> {
-                let iter = iter.into_py(py);
-                let iter = iter.as_ref(py).iter()?;
-                let mut result = Vec::new();
-                for item in iter {
-                    let target = match &list_comp.generators[0].target {
-                        ast::Expr::Name(name) => name.id.to_string(),
-                        _ => panic!("Expected string"),
-                    };
-                    let item = item?;
-                    let item = extract_constant_from_pyobject(item, py)?;
-                    state.insert(target, Box::new(item));
-                    let eval_expr =
-                        evaluate_expr(&list_comp.elt, state, static_tools, custom_tools)?;
-                    result.push(eval_expr);
-                }
-                Ok(result)
-            });
-            let result = result?;
             Ok(CustomConstant::Tuple(result))
         }
+        // Generators are evaluated eagerly, so `sum(x for x in xs)` receives a list.
+        ast::Expr::GeneratorExp(generator_exp) => {
+            let mut result = Vec::new();
+            evaluate_comprehension(
+                &generator_exp.generators,
+                state,
+                static_tools,
+                custom_tools,
+                &mut |state| {
+                    result.push(evaluate_expr(
+                        &generator_exp.elt,
+                        state,
+                        static_tools,
+                        custom_tools,
+                    )?);
+                    Ok(())
+                },
+            )?;
+            Ok(CustomConstant::Tuple(result))
+        }
+        // Sets are kept as lists without duplicates, in the order the items were first seen.
+        ast::Expr::SetComp(set_comp) => Python::with_gil(|py| {
+            let seen = PySet::empty(py)?;
+            let mut result = Vec::new();
+            evaluate_comprehension(
+                &set_comp.generators,
+                state,
+                static_tools,
+                custom_tools,
+                &mut |state| {
+                    let item = evaluate_expr(&set_comp.elt, state, static_tools, custom_tools)?;
+                    let key = item.clone().into_py(py);
+                    if !seen.contains(&key)? {
+                        seen.add(key)?;
+                        result.push(item);
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(CustomConstant::Tuple(result))
+        }),
+        ast::Expr::DictComp(dict_comp) => {
+            let mut keys: Vec<String> = Vec::new();
+            let mut values = Vec::new();
+            evaluate_comprehension(
+                &dict_comp.generators,
+                state,
+                static_tools,
+                custom_tools,
+                &mut |state| {
+                    let key = evaluate_expr(&dict_comp.key, state, static_tools, custom_tools)?;
+                    let value = evaluate_expr(&dict_comp.value, state, static_tools, custom_tools)?;
+                    let key = key.str();
+                    // A repeated key keeps its position and takes the last value, like in python.
+                    match keys.iter().position(|k| *k == key) {
+                        Some(i) => values[i] = value,
+                        None => {
+                            keys.push(key);
+                            values.push(value);
+                        }
+                    }
+                    Ok(())
+                },
+            )?;
+            Ok(CustomConstant::Dict(keys, values))
+        }
         ast::Expr::Call(call) => {
             let args = call
                 .args
@@ -587,7 +788,7 @@ fn evaluate_expr(
                 _ => panic!("Expected function name"),
             };
 
-            let keywords = call
+            let keyword_values = call
                 .keywords
                 .iter()
                 .map(|k| {
@@ -597,19 +798,33 @@ fn evaluate_expr(
                         static_tools,
                         custom_tools,
                     )?;
-                    Ok((k.arg.as_ref().unwrap().to_string(), value.str()))
+                    Ok((k.arg.as_ref().unwrap().to_string(), value))
                 })
-                .collect::<Result<HashMap<String, String>, InterpreterError>>()?;
+                .collect::<Result<Vec<(String, CustomConstant)>, InterpreterError>>()?;
+            let keywords = keyword_values
+                .iter()
+                .map(|(name, value)| (name.clone(), value.str()))
+                .collect::<HashMap<String, String>>();
             if func == "final_answer" {
-                if let Some(answer) = keywords.get("answer") {
-                    return Err(InterpreterError::FinalAnswer(answer.to_string()));
-                } else {
-                    return Err(InterpreterError::FinalAnswer(
-                        args.iter()
-                            .map(|c| c.str())
-                            .collect::<Vec<String>>()
-                            .join(" "),
-                    ));
+                let answer = keyword_values
+                    .iter()
+                    .find(|(name, _)| name == "answer")
+                    .map(|(_, value)| value)
+                    .or(if args.len() == 1 { args.first() } else { None });
+                match answer {
+                    // Structured answers are passed on as JSON so they can be checked against a schema.
+                    Some(answer @ (CustomConstant::Dict(..) | CustomConstant::Tuple(_))) => {
+                        return Err(InterpreterError::FinalAnswer(answer.to_json().to_string()));
+                    }
+                    Some(answer) => return Err(InterpreterError::FinalAnswer(answer.str())),
+                    None => {
+                        return Err(InterpreterError::FinalAnswer(
+                            args.iter()
+                                .map(|c| c.str())
+                                .collect::<Vec<String>>()
+                                .join(" "),
+                        ));
+                    }
                 }
             }
             if func == "print" {
@@ -693,6 +908,13 @@ fn evaluate_expr(
                 },
                 _ => {}
             }
+            if let (CustomConstant::Int(left), CustomConstant::Int(right)) =
+                (&left_val_exp, &right_val_exp)
+            {
+                if let Some(result) = evaluate_int_binop(&binop.op, left, right)? {
+                    return Ok(CustomConstant::Int(result));
+                }
+            }
             let left_val = match left_val_exp.clone() {
                 CustomConstant::Float(f) => f,
                 CustomConstant::Int(i) => convert_bigint_to_f64(&i),
@@ -743,13 +965,7 @@ fn evaluate_expr(
                     _ => panic!("Expected float or int"),
                 },
                 UnaryOp::UAdd => Ok(operand),
-                UnaryOp::Not => {
-                    if let CustomConstant::Bool(b) = operand {
-                        Ok(CustomConstant::Bool(!b))
-                    } else {
-                        panic!("Expected boolean")
-                    }
-                }
+                UnaryOp::Not => Ok(CustomConstant::Bool(!operand.is_truthy()?)),
                 UnaryOp::Invert => {
                     if let CustomConstant::Float(f) = operand {
                         Ok(CustomConstant::Float(-(f as i64) as f64))
@@ -948,11 +1164,182 @@ fn evaluate_expr(
     }
 }
 
+/// Runs the `for` and `if` clauses of a comprehension and calls `emit` for every combination of values that passes
+/// the conditions.
+///
+/// Like in python 3, the loop variables are local to the comprehension: the variables they shadow are restored
+/// afterwards, and the first iterable is evaluated before any of them is bound.
+fn evaluate_comprehension(
+    generators: &[ast::Comprehension],
+    state: &mut HashMap<String, Box<dyn Any>>,
+    static_tools: &HashMap<String, StaticTool>,
+    custom_tools: &HashMap<String, CustomTool>,
+    emit: &mut ComprehensionElement<'_>,
+) -> Result<(), InterpreterError> {
+    if generators.iter().any(|generator| generator.is_async) {
+        return Err(InterpreterError::RuntimeError(
+            "Async comprehensions are not supported".to_string(),
+        ));
+    }
+    let first = evaluate_expr(&generators[0].iter, state, static_tools, custom_tools)?;
+
+    let mut names = Vec::new();
+    for generator in generators {
+        target_names(&generator.target, &mut names)?;
+    }
+    let shadowed = names
+        .into_iter()
+        .map(|name| {
+            let value = state.remove(&name);
+            (name, value)
+        })
+        .collect::<Vec<_>>();
+
+    let result = evaluate_generators(generators, first, state, static_tools, custom_tools, emit);
+
+    for (name, value) in shadowed {
+        state.remove(&name);
+        if let Some(value) = value {
+            state.insert(name, value);
+        }
+    }
+    result
+}
+
+fn evaluate_generators(
+    generators: &[ast::Comprehension],
+    iter: CustomConstant,
+    state: &mut HashMap<String, Box<dyn Any>>,
+    static_tools: &HashMap<String, StaticTool>,
+    custom_tools: &HashMap<String, CustomTool>,
+    emit: &mut ComprehensionElement<'_>,
+) -> Result<(), InterpreterError> {
+    let (generator, rest) = match generators.split_first() {
+        Some(split) => split,
+        None => return emit(state),
+    };
+    Python::with_gil(|py| {
+        let iter = iter.into_py(py);
+        'items: for item in iter.as_ref(py).iter()? {
+            assign_target(&generator.target, item?, state, py)?;
+            for condition in &generator.ifs {
+                let condition = evaluate_expr(condition, state, static_tools, custom_tools)?;
+                if !condition.into_py(py).as_ref(py).is_true()? {
+                    continue 'items;
+                }
+            }
+            match rest.first() {
+                Some(next) => {
+                    let next_iter = evaluate_expr(&next.iter, state, static_tools, custom_tools)?;
+                    evaluate_generators(rest, next_iter, state, static_tools, custom_tools, emit)?;
+                }
+                None => emit(state)?,
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Applies an arithmetic operator to two ints like python, so that `2 * 3` stays an int and can index a list.
+/// Returns `None` for the operators whose result is a float, such as `/`.
+fn evaluate_int_binop(
+    op: &Operator,
+    left: &BigInt,
+    right: &BigInt,
+) -> Result<Option<BigInt>, InterpreterError> {
+    let zero = BigInt::from(0);
+    if matches!(op, Operator::FloorDiv | Operator::Mod) && *right == zero {
+        return Err(InterpreterError::RuntimeError(
+            "integer division or modulo by zero".to_string(),
+        ));
+    }
+    // Rust rounds towards zero, python towards negative infinity.
+    let floor_div = || {
+        let quotient = left / right;
+        match (left % right != zero) && ((*left < zero) != (*right < zero)) {
+            true => quotient - 1,
+            false => quotient,
+        }
+    };
+    Ok(match op {
+        Operator::Add => Some(left + right),
+        Operator::Sub => Some(left - right),
+        Operator::Mult => Some(left * right),
+        Operator::FloorDiv => Some(floor_div()),
+        Operator::Mod => Some(left - right * floor_div()),
+        Operator::Pow => u32::try_from(right).ok().map(|exponent| left.pow(exponent)),
+        _ => None,
+    })
+}
+
+/// Collects the variable names bound by a loop target such as `x` or `(key, value)`.
+fn target_names(target: &Expr, names: &mut Vec<String>) -> Result<(), InterpreterError> {
+    match target {
+        ast::Expr::Name(name) => names.push(name.id.to_string()),
+        ast::Expr::Tuple(tuple) => {
+            for elt in &tuple.elts {
+                target_names(elt, names)?;
+            }
+        }
+        ast::Expr::List(list) => {
+            for elt in &list.elts {
+                target_names(elt, names)?;
+            }
+        }
+        _ => {
+            return Err(InterpreterError::RuntimeError(
+                "Expected name or tuple as loop target".to_string(),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Binds a value to a loop target, unpacking it for `for key, value in ...`.
+fn assign_target(
+    target: &Expr,
+    value: &PyAny,
+    state: &mut HashMap<String, Box<dyn Any>>,
+    py: Python<'_>,
+) -> Result<(), InterpreterError> {
+    let elts = match target {
+        ast::Expr::Name(name) => {
+            let value = extract_constant_from_pyobject(value, py)?;
+            state.insert(name.id.to_string(), Box::new(value));
+            return Ok(());
+        }
+        ast::Expr::Tuple(tuple) => &tuple.elts,
+        ast::Expr::List(list) => &list.elts,
+        _ => {
+            return Err(InterpreterError::RuntimeError(
+                "Expected name or tuple as loop target".to_string(),
+            ))
+        }
+    };
+    let values = value.iter()?.collect::<Result<Vec<&PyAny>, _>>()?;
+    if elts.len() != values.len() {
+        return Err(InterpreterError::RuntimeError(format!(
+            "Tuple unpacking failed. Expected {} values, got {}",
+            elts.len(),
+            values.len()
+        )));
+    }
+    for (elt, value) in elts.iter().zip(values) {
+        assign_target(elt, value, state, py)?;
+    }
+    Ok(())
+}
+
 fn extract_constant_from_pyobject(
     obj: &PyAny,
     py: Python<'_>,
 ) -> Result<CustomConstant, InterpreterError> {
-    if let Ok(float_val) = obj.extract::<f64>() {
+    // Bools and ints also extract as floats, so they are checked first.
+    if let Ok(bool_val) = obj.downcast::<PyBool>() {
+        Ok(CustomConstant::Bool(bool_val.is_true()))
+    } else if let (Ok(_), Ok(int_val)) = (obj.downcast::<PyLong>(), obj.extract::<i64>()) {
+        Ok(CustomConstant::Int(BigInt::from(int_val)))
+    } else if let Ok(float_val) = obj.extract::<f64>() {
         Ok(CustomConstant::Float(float_val))
     } else if let Ok(string_val) = obj.extract::<String>() {
         Ok(CustomConstant::Str(string_val))
@@ -998,7 +1385,7 @@ pub fn evaluate_python_code(
 ) -> Result<String, InterpreterError> {
     let base_tools = get_base_python_tools();
     let static_tools = setup_static_tools(base_tools);
-    let custom_tools = setup_custom_tools(custom_tools);
+    let custom_tools = setup_custom_tools(custom_tools, Rc::default(), Arc::default());
     let ast = ast::Suite::parse(code, "<embedded>")
         .map_err(|e| InterpreterError::SyntaxError(e.to_string()))?;
 
@@ -1010,19 +1397,59 @@ pub struct LocalPythonInterpreter {
     static_tools: HashMap<String, ToolFunction>,
     custom_tools: HashMap<String, CustomToolFunction>,
     state: HashMap<String, Box<dyn Any>>,
+    /// The images and files returned by the custom tools since they were last taken.
+    artifacts: Arc<Mutex<Vec<Artifact>>>,
+    approval_policy: SharedApprovalPolicy,
 }
 
 impl LocalPythonInterpreter {
     pub fn new(custom_tools: Vec<Box<dyn AnyTool>>) -> Self {
-        let custom_tools = setup_custom_tools(custom_tools);
+        Self::build(custom_tools, None)
+    }
+    /// Creates an interpreter that asks the policy before running any of the custom tools.
+    pub fn with_approval_policy(
+        custom_tools: Vec<Box<dyn AnyTool>>,
+        approval_policy: Arc<dyn ApprovalPolicy>,
+    ) -> Self {
+        Self::build(custom_tools, Some(approval_policy))
+    }
+    fn build(
+        custom_tools: Vec<Box<dyn AnyTool>>,
+        approval_policy: Option<Arc<dyn ApprovalPolicy>>,
+    ) -> Self {
+        let artifacts = Arc::default();
+        let approval_policy = Rc::new(RefCell::new(approval_policy));
+        let custom_tools = setup_custom_tools(
+            custom_tools,
+            Rc::clone(&approval_policy),
+            Arc::clone(&artifacts),
+        );
         let base_tools = get_base_python_tools();
         let static_tools = setup_static_tools(base_tools);
         Self {
             static_tools,
             custom_tools,
             state: HashMap::new(),
+            artifacts,
+            approval_policy,
         }
     }
+    /// Asks the policy before running any of the custom tools. The variables defined so far are kept.
+    pub fn set_approval_policy(&mut self, approval_policy: Arc<dyn ApprovalPolicy>) {
+        *self.approval_policy.borrow_mut() = Some(approval_policy);
+    }
+    /// Replaces the custom tools, e.g. with the tools offered in the next step. The variables defined so far are kept.
+    pub fn set_custom_tools(&mut self, custom_tools: Vec<Box<dyn AnyTool>>) {
+        self.custom_tools = setup_custom_tools(
+            custom_tools,
+            Rc::clone(&self.approval_policy),
+            Arc::clone(&self.artifacts),
+        );
+    }
+    /// Returns the images and files produced by the custom tools since the last call.
+    pub fn take_artifacts(&mut self) -> Vec<Artifact> {
+        std::mem::take(&mut *self.artifacts.lock().unwrap())
+    }
     pub fn forward(&mut self, code: &str) -> Result<(String, String), InterpreterError> {
         let ast = ast::Suite::parse(code, "<embedded>")
             .map_err(|e| InterpreterError::SyntaxError(e.to_string()))?;
@@ -1347,6 +1774,66 @@ for url in urls:
         );
     }
 
+    #[test]
+    fn test_evaluate_python_code_with_comprehensions() {
+        let code = textwrap::dedent(
+            r#"
+        results = [{"title": "a", "url": "u1"}, {}, {"title": "b", "url": "u2"}]
+        links = {r['title']: r['url'] for r in results if r}
+        titles = {v: k for k, v in links.items()}
+        pairs = [x * 10 + y for x in [1, 2, 3] if x % 2 for y in [x, 4]]
+        total = sum(x for x in [1, 2, 3])
+        remainders = {x % 3 for x in [1, 2, 4, 5, 6]}
+        x = [1, 2]
+        doubled = [x * 2 for x in x]
+        picked = [x[i] for i in [1, 0]]
+        flags = [not b for b in [True, False]]
+    "#,
+        );
+        let mut state = HashMap::new();
+        let _ = evaluate_python_code(&code, vec![], &mut state).unwrap();
+        let value = |name: &str| {
+            state
+                .get(name)
+                .unwrap()
+                .downcast_ref::<CustomConstant>()
+                .unwrap()
+                .to_json()
+        };
+        assert_eq!(value("links"), json!({"a": "u1", "b": "u2"}));
+        assert_eq!(value("titles"), json!({"u1": "a", "u2": "b"}));
+        assert_eq!(value("pairs"), json!([11, 14, 33, 34]));
+        assert_eq!(value("total"), json!(6));
+        assert_eq!(value("remainders"), json!([1, 2, 0]));
+        assert_eq!(value("doubled"), json!([2, 4]));
+        assert_eq!(value("picked"), json!([2, 1]));
+        assert_eq!(value("flags"), json!([false, true]));
+        // The loop variables do not leak out of the comprehensions.
+        assert_eq!(value("x"), json!([1, 2]));
+        for name in ["r", "k", "v", "y"] {
+            assert!(!state.contains_key(name));
+        }
+    }
+
+    #[test]
+    fn test_evaluate_python_code_int_arithmetic() {
+        let code = textwrap::dedent(
+            r#"
+        values = [7 // 2, -7 // 2, 7 % -3, -7 % 3, 2 ** 10, 7 / 2, 1 + 2.5, max(3, 9)]
+    "#,
+        );
+        let mut state = HashMap::new();
+        let _ = evaluate_python_code(&code, vec![], &mut state).unwrap();
+        let values = state
+            .get("values")
+            .unwrap()
+            .downcast_ref::<CustomConstant>()
+            .unwrap()
+            .to_json();
+        assert_eq!(values, json!([3, -4, -2, 2, 1024, 3.5, 3.5, 9]));
+        assert!(evaluate_python_code("1 // 0", vec![], &mut state).is_err());
+    }
+
     #[test]
     fn test_evaluate_python_code_append_to_list() {
         let code = textwrap::dedent(
@@ -1422,4 +1909,112 @@ guidelines = (
         let (_, logs_2) = local_python_interpreter.forward(&code_2).unwrap();
         println!("logs_2: {:?}", logs_2);
     }
+
+    #[derive(serde::Deserialize, schemars::JsonSchema)]
+    struct RepeatToolParams {
+        text: String,
+        times: Option<usize>,
+        upper: Option<bool>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct RepeatTool;
+
+    impl crate::tools::Tool for RepeatTool {
+        type Params = RepeatToolParams;
+        fn name(&self) -> &'static str {
+            "repeat"
+        }
+        fn description(&self) -> &'static str {
+            "Repeats the text."
+        }
+        fn forward(&self, arguments: RepeatToolParams) -> Result<String> {
+            let text = arguments.text.repeat(arguments.times.unwrap_or(1));
+            Ok(match arguments.upper {
+                Some(true) => text.to_uppercase(),
+                _ => text,
+            })
+        }
+    }
+
+    #[derive(serde::Deserialize, schemars::JsonSchema)]
+    struct CitiesToolParams {}
+
+    #[derive(Debug, Clone)]
+    struct CitiesTool;
+
+    impl crate::tools::Tool for CitiesTool {
+        type Params = CitiesToolParams;
+        fn name(&self) -> &'static str {
+            "cities"
+        }
+        fn description(&self) -> &'static str {
+            "Lists cities."
+        }
+        fn forward(&self, arguments: CitiesToolParams) -> Result<String> {
+            Ok(self.forward_output(arguments)?.render())
+        }
+        fn forward_output(&self, _arguments: CitiesToolParams) -> Result<ToolOutput> {
+            Ok(ToolOutput::Json(json!([
+                {"name": "Paris", "population": 2100000, "capital": true},
+                {"name": "Lyon", "population": 520000, "capital": false, "mayor": null}
+            ])))
+        }
+    }
+
+    #[test]
+    fn test_custom_tool_json_output() {
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(CitiesTool)];
+        let mut interpreter = LocalPythonInterpreter::new(tools);
+        let code =
+            "cities = cities()\nprint(f\"{cities[1]['name']} {cities[0]['population'] + 1}\")";
+        let (_, logs) = interpreter.forward(code).unwrap();
+        assert_eq!(logs, "Lyon 2100001");
+        let (result, _) = interpreter.forward("cities[0]['name']").unwrap();
+        assert_eq!(result, "Paris");
+        assert!(interpreter.take_artifacts().is_empty());
+    }
+
+    #[test]
+    fn test_custom_tool_json_null_is_none() {
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(CitiesTool)];
+        let mut interpreter = LocalPythonInterpreter::new(tools);
+        let code = "tool_result = cities()[1]['mayor']\nif not tool_result:\n    print('No mayor')\nelse:\n    print(tool_result)";
+        let (_, logs) = interpreter.forward(code).unwrap();
+        assert_eq!(logs, "No mayor");
+        let (result, _) = interpreter.forward("tool_result").unwrap();
+        assert_eq!(result, "None");
+        assert!(CustomConstant::from_json(&json!(null)).is_none());
+        assert_eq!(CustomConstant::none().to_json(), json!(null));
+    }
+
+    #[test]
+    fn test_custom_tool_typed_arguments() {
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(RepeatTool)];
+        let mut interpreter = LocalPythonInterpreter::new(tools);
+        let (result, _) = interpreter.forward("repeat('ab', 3)").unwrap();
+        assert_eq!(result, "ababab");
+        let (result, _) = interpreter
+            .forward("repeat(text='ab', times=2, upper=True)")
+            .unwrap();
+        assert_eq!(result, "ABAB");
+        assert!(matches!(
+            interpreter.forward("repeat('ab', 2, True, 'extra')"),
+            Err(InterpreterError::RuntimeError(_))
+        ));
+    }
+
+    #[test]
+    fn test_set_approval_policy_keeps_state() {
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(RepeatTool)];
+        let mut interpreter = LocalPythonInterpreter::new(tools);
+        interpreter.forward("text = repeat('ab', 2)").unwrap();
+        let policy =
+            |_: &FunctionCall| crate::approval::ApprovalDecision::Deny("read only".to_string());
+        interpreter.set_approval_policy(Arc::new(policy));
+        let (result, _) = interpreter.forward("text").unwrap();
+        assert_eq!(result, "abab");
+        let (result, _) = interpreter.forward("repeat('ab', 2)").unwrap();
+        assert_eq!(result, "Error: The call to repeat was denied: read only");
+    }
 }
@@ -0,0 +1,109 @@
+//! This module contains the fallback model. It tries an ordered list of models and returns the first successful response.
+//!
+//! Timeouts are surfaced by the backends as errors, so configure a timeout on each backend
+//! (e.g. `OpenAIServerModel::with_timeout`) to move on from a backend that hangs.
+
+use std::collections::HashMap;
+
+use crate::errors::AgentError;
+use crate::tools::ToolInfo;
+use anyhow::Result;
+use log::warn;
+
+use super::model_traits::{Model, ModelResponse};
+use super::types::Message;
+
+#[derive(Debug)]
+pub struct FallbackModel {
+    models: Vec<Box<dyn Model>>,
+}
+
+impl FallbackModel {
+    /// Creates a fallback chain. The models are tried in the given order.
+    pub fn new(models: Vec<Box<dyn Model>>) -> Self {
+        Self { models }
+    }
+
+    /// Appends a model to the end of the chain.
+    pub fn with_model(mut self, model: impl Model + 'static) -> Self {
+        self.models.push(Box::new(model));
+        self
+    }
+}
+
+impl Model for FallbackModel {
+    fn run(
+        &self,
+        input_messages: Vec<Message>,
+        tools: Vec<ToolInfo>,
+        max_tokens: Option<usize>,
+        args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        let mut errors = Vec::new();
+        for (i, model) in self.models.iter().enumerate() {
+            match model.run(
+                input_messages.clone(),
+                tools.clone(),
+                max_tokens,
+                args.clone(),
+            ) {
+                Ok(response) => return Ok(response),
+                Err(e) => {
+                    warn!("Model {} in the fallback chain failed: {}", i, e);
+                    errors.push(format!("model {}: {}", i, e));
+                }
+            }
+        }
+        if errors.is_empty() {
+            return Err(AgentError::Generation(
+                "The fallback chain does not contain any models".to_string(),
+            ));
+        }
+        Err(AgentError::Generation(format!(
+            "All models in the fallback chain failed: {}",
+            errors.join("; ")
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::types::MessageRole;
+    use crate::test_utils::StubModel;
+
+    fn user_message() -> Vec<Message> {
+        vec![Message {
+            role: MessageRole::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    #[test]
+    fn test_fallback_model_uses_first_working_model() {
+        let model = FallbackModel::new(vec![])
+            .with_model(StubModel::failing("rate limited"))
+            .with_model(StubModel::answering("second"))
+            .with_model(StubModel::answering("third"));
+        let response = model.run(user_message(), vec![], None, None).unwrap();
+        assert_eq!(response.get_response().unwrap(), "second");
+    }
+
+    #[test]
+    fn test_fallback_model_reports_all_errors() {
+        let model = FallbackModel::new(vec![
+            Box::new(StubModel::failing("timed out")),
+            Box::new(StubModel::failing("server error")),
+        ]);
+        match model.run(user_message(), vec![], None, None) {
+            Err(AgentError::Generation(msg)) => {
+                assert!(msg.contains("model 0: timed out"));
+                assert!(msg.contains("model 1: server error"));
+            }
+            _ => panic!("Expected a generation error"),
+        }
+
+        let model = FallbackModel::new(vec![]);
+        assert!(model.run(user_message(), vec![], None, None).is_err());
+    }
+}
@@ -1,4 +1,9 @@
+pub mod fallback;
 pub mod model_traits;
 pub mod ollama;
 pub mod openai;
+pub mod retry;
+pub mod router;
+pub mod scripted;
 pub mod types;
+pub mod vector_store;
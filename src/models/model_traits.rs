@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::fmt::Debug;
 
 use crate::{
     errors::AgentError,
@@ -11,7 +12,7 @@ pub trait ModelResponse {
     fn get_tools_used(&self) -> Result<Vec<ToolCall>, AgentError>;
 }
 
-pub trait Model {
+pub trait Model: Debug {
     fn run(
         &self,
         input_messages: Vec<Message>,
@@ -20,3 +21,63 @@ pub trait Model {
         args: Option<HashMap<String, Vec<String>>>,
     ) -> Result<Box<dyn ModelResponse>, AgentError>;
 }
+
+impl Model for Box<dyn Model> {
+    fn run(
+        &self,
+        input_messages: Vec<Message>,
+        tools: Vec<ToolInfo>,
+        max_tokens: Option<usize>,
+        args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        self.as_ref().run(input_messages, tools, max_tokens, args)
+    }
+}
+
+/// The default number of texts sent to an embedding model in one request.
+pub const DEFAULT_EMBEDDING_BATCH_SIZE: usize = 32;
+
+/// A model that turns texts into vectors, so that similar texts get similar vectors.
+///
+/// Implementations send one request per call of [`EmbeddingModel::embed_batch`]; [`EmbeddingModel::embed`] splits
+/// any number of texts into batches and checks the embeddings it gets back.
+pub trait EmbeddingModel: Debug {
+    /// Returns one embedding per text, in the same order as the texts, with a single request.
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError>;
+
+    /// The length of the embeddings, if it is known before embedding anything.
+    fn dimensions(&self) -> Option<usize> {
+        None
+    }
+
+    /// The largest number of texts sent in one request.
+    fn max_batch_size(&self) -> usize {
+        DEFAULT_EMBEDDING_BATCH_SIZE
+    }
+
+    /// Returns one embedding per text, in the same order as the texts, with as many requests as needed.
+    fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(self.max_batch_size().max(1)) {
+            let batch_embeddings = self.embed_batch(batch)?;
+            if batch_embeddings.len() != batch.len() {
+                return Err(AgentError::Generation(format!(
+                    "The embedding model returned {} embeddings for {} texts",
+                    batch_embeddings.len(),
+                    batch.len()
+                )));
+            }
+            if let Some(dimensions) = self.dimensions() {
+                if let Some(embedding) = batch_embeddings.iter().find(|e| e.len() != dimensions) {
+                    return Err(AgentError::Generation(format!(
+                        "The embedding model returned an embedding of {} dimensions instead of {}",
+                        embedding.len(),
+                        dimensions
+                    )));
+                }
+            }
+            embeddings.extend(batch_embeddings);
+        }
+        Ok(embeddings)
+    }
+}
@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::time::Duration;
 
 use serde::Deserialize;
 use serde_json::json;
@@ -7,8 +8,9 @@ use crate::{errors::AgentError, tools::ToolInfo};
 use anyhow::Result;
 
 use super::{
-    model_traits::{Model, ModelResponse},
+    model_traits::{EmbeddingModel, Model, ModelResponse, DEFAULT_EMBEDDING_BATCH_SIZE},
     openai::ToolCall,
+    retry::RetryPolicy,
     types::{Message, MessageRole},
 };
 
@@ -34,6 +36,11 @@ impl ModelResponse for OllamaResponse {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct OllamaEmbeddingResponse {
+    pub embeddings: Vec<Vec<f32>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct OllamaModel {
     model_id: String,
@@ -41,6 +48,10 @@ pub struct OllamaModel {
     url: String,
     client: reqwest::blocking::Client,
     ctx_length: usize,
+    retry_policy: RetryPolicy,
+    timeout: Option<Duration>,
+    embedding_dimensions: Option<usize>,
+    embedding_batch_size: usize,
 }
 
 #[derive(Default)]
@@ -50,6 +61,10 @@ pub struct OllamaModelBuilder {
     client: Option<reqwest::blocking::Client>,
     url: Option<String>,
     ctx_length: Option<usize>,
+    retry_policy: Option<RetryPolicy>,
+    timeout: Option<Duration>,
+    embedding_dimensions: Option<usize>,
+    embedding_batch_size: Option<usize>,
 }
 
 impl OllamaModelBuilder {
@@ -61,6 +76,10 @@ impl OllamaModelBuilder {
             client: Some(client),
             url: Some("http://localhost:11434".to_string()),
             ctx_length: Some(2048),
+            retry_policy: Some(RetryPolicy::default()),
+            timeout: None,
+            embedding_dimensions: None,
+            embedding_batch_size: Some(DEFAULT_EMBEDDING_BATCH_SIZE),
         }
     }
 
@@ -84,6 +103,29 @@ impl OllamaModelBuilder {
         self
     }
 
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    /// Sets the timeout for a single request. It is applied to every request, so a custom client is kept.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Asks the model for embeddings of this length. Only some embedding models can shorten their embeddings.
+    pub fn embedding_dimensions(mut self, dimensions: usize) -> Self {
+        self.embedding_dimensions = Some(dimensions);
+        self
+    }
+
+    /// Sets the largest number of texts embedded in one request.
+    pub fn embedding_batch_size(mut self, batch_size: usize) -> Self {
+        self.embedding_batch_size = Some(batch_size);
+        self
+    }
+
     pub fn build(self) -> OllamaModel {
         OllamaModel {
             model_id: self.model_id,
@@ -91,6 +133,22 @@ impl OllamaModelBuilder {
             url: self.url.unwrap_or("http://localhost:11434".to_string()),
             client: self.client.unwrap_or_default(),
             ctx_length: self.ctx_length.unwrap_or(2048),
+            retry_policy: self.retry_policy.unwrap_or_default(),
+            timeout: self.timeout,
+            embedding_dimensions: self.embedding_dimensions,
+            embedding_batch_size: self
+                .embedding_batch_size
+                .unwrap_or(DEFAULT_EMBEDDING_BATCH_SIZE),
+        }
+    }
+}
+
+impl OllamaModel {
+    fn post(&self, path: &str) -> reqwest::blocking::RequestBuilder {
+        let request = self.client.post(format!("{}{}", self.url, path));
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
         }
     }
 }
@@ -133,14 +191,200 @@ impl Model for OllamaModel {
         }
 
         let response = self
-            .client
-            .post(format!("{}/api/chat", self.url))
-            .json(&body)
-            .send()
+            .retry_policy
+            .send(|| self.post("/api/chat").json(&body))
             .map_err(|e| {
                 AgentError::Generation(format!("Failed to get response from Ollama: {}", e))
             })?;
-        let output = response.json::<OllamaResponse>().unwrap();
+        let status = response.status();
+        let text = response.text().map_err(|e| {
+            AgentError::Generation(format!("Failed to read response from Ollama: {}", e))
+        })?;
+        if !status.is_success() {
+            return Err(AgentError::Generation(format!(
+                "Failed to get response from Ollama: HTTP {}: {}",
+                status, text
+            )));
+        }
+        let output = serde_json::from_str::<OllamaResponse>(&text).map_err(|e| {
+            AgentError::Generation(format!(
+                "Failed to parse response from Ollama: {}. Response body: {}",
+                e, text
+            ))
+        })?;
         Ok(Box::new(output))
     }
 }
+
+impl EmbeddingModel for OllamaModel {
+    /// Embeds the texts with the `/api/embed` endpoint. The model must be an embedding model, e.g. `nomic-embed-text`.
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut body = json!({
+            "model": self.model_id,
+            "input": texts,
+        });
+        if let Some(dimensions) = self.embedding_dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+        let response = self
+            .retry_policy
+            .send(|| self.post("/api/embed").json(&body))
+            .map_err(|e| {
+                AgentError::Generation(format!("Failed to get embeddings from Ollama: {}", e))
+            })?;
+        let status = response.status();
+        let text = response.text().map_err(|e| {
+            AgentError::Generation(format!("Failed to read embeddings from Ollama: {}", e))
+        })?;
+        if !status.is_success() {
+            return Err(AgentError::Generation(format!(
+                "Failed to get embeddings from Ollama: HTTP {}: {}",
+                status, text
+            )));
+        }
+        let output = serde_json::from_str::<OllamaEmbeddingResponse>(&text).map_err(|e| {
+            AgentError::Generation(format!(
+                "Failed to parse embeddings from Ollama: {}. Response body: {}",
+                e, text
+            ))
+        })?;
+        Ok(output.embeddings)
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.embedding_dimensions
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.embedding_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use std::time::Duration;
+
+    fn user_message() -> Vec<Message> {
+        vec![Message {
+            role: MessageRole::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    #[test]
+    fn test_ollama_retries_server_errors() {
+        let server = MockServer::start(vec![
+            MockServer::response(503, &[], "loading model"),
+            MockServer::response(
+                200,
+                &[],
+                r#"{"message": {"role": "assistant", "content": "Hello!"}}"#,
+            ),
+        ]);
+        let model = OllamaModelBuilder::new()
+            .url(server.url())
+            .retry_policy(RetryPolicy::new(2).initial_backoff(Duration::from_millis(1)))
+            .build();
+        let response = model.run(user_message(), vec![], None, None).unwrap();
+        assert_eq!(response.get_response().unwrap(), "Hello!");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].path, "/api/chat");
+    }
+
+    #[test]
+    fn test_ollama_timeout() {
+        // The connection is accepted by the kernel but never answered.
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let model = OllamaModelBuilder::new()
+            .url(format!("http://{}", listener.local_addr().unwrap()))
+            .retry_policy(RetryPolicy::none())
+            .timeout(Duration::from_millis(100))
+            .build();
+        let start = std::time::Instant::now();
+        assert!(model.run(user_message(), vec![], None, None).is_err());
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_ollama_error_body_is_a_generation_error() {
+        let server = MockServer::start(vec![MockServer::response(
+            200,
+            &[],
+            r#"{"error": "model 'llama3.2' not found"}"#,
+        )]);
+        let model = OllamaModelBuilder::new().url(server.url()).build();
+        let result = model.run(user_message(), vec![], None, None);
+        match result {
+            Err(AgentError::Generation(msg)) => assert!(msg.contains("not found")),
+            _ => panic!("Expected a generation error"),
+        }
+    }
+
+    #[test]
+    fn test_ollama_embeddings() {
+        let server = MockServer::start(vec![
+            MockServer::response(
+                200,
+                &[],
+                r#"{"model": "nomic-embed-text", "embeddings": [[0.1, 0.2], [0.3, 0.4]]}"#,
+            ),
+            MockServer::response(200, &[], r#"{"embeddings": [[0.5, 0.6]]}"#),
+        ]);
+        let model = OllamaModelBuilder::new()
+            .model_id("nomic-embed-text")
+            .url(server.url())
+            .embedding_dimensions(2)
+            .embedding_batch_size(2)
+            .build();
+        assert_eq!(model.dimensions(), Some(2));
+        let texts = vec![
+            "first".to_string(),
+            "second".to_string(),
+            "third".to_string(),
+        ];
+        let embeddings = model.embed(&texts).unwrap();
+        assert_eq!(
+            embeddings,
+            vec![vec![0.1, 0.2], vec![0.3, 0.4], vec![0.5, 0.6]]
+        );
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, "/api/embed");
+        let body = serde_json::from_str::<serde_json::Value>(&requests[0].body).unwrap();
+        assert_eq!(
+            body,
+            json!({"model": "nomic-embed-text", "input": ["first", "second"], "dimensions": 2})
+        );
+        assert!(requests[1].body.contains(r#"["third"]"#));
+    }
+
+    #[test]
+    fn test_ollama_embeddings_are_checked() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], r#"{"embeddings": [[0.1, 0.2]]}"#),
+            MockServer::response(200, &[], r#"{"embeddings": [[0.1, 0.2, 0.3]]}"#),
+        ]);
+        let model = OllamaModelBuilder::new()
+            .url(server.url())
+            .embedding_dimensions(2)
+            .build();
+        let error = model
+            .embed(&["first".to_string(), "second".to_string()])
+            .unwrap_err();
+        assert_eq!(
+            error.message(),
+            "The embedding model returned 1 embeddings for 2 texts"
+        );
+        let error = model.embed(&["first".to_string()]).unwrap_err();
+        assert_eq!(
+            error.message(),
+            "The embedding model returned an embedding of 3 dimensions instead of 2"
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use std::collections::HashMap;
 
 use crate::errors::AgentError;
-use crate::models::model_traits::{Model, ModelResponse};
+use crate::models::model_traits::{
+    EmbeddingModel, Model, ModelResponse, DEFAULT_EMBEDDING_BATCH_SIZE,
+};
+use crate::models::retry::RetryPolicy;
 use crate::models::types::{Message, MessageRole};
 use crate::tools::ToolInfo;
 use anyhow::Result;
-use reqwest::blocking::Client;
+use reqwest::blocking::{Client, RequestBuilder};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
 pub struct OpenAIResponse {
@@ -108,6 +112,17 @@ impl ModelResponse for OpenAIResponse {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbeddingResponse {
+    pub data: Vec<OpenAIEmbedding>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbedding {
+    pub index: usize,
+    pub embedding: Vec<f32>,
+}
+
 #[derive(Debug)]
 pub struct OpenAIServerModel {
     pub base_url: String,
@@ -115,6 +130,14 @@ pub struct OpenAIServerModel {
     pub client: Client,
     pub temperature: f32,
     pub api_key: String,
+    pub retry_policy: RetryPolicy,
+    /// The timeout applied to every request.
+    pub timeout: Option<Duration>,
+    /// The URL of the embeddings endpoint, next to the chat completions endpoint by default.
+    pub embeddings_url: String,
+    /// The length of the embeddings asked for, supported by the `text-embedding-3` models.
+    pub embedding_dimensions: Option<usize>,
+    pub embedding_batch_size: usize,
 }
 
 impl OpenAIServerModel {
@@ -130,6 +153,10 @@ impl OpenAIServerModel {
         let model_id = model_id.unwrap_or("gpt-4o-mini").to_string();
         let base_url = base_url.unwrap_or("https://api.openai.com/v1/chat/completions");
         let client = Client::new();
+        let embeddings_url = match base_url.strip_suffix("/chat/completions") {
+            Some(api_url) => format!("{}/embeddings", api_url),
+            None => format!("{}/embeddings", base_url.trim_end_matches('/')),
+        };
 
         OpenAIServerModel {
             base_url: base_url.to_string(),
@@ -137,6 +164,52 @@ impl OpenAIServerModel {
             client,
             temperature: temperature.unwrap_or(0.5),
             api_key,
+            retry_policy: RetryPolicy::default(),
+            timeout: None,
+            embeddings_url,
+            embedding_dimensions: None,
+            embedding_batch_size: DEFAULT_EMBEDDING_BATCH_SIZE,
+        }
+    }
+
+    /// Sets the URL of the embeddings endpoint, for servers that do not serve it next to the chat completions.
+    pub fn with_embeddings_url(mut self, embeddings_url: &str) -> Self {
+        self.embeddings_url = embeddings_url.to_string();
+        self
+    }
+
+    /// Asks for embeddings of this length instead of the model's default.
+    pub fn with_embedding_dimensions(mut self, dimensions: usize) -> Self {
+        self.embedding_dimensions = Some(dimensions);
+        self
+    }
+
+    /// Sets the largest number of texts embedded in one request.
+    pub fn with_embedding_batch_size(mut self, batch_size: usize) -> Self {
+        self.embedding_batch_size = batch_size;
+        self
+    }
+
+    /// Sets the policy used to retry rate-limited and failed requests.
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the timeout for a single request. A request that times out is reported as a generation error.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn post(&self, url: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.api_key));
+        match self.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
         }
     }
 }
@@ -180,24 +253,169 @@ impl Model for OpenAIServerModel {
         }
 
         let response = self
-            .client
-            .post(&self.base_url)
-            .header("Authorization", format!("Bearer {}", self.api_key))
-            .json(&body)
-            .send()
+            .retry_policy
+            .send(|| self.post(&self.base_url).json(&body))
             .map_err(|e| {
                 AgentError::Generation(format!("Failed to get response from OpenAI: {}", e))
             })?;
 
         match response.status() {
             reqwest::StatusCode::OK => {
-                let response = response.json::<OpenAIResponse>().unwrap();
+                let text = response.text().map_err(|e| {
+                    AgentError::Generation(format!("Failed to read response from OpenAI: {}", e))
+                })?;
+                let response = serde_json::from_str::<OpenAIResponse>(&text).map_err(|e| {
+                    AgentError::Generation(format!(
+                        "Failed to parse response from OpenAI: {}. Response body: {}",
+                        e, text
+                    ))
+                })?;
                 Ok(Box::new(response))
             }
-            _ => Err(AgentError::Generation(format!(
-                "Failed to get response from OpenAI: {}",
-                response.text().unwrap()
+            status => Err(AgentError::Generation(format!(
+                "Failed to get response from OpenAI: HTTP {}: {}",
+                status,
+                response.text().unwrap_or_default()
             ))),
         }
     }
 }
+
+impl EmbeddingModel for OpenAIServerModel {
+    /// Embeds the texts with the `/v1/embeddings` endpoint. The model must be an embedding model, e.g.
+    /// `text-embedding-3-small`.
+    fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut body = json!({
+            "model": self.model_id,
+            "input": texts,
+        });
+        if let Some(dimensions) = self.embedding_dimensions {
+            body["dimensions"] = json!(dimensions);
+        }
+
+        let response = self
+            .retry_policy
+            .send(|| self.post(&self.embeddings_url).json(&body))
+            .map_err(|e| {
+                AgentError::Generation(format!("Failed to get embeddings from OpenAI: {}", e))
+            })?;
+        let status = response.status();
+        let text = response.text().map_err(|e| {
+            AgentError::Generation(format!("Failed to read embeddings from OpenAI: {}", e))
+        })?;
+        if !status.is_success() {
+            return Err(AgentError::Generation(format!(
+                "Failed to get embeddings from OpenAI: HTTP {}: {}",
+                status, text
+            )));
+        }
+        let mut output = serde_json::from_str::<OpenAIEmbeddingResponse>(&text).map_err(|e| {
+            AgentError::Generation(format!(
+                "Failed to parse embeddings from OpenAI: {}. Response body: {}",
+                e, text
+            ))
+        })?;
+        output.data.sort_by_key(|embedding| embedding.index);
+        Ok(output
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    fn dimensions(&self) -> Option<usize> {
+        self.embedding_dimensions.or(match self.model_id.as_str() {
+            "text-embedding-3-small" | "text-embedding-ada-002" => Some(1536),
+            "text-embedding-3-large" => Some(3072),
+            _ => None,
+        })
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.embedding_batch_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use std::time::Duration;
+
+    fn user_message() -> Vec<Message> {
+        vec![Message {
+            role: MessageRole::User,
+            content: "Hi".to_string(),
+        }]
+    }
+
+    #[test]
+    fn test_openai_retries_rate_limits() {
+        let server = MockServer::start(vec![
+            MockServer::response(429, &[("Retry-After", "0")], "rate limited"),
+            MockServer::response(
+                200,
+                &[],
+                r#"{"choices": [{"message": {"role": "assistant", "content": "Hello!"}}]}"#,
+            ),
+        ]);
+        let model =
+            OpenAIServerModel::new(Some(&server.url()), None, None, Some("key".to_string()))
+                .with_retry_policy(RetryPolicy::new(2).initial_backoff(Duration::from_millis(1)));
+        let response = model.run(user_message(), vec![], None, None).unwrap();
+        assert_eq!(response.get_response().unwrap(), "Hello!");
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[1].method, "POST");
+        assert_eq!(requests[1].header("authorization"), Some("Bearer key"));
+        assert!(requests[1].body.contains("gpt-4o-mini"));
+    }
+
+    #[test]
+    fn test_openai_malformed_body_is_a_generation_error() {
+        let server = MockServer::start(vec![MockServer::response(200, &[], "<html>oops</html>")]);
+        let model =
+            OpenAIServerModel::new(Some(&server.url()), None, None, Some("key".to_string()));
+        let result = model.run(user_message(), vec![], None, None);
+        match result {
+            Err(AgentError::Generation(msg)) => assert!(msg.contains("Failed to parse response")),
+            _ => panic!("Expected a generation error"),
+        }
+    }
+
+    #[test]
+    fn test_openai_embeddings() {
+        let server = MockServer::start(vec![MockServer::response(
+            200,
+            &[],
+            r#"{"object": "list", "data": [
+                {"object": "embedding", "index": 1, "embedding": [0.3, 0.4]},
+                {"object": "embedding", "index": 0, "embedding": [0.1, 0.2]}
+            ]}"#,
+        )]);
+        let model = OpenAIServerModel::new(
+            Some(&format!("{}/v1/chat/completions", server.url())),
+            Some("text-embedding-3-small"),
+            None,
+            Some("key".to_string()),
+        );
+        assert_eq!(model.dimensions(), Some(1536));
+        let model = model.with_embedding_dimensions(2);
+        assert_eq!(model.dimensions(), Some(2));
+        let embeddings = model
+            .embed(&["first".to_string(), "second".to_string()])
+            .unwrap();
+        assert_eq!(embeddings, vec![vec![0.1, 0.2], vec![0.3, 0.4]]);
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/v1/embeddings");
+        assert_eq!(requests[0].header("authorization"), Some("Bearer key"));
+        let body = serde_json::from_str::<Value>(&requests[0].body).unwrap();
+        assert_eq!(
+            body,
+            json!({"model": "text-embedding-3-small", "input": ["first", "second"], "dimensions": 2})
+        );
+    }
+}
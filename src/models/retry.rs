@@ -0,0 +1,290 @@
+//! This module contains the retry policy shared by the model backends.
+//!
+//! Requests are retried on rate limits (HTTP 429), server errors (HTTP 5xx) and transient
+//! connection failures, waiting with jittered exponential backoff between attempts.
+
+use std::time::Duration;
+
+use reqwest::blocking::{RequestBuilder, Response};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+
+/// Controls how often and how long a backend waits before retrying a failed request.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// The maximum number of retries after the first attempt.
+    pub max_retries: usize,
+    /// The backoff before the first retry.
+    pub initial_backoff: Duration,
+    /// The upper bound for the computed backoff.
+    pub max_backoff: Duration,
+    /// The longest delay requested by a `Retry-After` header that is honored, so a server cannot stall the agent.
+    pub max_retry_after: Duration,
+    /// The factor the backoff grows by after every attempt.
+    pub multiplier: f64,
+    /// Whether to randomize the backoff to avoid many clients retrying in lockstep.
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            max_retry_after: Duration::from_secs(60),
+            multiplier: 2.0,
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            ..Default::default()
+        }
+    }
+
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self::new(0)
+    }
+
+    pub fn initial_backoff(mut self, initial_backoff: Duration) -> Self {
+        self.initial_backoff = initial_backoff;
+        self
+    }
+
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    pub fn max_retry_after(mut self, max_retry_after: Duration) -> Self {
+        self.max_retry_after = max_retry_after;
+        self
+    }
+
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The time to wait before the given retry (starting at 0).
+    ///
+    /// With jitter enabled the backoff is drawn uniformly from the upper half of the exponential delay.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let exponent = i32::try_from(retry).unwrap_or(i32::MAX);
+        let delay = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let delay = delay.min(self.max_backoff.as_secs_f64());
+        let delay = if self.jitter {
+            delay / 2.0 + fastrand::f64() * delay / 2.0
+        } else {
+            delay
+        };
+        Duration::from_secs_f64(delay.max(0.0))
+    }
+
+    /// Sends the request built by `build_request`, retrying according to this policy.
+    ///
+    /// The closure is called once per attempt since a blocking request cannot be cloned once the body is consumed.
+    /// When the retries are exhausted the last response is returned so that the caller can report the error body.
+    pub fn send(
+        &self,
+        build_request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, reqwest::Error> {
+        let mut retry = 0;
+        loop {
+            let can_retry = retry < self.max_retries;
+            match build_request().send() {
+                Ok(response) => {
+                    let status = response.status();
+                    if !can_retry || !is_retryable_status(status) {
+                        return Ok(response);
+                    }
+                    let wait = retry_after(response.headers())
+                        .map(|wait| wait.min(self.max_retry_after))
+                        .unwrap_or_else(|| self.backoff(retry));
+                    log::debug!(
+                        "Request failed with HTTP {}, retrying in {:?} ({}/{})",
+                        status,
+                        wait,
+                        retry + 1,
+                        self.max_retries
+                    );
+                    std::thread::sleep(wait);
+                }
+                Err(e) => {
+                    if !can_retry || !is_retryable_error(&e) {
+                        return Err(e);
+                    }
+                    let wait = self.backoff(retry);
+                    log::debug!(
+                        "Request failed with {}, retrying in {:?} ({}/{})",
+                        e,
+                        wait,
+                        retry + 1,
+                        self.max_retries
+                    );
+                    std::thread::sleep(wait);
+                }
+            }
+            retry += 1;
+        }
+    }
+}
+
+/// Rate limits and server errors are worth retrying, client errors are not.
+pub fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Connection failures, timeouts and resets are transient; everything else (invalid urls, bad bodies) is not.
+pub fn is_retryable_error(error: &reqwest::Error) -> bool {
+    if error.is_connect() || error.is_timeout() {
+        return true;
+    }
+    let mut source = std::error::Error::source(error);
+    while let Some(err) = source {
+        if let Some(io_error) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_error.kind(),
+                std::io::ErrorKind::ConnectionReset
+                    | std::io::ErrorKind::ConnectionAborted
+                    | std::io::ErrorKind::BrokenPipe
+                    | std::io::ErrorKind::UnexpectedEof
+            );
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Parses the delay requested by the server, either from `retry-after-ms` or from `Retry-After`
+/// given in seconds or as an HTTP date.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    if let Some(ms) = headers
+        .get("retry-after-ms")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<f64>().ok())
+    {
+        return Some(Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+    }
+    let value = headers
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+    if let Ok(seconds) = value.parse::<f64>() {
+        return Some(Duration::from_secs_f64(seconds.max(0.0)));
+    }
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    let wait = date.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(wait.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_backoff_grows_and_is_capped() {
+        let policy = RetryPolicy::new(5)
+            .initial_backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(300))
+            .jitter(false);
+        assert_eq!(policy.backoff(0), Duration::from_millis(100));
+        assert_eq!(policy.backoff(1), Duration::from_millis(200));
+        assert_eq!(policy.backoff(2), Duration::from_millis(300));
+        assert_eq!(policy.backoff(10), Duration::from_millis(300));
+
+        let policy = policy.jitter(true);
+        for _ in 0..20 {
+            let backoff = policy.backoff(1);
+            assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn test_retry_after_parsing() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert("retry-after", HeaderValue::from_static("2"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(2)));
+
+        headers.insert(
+            "retry-after",
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+
+        headers.insert("retry-after-ms", HeaderValue::from_static("1500"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn test_send_retries_rate_limits_and_server_errors() {
+        let server = MockServer::start(vec![
+            MockServer::response(429, &[("Retry-After", "0")], "slow down"),
+            MockServer::response(503, &[], "unavailable"),
+            MockServer::response(200, &[], "ok"),
+        ]);
+        let client = reqwest::blocking::Client::new();
+        let policy = RetryPolicy::new(3).initial_backoff(Duration::from_millis(1));
+        let response = policy.send(|| client.get(server.url())).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.text().unwrap(), "ok");
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_send_caps_retry_after() {
+        let server = MockServer::start(vec![
+            MockServer::response(429, &[("Retry-After", "3600")], "slow down"),
+            MockServer::response(200, &[], "ok"),
+        ]);
+        let client = reqwest::blocking::Client::new();
+        let policy = RetryPolicy::new(1).max_retry_after(Duration::from_millis(1));
+        let start = std::time::Instant::now();
+        let response = policy.send(|| client.get(server.url())).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_send_gives_up_after_max_retries() {
+        let server = MockServer::start(vec![
+            MockServer::response(500, &[], "boom"),
+            MockServer::response(500, &[], "boom"),
+            MockServer::response(200, &[], "ok"),
+        ]);
+        let client = reqwest::blocking::Client::new();
+        let policy = RetryPolicy::new(1).initial_backoff(Duration::from_millis(1));
+        let response = policy.send(|| client.get(server.url())).unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_send_does_not_retry_client_errors() {
+        let server = MockServer::start(vec![
+            MockServer::response(400, &[], "bad request"),
+            MockServer::response(200, &[], "ok"),
+        ]);
+        let client = reqwest::blocking::Client::new();
+        let policy = RetryPolicy::new(3).initial_backoff(Duration::from_millis(1));
+        let response = policy.send(|| client.get(server.url())).unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
@@ -0,0 +1,123 @@
+//! This module contains the router model. It picks one of several models for every call,
+//! e.g. a cheap model for the planning prompts and a strong one for the action steps.
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use crate::errors::AgentError;
+use crate::prompts::{SYSTEM_PROMPT_FACTS, SYSTEM_PROMPT_PLAN};
+use crate::tools::ToolInfo;
+use anyhow::Result;
+
+use super::model_traits::{Model, ModelResponse};
+use super::types::{Message, MessageRole};
+
+/// Chooses the index of the model to use for a call from its messages and tools.
+pub type RouteFn = Box<dyn Fn(&[Message], &[ToolInfo]) -> usize>;
+
+pub struct RouterModel {
+    models: Vec<Box<dyn Model>>,
+    route: RouteFn,
+}
+
+impl RouterModel {
+    /// Creates a router over `models`. `route` returns the index of the model to use for each call.
+    pub fn new(
+        models: Vec<Box<dyn Model>>,
+        route: impl Fn(&[Message], &[ToolInfo]) -> usize + 'static,
+    ) -> Self {
+        Self {
+            models,
+            route: Box::new(route),
+        }
+    }
+
+    /// Routes the planning prompts used by `MultiStepAgent::planning_step` to `planner` and every other call to `executor`.
+    pub fn planning(planner: impl Model + 'static, executor: impl Model + 'static) -> Self {
+        Self::new(
+            vec![Box::new(planner), Box::new(executor)],
+            |messages, _| {
+                if is_planning_prompt(messages) {
+                    0
+                } else {
+                    1
+                }
+            },
+        )
+    }
+}
+
+impl Debug for RouterModel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RouterModel")
+            .field("models", &self.models)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Whether the messages are one of the fact-finding or planning prompts of the planning step.
+pub fn is_planning_prompt(messages: &[Message]) -> bool {
+    messages.first().is_some_and(|message| {
+        message.role == MessageRole::System
+            && (message.content == SYSTEM_PROMPT_FACTS || message.content == SYSTEM_PROMPT_PLAN)
+    })
+}
+
+impl Model for RouterModel {
+    fn run(
+        &self,
+        input_messages: Vec<Message>,
+        tools: Vec<ToolInfo>,
+        max_tokens: Option<usize>,
+        args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        let index = (self.route)(&input_messages, &tools);
+        let model = self.models.get(index).ok_or_else(|| {
+            AgentError::Generation(format!(
+                "The router selected model {} but only {} models are available",
+                index,
+                self.models.len()
+            ))
+        })?;
+        model.run(input_messages, tools, max_tokens, args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::StubModel;
+
+    #[test]
+    fn test_planning_router() {
+        let model = RouterModel::planning(
+            StubModel::answering("cheap"),
+            StubModel::answering("strong"),
+        );
+        let planning_messages = vec![
+            Message {
+                role: MessageRole::System,
+                content: SYSTEM_PROMPT_PLAN.to_string(),
+            },
+            Message {
+                role: MessageRole::User,
+                content: "Plan this".to_string(),
+            },
+        ];
+        let response = model.run(planning_messages, vec![], None, None).unwrap();
+        assert_eq!(response.get_response().unwrap(), "cheap");
+
+        let action_messages = vec![Message {
+            role: MessageRole::System,
+            content: "You are an expert assistant".to_string(),
+        }];
+        let response = model.run(action_messages, vec![], None, None).unwrap();
+        assert_eq!(response.get_response().unwrap(), "strong");
+    }
+
+    #[test]
+    fn test_router_rejects_unknown_model() {
+        let model = RouterModel::new(vec![Box::new(StubModel::answering("only"))], |_, _| 3);
+        assert!(model.run(vec![], vec![], None, None).is_err());
+    }
+}
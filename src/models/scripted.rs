@@ -0,0 +1,178 @@
+//! This module contains a deterministic model for testing agents without a live LLM.
+//!
+//! The model returns a queued sequence of responses and tool calls and records the messages it receives.
+//! Clones share the same script and recordings, so keep a clone around to inspect the calls after a run.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use serde_json::Value;
+
+use crate::errors::AgentError;
+use crate::tools::ToolInfo;
+use anyhow::Result;
+
+use super::model_traits::{Model, ModelResponse};
+use super::openai::{FunctionCall, ToolCall};
+use super::types::Message;
+
+/// A canned model response made of text and tool calls.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedResponse {
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+}
+
+impl ScriptedResponse {
+    /// A response with only text, e.g. a code blob for the code agent.
+    pub fn text(content: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            tool_calls: vec![],
+        }
+    }
+
+    /// A response calling a single tool.
+    pub fn tool_call(name: &str, arguments: Value) -> Self {
+        Self::default().with_tool_call(name, arguments)
+    }
+
+    /// Adds another tool call to the response.
+    pub fn with_tool_call(mut self, name: &str, arguments: Value) -> Self {
+        self.tool_calls.push(ToolCall {
+            id: Some(format!("call_{}", self.tool_calls.len())),
+            call_type: Some("function".to_string()),
+            function: FunctionCall {
+                name: name.to_string(),
+                arguments,
+            },
+        });
+        self
+    }
+}
+
+impl ModelResponse for ScriptedResponse {
+    fn get_response(&self) -> Result<String, AgentError> {
+        Ok(self.content.clone())
+    }
+
+    fn get_tools_used(&self) -> Result<Vec<ToolCall>, AgentError> {
+        Ok(self.tool_calls.clone())
+    }
+}
+
+/// A call received by the [`ScriptedModel`].
+#[derive(Debug, Clone)]
+pub struct RecordedCall {
+    pub messages: Vec<Message>,
+    /// The names of the tools offered to the model.
+    pub tools: Vec<String>,
+    pub max_tokens: Option<usize>,
+    pub args: Option<HashMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedModel {
+    responses: Arc<Mutex<VecDeque<Result<ScriptedResponse, AgentError>>>>,
+    calls: Arc<Mutex<Vec<RecordedCall>>>,
+}
+
+impl ScriptedModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a response.
+    pub fn with_response(self, response: ScriptedResponse) -> Self {
+        self.responses.lock().unwrap().push_back(Ok(response));
+        self
+    }
+
+    /// Queues a text-only response.
+    pub fn with_text(self, content: &str) -> Self {
+        self.with_response(ScriptedResponse::text(content))
+    }
+
+    /// Queues a response calling a single tool.
+    pub fn with_tool_call(self, name: &str, arguments: Value) -> Self {
+        self.with_response(ScriptedResponse::tool_call(name, arguments))
+    }
+
+    /// Queues an error, e.g. to exercise a fallback chain.
+    pub fn with_error(self, error: AgentError) -> Self {
+        self.responses.lock().unwrap().push_back(Err(error));
+        self
+    }
+
+    /// The calls received so far, in order.
+    pub fn calls(&self) -> Vec<RecordedCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// The number of queued responses that have not been served yet.
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+}
+
+impl Model for ScriptedModel {
+    fn run(
+        &self,
+        input_messages: Vec<Message>,
+        tools: Vec<ToolInfo>,
+        max_tokens: Option<usize>,
+        args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        self.calls.lock().unwrap().push(RecordedCall {
+            messages: input_messages,
+            tools: tools
+                .iter()
+                .map(|tool| tool.function.name.to_string())
+                .collect(),
+            max_tokens,
+            args,
+        });
+        match self.responses.lock().unwrap().pop_front() {
+            Some(Ok(response)) => Ok(Box::new(response)),
+            Some(Err(e)) => Err(e),
+            None => Err(AgentError::Generation(
+                "ScriptedModel has no responses left".to_string(),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::types::MessageRole;
+    use serde_json::json;
+
+    #[test]
+    fn test_scripted_model_serves_responses_in_order() {
+        let model = ScriptedModel::new()
+            .with_text("thinking")
+            .with_tool_call("final_answer", json!({"answer": "42"}))
+            .with_error(AgentError::Generation("down".to_string()));
+        let recorder = model.clone();
+        let messages = vec![Message {
+            role: MessageRole::User,
+            content: "What is the answer?".to_string(),
+        }];
+
+        let first = model.run(messages.clone(), vec![], None, None).unwrap();
+        assert_eq!(first.get_response().unwrap(), "thinking");
+        let second = model.run(messages.clone(), vec![], Some(10), None).unwrap();
+        let tool_calls = second.get_tools_used().unwrap();
+        assert_eq!(tool_calls[0].function.name, "final_answer");
+        assert_eq!(tool_calls[0].function.arguments, json!({"answer": "42"}));
+        assert!(model.run(messages.clone(), vec![], None, None).is_err());
+        assert!(model.run(messages, vec![], None, None).is_err());
+
+        let calls = recorder.calls();
+        assert_eq!(calls.len(), 4);
+        assert_eq!(calls[1].max_tokens, Some(10));
+        assert_eq!(calls[0].messages[0].content, "What is the answer?");
+        assert_eq!(recorder.remaining(), 0);
+    }
+}
@@ -0,0 +1,219 @@
+//! A small in-memory vector store that finds the items whose embeddings are closest to a query, by cosine
+//! similarity.
+//!
+//! ```rust,no_run
+//! use smolagents_rs::models::ollama::OllamaModelBuilder;
+//! use smolagents_rs::models::vector_store::VectorStore;
+//!
+//! let model = OllamaModelBuilder::new().model_id("nomic-embed-text").build();
+//! let mut store = VectorStore::new();
+//! store
+//!     .add_texts(&model, vec!["Rust has no garbage collector.".to_string()])
+//!     .unwrap();
+//! let results = store.search_text(&model, "memory management", 3).unwrap();
+//! ```
+
+use crate::errors::AgentError;
+
+use super::model_traits::EmbeddingModel;
+
+/// The cosine of the angle between two vectors, from -1 to 1, or 0 if one of them is zero.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm = |v: &[f32]| v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    match norm(a) * norm(b) {
+        0.0 => 0.0,
+        norms => dot / norms,
+    }
+}
+
+/// Items stored with their embeddings. All the embeddings must have the same length.
+#[derive(Debug, Clone)]
+pub struct VectorStore<T = String> {
+    vectors: Vec<Vec<f32>>,
+    items: Vec<T>,
+}
+
+impl<T> Default for VectorStore<T> {
+    fn default() -> Self {
+        VectorStore {
+            vectors: Vec::new(),
+            items: Vec::new(),
+        }
+    }
+}
+
+impl<T> VectorStore<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The length of the stored embeddings, or `None` if the store is empty.
+    pub fn dimensions(&self) -> Option<usize> {
+        self.vectors.first().map(Vec::len)
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Adds an item with its embedding.
+    pub fn insert(&mut self, vector: Vec<f32>, item: T) -> Result<(), AgentError> {
+        self.check_dimensions(&vector)?;
+        self.vectors.push(vector);
+        self.items.push(item);
+        Ok(())
+    }
+
+    /// Embeds the texts with the model and adds the items, the `i`-th text being the content of the `i`-th item.
+    pub fn add<M: EmbeddingModel + ?Sized>(
+        &mut self,
+        model: &M,
+        texts: &[String],
+        items: Vec<T>,
+    ) -> Result<(), AgentError> {
+        if texts.len() != items.len() {
+            return Err(AgentError::Execution(format!(
+                "Got {} texts for {} items",
+                texts.len(),
+                items.len()
+            )));
+        }
+        let vectors = model.embed(texts)?;
+        let dimensions = self.dimensions().or(vectors.first().map(Vec::len));
+        if let Some(vector) = vectors.iter().find(|v| Some(v.len()) != dimensions) {
+            return Err(AgentError::Execution(format!(
+                "Expected an embedding of {} dimensions, got {}",
+                dimensions.unwrap_or_default(),
+                vector.len()
+            )));
+        }
+        self.vectors.extend(vectors);
+        self.items.extend(items);
+        Ok(())
+    }
+
+    /// The `k` items closest to the query embedding with their similarity, most similar first.
+    pub fn search(&self, query: &[f32], k: usize) -> Vec<(&T, f32)> {
+        let mut scores = self
+            .vectors
+            .iter()
+            .map(|vector| cosine_similarity(query, vector))
+            .enumerate()
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+            .into_iter()
+            .take(k)
+            .map(|(i, score)| (&self.items[i], score))
+            .collect()
+    }
+
+    /// Embeds the query with the model and returns the `k` closest items with their similarity.
+    pub fn search_text<M: EmbeddingModel + ?Sized>(
+        &self,
+        model: &M,
+        query: &str,
+        k: usize,
+    ) -> Result<Vec<(&T, f32)>, AgentError> {
+        let query = model.embed(&[query.to_string()])?.pop().ok_or_else(|| {
+            AgentError::Generation("The embedding model returned no embedding".into())
+        })?;
+        self.check_dimensions(&query)?;
+        Ok(self.search(&query, k))
+    }
+
+    fn check_dimensions(&self, vector: &[f32]) -> Result<(), AgentError> {
+        match self.dimensions() {
+            Some(dimensions) if dimensions != vector.len() => Err(AgentError::Execution(format!(
+                "Expected an embedding of {} dimensions, got {}",
+                dimensions,
+                vector.len()
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl VectorStore<String> {
+    /// Embeds the texts with the model and stores them.
+    pub fn add_texts<M: EmbeddingModel + ?Sized>(
+        &mut self,
+        model: &M,
+        texts: Vec<String>,
+    ) -> Result<(), AgentError> {
+        self.add(model, &texts.clone(), texts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Embeds a text as the number of `a` and `b` characters in it.
+    #[derive(Debug)]
+    struct LetterEmbeddings;
+
+    impl EmbeddingModel for LetterEmbeddings {
+        fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    ['a', 'b']
+                        .iter()
+                        .map(|letter| text.matches(*letter).count() as f32)
+                        .collect()
+                })
+                .collect())
+        }
+
+        fn max_batch_size(&self) -> usize {
+            2
+        }
+    }
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]), 1.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[0.0, 3.0]), 0.0);
+        assert!((cosine_similarity(&[1.0, 1.0], &[-1.0, -1.0]) + 1.0).abs() < 1e-6);
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+    }
+
+    #[test]
+    fn test_vector_store() {
+        let mut store = VectorStore::new();
+        store
+            .add_texts(
+                &LetterEmbeddings,
+                vec!["aaa".to_string(), "bbb".to_string(), "ab".to_string()],
+            )
+            .unwrap();
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.dimensions(), Some(2));
+
+        let results = store.search_text(&LetterEmbeddings, "aaab", 2).unwrap();
+        let texts = results
+            .iter()
+            .map(|(text, _)| text.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(texts, vec!["aaa", "ab"]);
+        assert!(results[0].1 > results[1].1);
+
+        let error = store.insert(vec![1.0, 0.0, 0.0], "c".to_string());
+        assert_eq!(
+            error.unwrap_err().message(),
+            "Expected an embedding of 2 dimensions, got 3"
+        );
+        store.insert(vec![0.0, 1.0], "b".to_string()).unwrap();
+        assert_eq!(store.search(&[0.0, 1.0], 1)[0].0, "bbb");
+    }
+}
@@ -0,0 +1,154 @@
+//! Helpers shared by the unit tests.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use crate::errors::AgentError;
+use crate::models::model_traits::{Model, ModelResponse};
+use crate::models::openai::ToolCall;
+use crate::models::types::Message;
+use crate::tools::ToolInfo;
+
+/// A request received by the [`MockServer`].
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// A minimal HTTP server on localhost that answers each connection with the next canned response.
+pub struct MockServer {
+    url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockServer {
+    pub fn start(responses: Vec<String>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = requests.clone();
+        std::thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+
+                let mut headers = Vec::new();
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((key, value)) = line.split_once(':') {
+                        if key.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                        headers.push((key.trim().to_string(), value.trim().to_string()));
+                    }
+                }
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                recorded.lock().unwrap().push(RecordedRequest {
+                    method,
+                    path,
+                    headers,
+                    body: String::from_utf8_lossy(&body).to_string(),
+                });
+                let _ = stream.write_all(response.as_bytes());
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// Formats a raw HTTP response with the given status, headers and body.
+    pub fn response(status: u16, headers: &[(&str, &str)], body: &str) -> String {
+        let mut response = format!("HTTP/1.1 {} Mock\r\n", status);
+        for (key, value) in headers {
+            response.push_str(&format!("{}: {}\r\n", key, value));
+        }
+        response.push_str(&format!(
+            "Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        ));
+        response
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+/// A model that always returns the same text, or always fails.
+#[derive(Debug)]
+pub struct StubModel {
+    result: Result<String, String>,
+}
+
+impl StubModel {
+    pub fn answering(answer: &str) -> Self {
+        Self {
+            result: Ok(answer.to_string()),
+        }
+    }
+
+    pub fn failing(error: &str) -> Self {
+        Self {
+            result: Err(error.to_string()),
+        }
+    }
+}
+
+struct StubResponse(String);
+
+impl ModelResponse for StubResponse {
+    fn get_response(&self) -> Result<String, AgentError> {
+        Ok(self.0.clone())
+    }
+
+    fn get_tools_used(&self) -> Result<Vec<ToolCall>, AgentError> {
+        Ok(vec![])
+    }
+}
+
+impl Model for StubModel {
+    fn run(
+        &self,
+        _input_messages: Vec<Message>,
+        _tools: Vec<ToolInfo>,
+        _max_tokens: Option<usize>,
+        _args: Option<HashMap<String, Vec<String>>>,
+    ) -> Result<Box<dyn ModelResponse>, AgentError> {
+        match &self.result {
+            Ok(answer) => Ok(Box::new(StubResponse(answer.clone()))),
+            Err(error) => Err(AgentError::Generation(error.clone())),
+        }
+    }
+}
@@ -0,0 +1,100 @@
+//! Helpers for testing agents offline.
+//!
+//! Pair a [`ScriptedModel`] with the helpers below to run full agent loops without a live LLM
+//! and inspect the resulting step logs.
+//!
+//! ```rust
+//! use serde_json::json;
+//! use smolagents_rs::agents::{Agent, FunctionCallingAgent};
+//! use smolagents_rs::testing::{assert_no_errors, ScriptedModel};
+//!
+//! let model = ScriptedModel::new().with_tool_call("final_answer", json!({"answer": "42"}));
+//! let mut agent = FunctionCallingAgent::new(model.clone(), vec![], None, None, None, None).unwrap();
+//! assert_eq!(agent.run("What is the answer?", false, true).unwrap(), "42");
+//! assert_no_errors(agent.get_logs_mut());
+//! assert_eq!(model.calls().len(), 1);
+//! ```
+
+use crate::agents::{AgentStep, Step};
+use crate::errors::AgentError;
+use crate::models::openai::ToolCall;
+
+pub use crate::models::scripted::{RecordedCall, ScriptedModel, ScriptedResponse};
+
+/// The action steps in the logs, in order.
+pub fn action_steps(logs: &[Step]) -> Vec<&AgentStep> {
+    logs.iter()
+        .filter_map(|step| match step {
+            Step::ActionStep(step) => Some(step),
+            _ => None,
+        })
+        .collect()
+}
+
+/// All tool calls made during the run, including the final answer.
+pub fn tool_calls(logs: &[Step]) -> Vec<&ToolCall> {
+    action_steps(logs)
+        .into_iter()
+        .flat_map(|step| step.tool_calls())
+        .collect()
+}
+
+/// The names of the tools called during the run, excluding the final answer.
+pub fn called_tools(logs: &[Step]) -> Vec<&str> {
+    tool_calls(logs)
+        .into_iter()
+        .map(|call| call.function.name.as_str())
+        .filter(|name| *name != "final_answer")
+        .collect()
+}
+
+/// All observations recorded during the run, in order.
+pub fn observations(logs: &[Step]) -> Vec<&str> {
+    action_steps(logs)
+        .into_iter()
+        .flat_map(|step| step.observations())
+        .map(String::as_str)
+        .collect()
+}
+
+/// All step errors recorded during the run, in order.
+pub fn errors(logs: &[Step]) -> Vec<&AgentError> {
+    action_steps(logs)
+        .into_iter()
+        .filter_map(|step| step.error())
+        .collect()
+}
+
+/// Panics if any step recorded an error.
+pub fn assert_no_errors(logs: &[Step]) {
+    let errors = errors(logs);
+    assert!(
+        errors.is_empty(),
+        "Expected no step errors, got: {:?}",
+        errors
+    );
+}
+
+/// Panics if the tool was never called.
+pub fn assert_tool_called(logs: &[Step], name: &str) {
+    let called = called_tools(logs);
+    assert!(
+        called.contains(&name),
+        "Expected tool '{}' to be called, called tools: {:?}",
+        name,
+        called
+    );
+}
+
+/// Panics if no observation contains the text.
+pub fn assert_observation_contains(logs: &[Step], text: &str) {
+    let observations = observations(logs);
+    assert!(
+        observations
+            .iter()
+            .any(|observation| observation.contains(text)),
+        "Expected an observation containing '{}', got: {:?}",
+        text,
+        observations
+    );
+}
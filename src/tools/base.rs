@@ -1,3 +1,6 @@
+use std::collections::HashSet;
+use std::sync::{Mutex, OnceLock};
+
 use schemars::JsonSchema;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 
@@ -19,6 +22,20 @@ pub struct BaseTool {
     pub description: &'static str,
 }
 
+/// Returns a `'static` copy of a tool name built at runtime. Each string is only allocated once.
+pub(crate) fn intern(text: String) -> &'static str {
+    static STRINGS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut strings = STRINGS.get_or_init(Default::default).lock().unwrap();
+    match strings.get(text.as_str()) {
+        Some(text) => text,
+        None => {
+            let text: &'static str = Box::leak(text.into_boxed_str());
+            strings.insert(text);
+            text
+        }
+    }
+}
+
 impl Tool for BaseTool {
     type Params = serde_json::Value;
     fn name(&self) -> &'static str {
@@ -0,0 +1,202 @@
+//! This module contains the Bing search tool.
+
+use serde_json::Value;
+
+use super::web_client::WebClient;
+use super::web_search::{
+    fetch, SafeSearch, SearchProvider, SearchQuery, SearchResult, TimeRange, WebSearchTool,
+};
+use anyhow::Result;
+
+/// Searches the web with the Bing Web Search API.
+#[derive(Debug, Clone)]
+pub struct BingProvider {
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl BingProvider {
+    /// Creates the provider. The API key is read from `BING_API_KEY` if not given.
+    pub fn new(api_key: Option<String>) -> Self {
+        let api_key = api_key.unwrap_or_else(|| std::env::var("BING_API_KEY").unwrap_or_default());
+        Self {
+            api_key,
+            base_url: "https://api.bing.microsoft.com/v7.0/search".to_string(),
+        }
+    }
+}
+
+impl SearchProvider for BingProvider {
+    fn name(&self) -> &'static str {
+        "bing_search"
+    }
+    fn description(&self) -> &'static str {
+        "Performs a Bing web search for your query then returns a string of the top search results."
+    }
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>> {
+        let request = client
+            .get(&self.base_url)
+            .header("Ocp-Apim-Subscription-Key", &self.api_key)
+            .query(&bing_params(query));
+        parse_bing_results(&fetch(client, request)?)
+    }
+}
+
+/// Maps the query to the parameters of the Bing Web Search API. The year filter takes precedence over the time range.
+pub fn bing_params(query: &SearchQuery) -> Vec<(&'static str, String)> {
+    let mut params = vec![("q", query.query_text())];
+    if let Some(year) = &query.filter_year {
+        params.push(("freshness", format!("{}-01-01..{}-12-31", year, year)));
+    } else if let Some(time_range) = query.time_range {
+        let freshness = match time_range {
+            TimeRange::Day => "Day".to_string(),
+            TimeRange::Week => "Week".to_string(),
+            TimeRange::Month => "Month".to_string(),
+            // Bing has no yearly filter, so ask for the last 365 days instead.
+            TimeRange::Year => {
+                let today = chrono::Local::now().date_naive();
+                format!("{}..{}", today - chrono::Duration::days(365), today)
+            }
+        };
+        params.push(("freshness", freshness));
+    }
+    if let Some(count) = query.count {
+        params.push(("count", count.to_string()));
+    }
+    let offset = query.offset(query.count.unwrap_or(10));
+    if offset > 0 {
+        params.push(("offset", offset.to_string()));
+    }
+    match (&query.language, &query.region) {
+        (Some(language), Some(region)) => params.push((
+            "mkt",
+            format!("{}-{}", language.to_lowercase(), region.to_uppercase()),
+        )),
+        (language, region) => {
+            if let Some(region) = region {
+                params.push(("cc", region.to_uppercase()));
+            }
+            if let Some(language) = language {
+                params.push(("setLang", language.to_lowercase()));
+            }
+        }
+    }
+    if let Some(safe_search) = query.safe_search {
+        let level = match safe_search {
+            SafeSearch::Off => "Off",
+            SafeSearch::Moderate => "Moderate",
+            SafeSearch::Strict => "Strict",
+        };
+        params.push(("safeSearch", level.to_string()));
+    }
+    params
+}
+
+/// Parses the web pages of a Bing Web Search API response.
+pub fn parse_bing_results(body: &str) -> Result<Vec<SearchResult>> {
+    let response: Value = serde_json::from_str(body)?;
+    let Some(results) = response["webPages"]["value"].as_array() else {
+        return Ok(Vec::new());
+    };
+    Ok(results
+        .iter()
+        .filter_map(|result| {
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            Some(SearchResult {
+                title: text(&result["name"])?,
+                url: text(&result["url"])?,
+                snippet: text(&result["snippet"]).unwrap_or_default(),
+                date: text(&result["datePublished"]),
+                source: text(&result["siteName"]),
+            })
+        })
+        .collect())
+}
+
+pub type BingSearchTool = WebSearchTool<BingProvider>;
+
+impl WebSearchTool<BingProvider> {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self::with_provider(BingProvider::new(api_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+
+    const RESULTS_JSON: &str = r#"{
+        "_type": "SearchResponse",
+        "webPages": {
+            "totalEstimatedMatches": 1,
+            "value": [
+                {
+                    "id": "https://api.bing.microsoft.com/api/v7/#WebPages.0",
+                    "name": "Paris - Wikipedia",
+                    "url": "https://en.wikipedia.org/wiki/Paris",
+                    "siteName": "Wikipedia",
+                    "snippet": "Paris is the capital of France.",
+                    "dateLastCrawled": "2024-01-02T00:00:00.0000000Z"
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_bing_results() {
+        let results = parse_bing_results(RESULTS_JSON).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Paris - Wikipedia".to_string(),
+                snippet: "Paris is the capital of France.".to_string(),
+                url: "https://en.wikipedia.org/wiki/Paris".to_string(),
+                date: None,
+                source: Some("Wikipedia".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_bing_provider_sends_key_and_freshness() {
+        let server = MockServer::start(vec![MockServer::response(200, &[], RESULTS_JSON)]);
+        let mut provider = BingProvider::new(Some("key".to_string()));
+        provider.base_url = server.url();
+        let query = SearchQuery::new("paris").with_filter_year("2024");
+        assert_eq!(
+            provider
+                .search(&query, &WebClient::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        let request = &server.requests()[0];
+        assert_eq!(request.header("ocp-apim-subscription-key"), Some("key"));
+        assert_eq!(request.path, "/?q=paris&freshness=2024-01-01..2024-12-31");
+
+        let query = SearchQuery::new("paris")
+            .with_site("wikipedia.org")
+            .with_time_range(TimeRange::Day)
+            .with_count(5)
+            .with_page(2)
+            .with_language("fr")
+            .with_region("fr")
+            .with_safe_search(SafeSearch::Off);
+        let params = bing_params(&query)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                "q=site:wikipedia.org paris",
+                "freshness=Day",
+                "count=5",
+                "offset=5",
+                "mkt=fr-FR",
+                "safeSearch=Off"
+            ]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+//! This module contains the Brave search tool.
+
+use serde_json::Value;
+
+use super::web_client::WebClient;
+use super::web_search::{
+    fetch, SafeSearch, SearchProvider, SearchQuery, SearchResult, WebSearchTool,
+};
+use anyhow::Result;
+
+/// Searches the web with the Brave Search API.
+#[derive(Debug, Clone)]
+pub struct BraveProvider {
+    pub api_key: String,
+    pub base_url: String,
+}
+
+impl BraveProvider {
+    /// Creates the provider. The API key is read from `BRAVE_API_KEY` if not given.
+    pub fn new(api_key: Option<String>) -> Self {
+        let api_key = api_key.unwrap_or_else(|| std::env::var("BRAVE_API_KEY").unwrap_or_default());
+        Self {
+            api_key,
+            base_url: "https://api.search.brave.com/res/v1/web/search".to_string(),
+        }
+    }
+}
+
+impl SearchProvider for BraveProvider {
+    fn name(&self) -> &'static str {
+        "brave_search"
+    }
+    fn description(&self) -> &'static str {
+        "Performs a Brave web search for your query then returns a string of the top search results."
+    }
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>> {
+        let request = client
+            .get(&self.base_url)
+            .header("Accept", "application/json")
+            .header("X-Subscription-Token", &self.api_key)
+            .query(&brave_params(query));
+        parse_brave_results(&fetch(client, request)?)
+    }
+}
+
+/// Maps the query to the parameters of the Brave Search API. The year filter takes precedence over the time range.
+pub fn brave_params(query: &SearchQuery) -> Vec<(&'static str, String)> {
+    let mut params = vec![("q", query.query_text())];
+    if let Some(year) = &query.filter_year {
+        params.push(("freshness", format!("{}-01-01to{}-12-31", year, year)));
+    } else if let Some(time_range) = query.time_range {
+        params.push(("freshness", format!("p{}", time_range.letter())));
+    }
+    if let Some(count) = query.count {
+        params.push(("count", count.to_string()));
+    }
+    // Brave counts the offset in pages rather than results.
+    if let Some(page) = query.page.filter(|page| *page > 1) {
+        params.push(("offset", (page - 1).to_string()));
+    }
+    if let Some(region) = &query.region {
+        params.push(("country", region.to_lowercase()));
+    }
+    if let Some(language) = &query.language {
+        params.push(("search_lang", language.to_lowercase()));
+    }
+    if let Some(safe_search) = query.safe_search {
+        let level = match safe_search {
+            SafeSearch::Off => "off",
+            SafeSearch::Moderate => "moderate",
+            SafeSearch::Strict => "strict",
+        };
+        params.push(("safesearch", level.to_string()));
+    }
+    params
+}
+
+/// Parses the web results of a Brave Search API response.
+pub fn parse_brave_results(body: &str) -> Result<Vec<SearchResult>> {
+    let response: Value = serde_json::from_str(body)?;
+    let Some(results) = response["web"]["results"].as_array() else {
+        return Ok(Vec::new());
+    };
+    Ok(results
+        .iter()
+        .filter_map(|result| {
+            let text = |value: &Value| value.as_str().map(str::to_string);
+            Some(SearchResult {
+                title: text(&result["title"])?,
+                url: text(&result["url"])?,
+                snippet: text(&result["description"]).unwrap_or_default(),
+                date: text(&result["age"]).or_else(|| text(&result["page_age"])),
+                source: text(&result["profile"]["name"]),
+            })
+        })
+        .collect())
+}
+
+pub type BraveSearchTool = WebSearchTool<BraveProvider>;
+
+impl WebSearchTool<BraveProvider> {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self::with_provider(BraveProvider::new(api_key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use crate::tools::TimeRange;
+
+    const RESULTS_JSON: &str = r#"{
+        "type": "search",
+        "web": {
+            "type": "search",
+            "results": [
+                {
+                    "title": "Paris - Wikipedia",
+                    "url": "https://en.wikipedia.org/wiki/Paris",
+                    "description": "Paris is the capital of France.",
+                    "age": "January 2, 2024",
+                    "profile": {"name": "Wikipedia"}
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_parse_brave_results() {
+        let results = parse_brave_results(RESULTS_JSON).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Paris - Wikipedia".to_string(),
+                snippet: "Paris is the capital of France.".to_string(),
+                url: "https://en.wikipedia.org/wiki/Paris".to_string(),
+                date: Some("January 2, 2024".to_string()),
+                source: Some("Wikipedia".to_string()),
+            }]
+        );
+        assert!(parse_brave_results(r#"{"type": "search"}"#)
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn test_brave_provider_sends_key_and_freshness() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], RESULTS_JSON),
+            MockServer::response(401, &[], r#"{"error": "unauthorized"}"#),
+        ]);
+        let mut provider = BraveProvider::new(Some("key".to_string()));
+        provider.base_url = server.url();
+        let query = SearchQuery::new("paris").with_filter_year("2024");
+        assert_eq!(
+            provider
+                .search(&query, &WebClient::default())
+                .unwrap()
+                .len(),
+            1
+        );
+        let request = &server.requests()[0];
+        assert_eq!(request.header("x-subscription-token"), Some("key"));
+        assert_eq!(request.path, "/?q=paris&freshness=2024-01-01to2024-12-31");
+
+        let query = SearchQuery::new("paris")
+            .with_time_range(TimeRange::Week)
+            .with_count(5)
+            .with_page(3)
+            .with_region("FR")
+            .with_safe_search(SafeSearch::Strict);
+        let params = brave_params(&query)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                "q=paris",
+                "freshness=pw",
+                "count=5",
+                "offset=2",
+                "country=fr",
+                "safesearch=strict"
+            ]
+        );
+
+        let error = provider
+            .search(&query, &WebClient::default())
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("HTTP 401"));
+    }
+}
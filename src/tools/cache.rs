@@ -0,0 +1,477 @@
+//! This module contains an opt-in cache of tool outputs, so repeated calls with the same arguments, in the same run
+//! or across runs, do not hit the network again.
+//!
+//! A [`ToolCache`] wraps the tools whose outputs may be reused, those whose [`Tool::cacheable`] flag is set unless
+//! it is overridden per tool. The outputs are kept by a [`CacheBackend`]: [`MemoryCache`] for a single process or
+//! [`DiskCache`] to share them between runs.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use smolagents_rs::tools::{AnyTool, DuckDuckGoSearchTool, MemoryCache, ToolCache, VisitWebsiteTool};
+//!
+//! let cache = ToolCache::new(MemoryCache::new())
+//!     .with_ttl(Some(Duration::from_secs(3600)))
+//!     .with_tool_ttl("visit_website", Some(Duration::from_secs(600)));
+//! let tools: Vec<Box<dyn AnyTool>> = cache.wrap_all(vec![
+//!     Box::new(DuckDuckGoSearchTool::new()),
+//!     Box::new(VisitWebsiteTool::new()),
+//! ]);
+//! ```
+//!
+//! Failed calls are never cached. The data of image and file outputs is stored as base64.
+//!
+//! [`Tool::cacheable`]: super::tool_traits::Tool::cacheable
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+
+use super::output::ToolOutput;
+use super::tool_traits::{AnyTool, ToolInfo};
+use crate::errors::AgentError;
+
+/// Where a [`ToolCache`] keeps the outputs.
+pub trait CacheBackend: Debug + Send + Sync {
+    /// The output stored under the key, unless it has expired.
+    fn get(&self, key: &str) -> Option<String>;
+    /// Stores an output under the key, for the given time or forever.
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>);
+}
+
+/// Keeps the outputs in memory, for the lifetime of the process. Expired outputs are dropped when new ones are stored.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, (String, Option<Instant>)>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CacheBackend for MemoryCache {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((_, Some(expires_at))) if *expires_at <= Instant::now() => {
+                entries.remove(key);
+                None
+            }
+            entry => entry.map(|(value, _)| value.clone()),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries
+            .retain(|_, (_, expires_at)| !expires_at.is_some_and(|expires_at| expires_at <= now));
+        entries.insert(
+            key.to_string(),
+            (value.to_string(), ttl.map(|ttl| now + ttl)),
+        );
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DiskEntry {
+    key: String,
+    value: String,
+    /// Milliseconds since the unix epoch.
+    expires_at: Option<u128>,
+}
+
+/// Keeps the outputs in a directory, one JSON file per call, so they survive the process.
+#[derive(Debug, Clone)]
+pub struct DiskCache {
+    dir: PathBuf,
+}
+
+impl DiskCache {
+    /// Uses the directory, creating it if needed.
+    pub fn new(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            anyhow::anyhow!("Failed to create cache directory {}: {}", dir.display(), e)
+        })?;
+        Ok(DiskCache { dir })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", hash(key)))
+    }
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis()
+}
+
+impl CacheBackend for DiskCache {
+    fn get(&self, key: &str) -> Option<String> {
+        let path = self.path(key);
+        let content = std::fs::read_to_string(&path).ok()?;
+        let entry = serde_json::from_str::<DiskEntry>(&content).ok()?;
+        if entry.key != key {
+            return None;
+        }
+        if entry
+            .expires_at
+            .is_some_and(|expires_at| expires_at <= now_millis())
+        {
+            let _ = std::fs::remove_file(&path);
+            return None;
+        }
+        Some(entry.value)
+    }
+
+    fn set(&self, key: &str, value: &str, ttl: Option<Duration>) {
+        let entry = DiskEntry {
+            key: key.to_string(),
+            value: value.to_string(),
+            expires_at: ttl.map(|ttl| now_millis() + ttl.as_millis()),
+        };
+        let result = serde_json::to_string(&entry)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| Ok(std::fs::write(self.path(key), content)?));
+        if let Err(e) = result {
+            log::warn!("Failed to write to the tool cache: {}", e);
+        }
+    }
+}
+
+fn hash(value: &str) -> String {
+    let digest = Sha256::digest(value.as_bytes());
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// The arguments with sorted object keys and without null values, which mean the same as a missing argument.
+pub fn canonicalize(value: &Value) -> Value {
+    match value {
+        Value::Object(object) => {
+            let mut entries = object
+                .iter()
+                .filter(|(_, value)| !value.is_null())
+                .map(|(key, value)| (key.clone(), canonicalize(value)))
+                .collect::<Vec<_>>();
+            entries.sort_by(|a, b| a.0.cmp(&b.0));
+            Value::Object(entries.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.iter().map(canonicalize).collect()),
+        value => value.clone(),
+    }
+}
+
+/// The key of a call: the tool name and its canonicalized arguments.
+pub fn cache_key(tool: &str, arguments: &Value) -> String {
+    json!({ "tool": tool, "arguments": canonicalize(arguments) }).to_string()
+}
+
+/// Decides which tools are cached and for how long. Clones share the backend.
+#[derive(Debug, Clone)]
+pub struct ToolCache {
+    backend: Arc<dyn CacheBackend>,
+    ttl: Option<Duration>,
+    tool_ttls: HashMap<String, Option<Duration>>,
+    cacheable: HashMap<String, bool>,
+}
+
+impl ToolCache {
+    /// A cache that keeps the outputs forever, until the backend drops them.
+    pub fn new(backend: impl CacheBackend + 'static) -> Self {
+        ToolCache {
+            backend: Arc::new(backend),
+            ttl: None,
+            tool_ttls: HashMap::new(),
+            cacheable: HashMap::new(),
+        }
+    }
+
+    /// How long outputs are reused, or `None` for forever.
+    pub fn with_ttl(mut self, ttl: Option<Duration>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// How long the outputs of one tool are reused, instead of the default TTL.
+    pub fn with_tool_ttl(mut self, tool: &str, ttl: Option<Duration>) -> Self {
+        self.tool_ttls.insert(tool.to_string(), ttl);
+        self
+    }
+
+    /// Overrides whether the outputs of a tool are cached.
+    pub fn with_cacheable(mut self, tool: &str, cacheable: bool) -> Self {
+        self.cacheable.insert(tool.to_string(), cacheable);
+        self
+    }
+
+    pub fn is_cacheable(&self, tool: &dyn AnyTool) -> bool {
+        self.cacheable
+            .get(tool.name())
+            .copied()
+            .unwrap_or_else(|| tool.cacheable())
+    }
+
+    pub fn ttl(&self, tool: &str) -> Option<Duration> {
+        self.tool_ttls.get(tool).copied().unwrap_or(self.ttl)
+    }
+
+    /// Caches the outputs of the tool, whether it is cacheable or not.
+    pub fn wrap(&self, tool: Box<dyn AnyTool>) -> Box<dyn AnyTool> {
+        Box::new(CachedTool {
+            cache: self.clone(),
+            inner: tool,
+        })
+    }
+
+    /// Caches the outputs of the cacheable tools and leaves the others as they are.
+    pub fn wrap_all(&self, tools: Vec<Box<dyn AnyTool>>) -> Vec<Box<dyn AnyTool>> {
+        tools
+            .into_iter()
+            .map(|tool| {
+                if self.is_cacheable(tool.as_ref()) {
+                    self.wrap(tool)
+                } else {
+                    tool
+                }
+            })
+            .collect()
+    }
+}
+
+/// A tool whose outputs are looked up in a [`ToolCache`] before it is called.
+#[derive(Debug)]
+pub struct CachedTool {
+    cache: ToolCache,
+    inner: Box<dyn AnyTool>,
+}
+
+impl AnyTool for CachedTool {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn description(&self) -> &'static str {
+        self.inner.description()
+    }
+
+    fn forward_json(&self, json_args: Value) -> Result<String, AgentError> {
+        self.forward_json_output(json_args)
+            .map(|output| output.render())
+    }
+
+    fn forward_json_output(&self, json_args: Value) -> Result<ToolOutput, AgentError> {
+        let key = cache_key(self.name(), &json_args);
+        let cached = self.cache.backend.get(&key);
+        if let Some(output) = cached.and_then(|output| serde_json::from_str(&output).ok()) {
+            log::debug!("Reusing the cached output of {}", self.name());
+            return Ok(output);
+        }
+        let output = self.inner.forward_json_output(json_args)?;
+        match serde_json::to_string(&output) {
+            Ok(value) => self
+                .cache
+                .backend
+                .set(&key, &value, self.cache.ttl(self.name())),
+            Err(e) => log::warn!("Failed to cache the output of {}: {}", self.name(), e),
+        }
+        Ok(output)
+    }
+
+    fn tool_info(&self) -> ToolInfo {
+        self.inner.tool_info()
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTool> {
+        Box::new(CachedTool {
+            cache: self.cache.clone(),
+            inner: self.inner.clone_box(),
+        })
+    }
+
+    fn reset(&self) {
+        self.inner.reset()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.inner.cacheable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{BaseTool, Tool};
+    use schemars::JsonSchema;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Deserialize, JsonSchema)]
+    struct CountingToolParams {
+        query: String,
+        limit: Option<usize>,
+    }
+
+    /// Echoes the query `limit` times with the number of calls so far, failing on an empty query.
+    #[derive(Debug, Clone)]
+    struct CountingTool {
+        tool: BaseTool,
+        calls: Arc<AtomicUsize>,
+        cacheable: bool,
+    }
+
+    impl CountingTool {
+        fn new(name: &'static str, cacheable: bool) -> Self {
+            CountingTool {
+                tool: BaseTool {
+                    name,
+                    description: "Counts calls.",
+                },
+                calls: Arc::default(),
+                cacheable,
+            }
+        }
+    }
+
+    impl Tool for CountingTool {
+        type Params = CountingToolParams;
+        fn name(&self) -> &'static str {
+            self.tool.name
+        }
+        fn description(&self) -> &'static str {
+            self.tool.description
+        }
+        fn cacheable(&self) -> bool {
+            self.cacheable
+        }
+        fn forward(&self, arguments: CountingToolParams) -> Result<String> {
+            let calls = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if arguments.query.is_empty() {
+                anyhow::bail!("Empty query");
+            }
+            let query = arguments.query.repeat(arguments.limit.unwrap_or(1));
+            Ok(format!("{} {}", query, calls))
+        }
+    }
+
+    #[test]
+    fn test_cache_key_is_canonical() {
+        assert_eq!(
+            cache_key("search", &json!({"query": "rust", "limit": 3})),
+            cache_key(
+                "search",
+                &json!({"limit": 3, "query": "rust", "page": null})
+            )
+        );
+        assert_ne!(
+            cache_key("search", &json!({"query": "rust"})),
+            cache_key("visit", &json!({"query": "rust"}))
+        );
+    }
+
+    #[test]
+    fn test_cached_tool() {
+        let tool = CountingTool::new("search", true);
+        let cache = ToolCache::new(MemoryCache::new());
+        let cached = cache.wrap(Box::new(tool.clone()));
+
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 1");
+        assert_eq!(
+            cached
+                .forward_json(json!({"query": "a", "limit": null}))
+                .unwrap(),
+            "a 1"
+        );
+        assert_eq!(cached.forward_json(json!({"query": "b"})).unwrap(), "b 2");
+        assert!(cached.forward_json(json!({"query": ""})).is_err());
+        assert!(cached.forward_json(json!({"query": ""})).is_err());
+        assert_eq!(tool.calls.load(Ordering::SeqCst), 4);
+
+        let clone = cached.clone_box();
+        assert_eq!(clone.forward_json(json!({"query": "b"})).unwrap(), "b 2");
+        assert_eq!(tool.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn test_ttl() {
+        let tool = CountingTool::new("search", true);
+        let cache = ToolCache::new(MemoryCache::new())
+            .with_tool_ttl("search", Some(Duration::from_millis(50)));
+        assert_eq!(cache.ttl("search"), Some(Duration::from_millis(50)));
+        assert_eq!(cache.ttl("other"), None);
+        let cached = cache.wrap(Box::new(tool));
+
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 1");
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 1");
+        std::thread::sleep(Duration::from_millis(80));
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 2");
+    }
+
+    #[test]
+    fn test_memory_cache_prunes_expired_entries() {
+        let cache = MemoryCache::new();
+        cache.set("a", "1", Some(Duration::from_millis(20)));
+        cache.set("b", "2", None);
+        std::thread::sleep(Duration::from_millis(40));
+        cache.set("c", "3", Some(Duration::from_secs(60)));
+        let mut keys = cache
+            .entries
+            .lock()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_disk_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = CountingTool::new("search", true);
+        let cached = ToolCache::new(DiskCache::new(dir.path().join("cache")).unwrap())
+            .wrap(Box::new(tool.clone()));
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 1");
+
+        let cached = ToolCache::new(DiskCache::new(dir.path().join("cache")).unwrap())
+            .wrap(Box::new(tool.clone()));
+        assert_eq!(cached.forward_json(json!({"query": "a"})).unwrap(), "a 1");
+        assert_eq!(tool.calls.load(Ordering::SeqCst), 1);
+
+        let backend = DiskCache::new(dir.path().join("cache")).unwrap();
+        backend.set("key", "value", Some(Duration::ZERO));
+        assert_eq!(backend.get("key"), None);
+    }
+
+    #[test]
+    fn test_wrap_all() {
+        let cache = ToolCache::new(MemoryCache::new()).with_cacheable("write", true);
+        let tools: Vec<Box<dyn AnyTool>> = vec![
+            Box::new(CountingTool::new("search", true)),
+            Box::new(CountingTool::new("write", false)),
+            Box::new(CountingTool::new("shell", false)),
+        ];
+        let tools = cache.wrap_all(tools);
+        for tool in &tools {
+            tool.forward_json(json!({"query": "a"})).unwrap();
+        }
+        let outputs = tools
+            .iter()
+            .map(|tool| tool.forward_json(json!({"query": "a"})).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(outputs, vec!["a 1", "a 1", "a 2"]);
+    }
+}
@@ -1,118 +1,212 @@
 //! This module contains the DuckDuckGo search tool.
 
-use schemars::JsonSchema;
 use scraper::Selector;
-use serde::{Deserialize, Serialize};
 
-use super::base::BaseTool;
-use super::tool_traits::Tool;
+use super::web_client::WebClient;
+use super::web_search::{
+    fetch, SafeSearch, SearchProvider, SearchQuery, SearchResult, WebSearchTool,
+};
 use anyhow::Result;
 
-#[derive(Deserialize, JsonSchema)]
-#[schemars(title = "DuckDuckGoSearchToolParams")]
-pub struct DuckDuckGoSearchToolParams {
-    #[schemars(description = "The query to search for")]
-    query: String,
+/// Searches the HTML version of DuckDuckGo. No API key is needed.
+///
+/// DuckDuckGo cannot filter by year, so the year is added to the query instead. The region and
+/// language are combined into DuckDuckGo's region code, e.g. `de-de`.
+#[derive(Debug, Clone)]
+pub struct DuckDuckGoProvider {
+    pub base_url: String,
 }
 
-#[derive(Debug, Serialize, Default)]
-pub struct SearchResult {
-    pub title: String,
-    pub snippet: String,
-    pub url: String,
+impl Default for DuckDuckGoProvider {
+    fn default() -> Self {
+        Self {
+            base_url: "https://html.duckduckgo.com/html/".to_string(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Default, Clone)]
-pub struct DuckDuckGoSearchTool {
-    pub tool: BaseTool,
+impl SearchProvider for DuckDuckGoProvider {
+    fn name(&self) -> &'static str {
+        "duckduckgo_search"
+    }
+    fn description(&self) -> &'static str {
+        "Performs a duckduckgo web search for your query then returns a string of the top search results."
+    }
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>> {
+        let request = client.get(&self.base_url).query(&duckduckgo_params(query));
+        parse_duckduckgo_results(&fetch(client, request)?)
+    }
 }
 
-impl DuckDuckGoSearchTool {
-    pub fn new() -> Self {
-        DuckDuckGoSearchTool {
-            tool: BaseTool {
-                name: "duckduckgo_search",
-                description: "Performs a duckduckgo web search for your query then returns a string of the top search results.",
-            },
-        }
+/// The number of results on a page of the HTML version of DuckDuckGo.
+const DUCKDUCKGO_PAGE_SIZE: usize = 30;
+
+/// Maps the query to the parameters of the HTML endpoint.
+pub fn duckduckgo_params(query: &SearchQuery) -> Vec<(&'static str, String)> {
+    let mut params = vec![("q", query.query_with_year())];
+    let region = match (&query.region, &query.language) {
+        (Some(region), _) if region.contains('-') => Some(region.to_lowercase()),
+        (Some(region), language) => Some(format!(
+            "{}-{}",
+            region.to_lowercase(),
+            language.as_deref().unwrap_or("en").to_lowercase()
+        )),
+        (None, _) => None,
+    };
+    if let Some(region) = region {
+        params.push(("kl", region));
+    }
+    if let Some(safe_search) = query.safe_search {
+        let kp = match safe_search {
+            SafeSearch::Strict => "1",
+            SafeSearch::Moderate => "-1",
+            SafeSearch::Off => "-2",
+        };
+        params.push(("kp", kp.to_string()));
+    }
+    if let Some(time_range) = query.time_range {
+        params.push(("df", time_range.letter().to_string()));
     }
+    let offset = query.offset(DUCKDUCKGO_PAGE_SIZE);
+    if offset > 0 {
+        params.push(("s", offset.to_string()));
+        params.push(("dc", (offset + 1).to_string()));
+    }
+    params
+}
 
-    pub fn forward(&self, query: &str) -> Result<Vec<SearchResult>> {
-        let client = reqwest::blocking::Client::builder()
-            .user_agent("Mozilla/5.0 (compatible; MyRustTool/1.0)")
-            .build()?;
-        let response = client
-            .get(format!("https://html.duckduckgo.com/html/?q={}", query))
-            .send()?;
-        let html = response.text().unwrap();
-        let document = scraper::Html::parse_document(&html);
-        let result_selector = Selector::parse(".result")
-            .map_err(|e| anyhow::anyhow!("Failed to parse result selector: {}", e))?;
-        let title_selector = Selector::parse(".result__title a")
-            .map_err(|e| anyhow::anyhow!("Failed to parse title selector: {}", e))?;
-        let snippet_selector = Selector::parse(".result__snippet")
-            .map_err(|e| anyhow::anyhow!("Failed to parse snippet selector: {}", e))?;
-        let url_selector = Selector::parse(".result__url")
-            .map_err(|e| anyhow::anyhow!("Failed to parse url selector: {}", e))?;
-        let mut results = Vec::new();
-
-        for result in document.select(&result_selector) {
-            let title_element = result.select(&title_selector).next();
-            let snippet_element = result.select(&snippet_selector).next();
-            if let (Some(title), Some(snippet)) = (title_element, snippet_element) {
-                let title_text = title.text().collect::<String>().trim().to_string();
-                let snippet_text = snippet.text().collect::<String>().trim().to_string();
-                let url = result
-                    .select(&url_selector)
-                    .next()
-                    .unwrap()
-                    .text()
-                    .collect::<Vec<_>>()
-                    .join("")
-                    .trim()
-                    .to_string();
-                if !title_text.is_empty() && !url.is_empty() {
-                    results.push(SearchResult {
-                        title: title_text,
-                        snippet: snippet_text,
-                        url,
-                    });
-                }
+/// Parses the results page of the HTML version of DuckDuckGo.
+pub fn parse_duckduckgo_results(html: &str) -> Result<Vec<SearchResult>> {
+    let document = scraper::Html::parse_document(html);
+    let result_selector = Selector::parse(".result")
+        .map_err(|e| anyhow::anyhow!("Failed to parse result selector: {}", e))?;
+    let title_selector = Selector::parse(".result__title a")
+        .map_err(|e| anyhow::anyhow!("Failed to parse title selector: {}", e))?;
+    let snippet_selector = Selector::parse(".result__snippet")
+        .map_err(|e| anyhow::anyhow!("Failed to parse snippet selector: {}", e))?;
+    let url_selector = Selector::parse(".result__url")
+        .map_err(|e| anyhow::anyhow!("Failed to parse url selector: {}", e))?;
+    let mut results = Vec::new();
+
+    for result in document.select(&result_selector) {
+        let title_element = result.select(&title_selector).next();
+        let snippet_element = result.select(&snippet_selector).next();
+        let url_element = result.select(&url_selector).next();
+        if let (Some(title), Some(snippet), Some(url)) =
+            (title_element, snippet_element, url_element)
+        {
+            let title_text = title.text().collect::<String>().trim().to_string();
+            let snippet_text = snippet.text().collect::<String>().trim().to_string();
+            let url = url.text().collect::<String>().trim().to_string();
+            if !title_text.is_empty() && !url.is_empty() {
+                results.push(SearchResult {
+                    title: title_text,
+                    snippet: snippet_text,
+                    url,
+                    ..Default::default()
+                });
             }
         }
-        Ok(results)
     }
+    Ok(results)
 }
 
-impl Tool for DuckDuckGoSearchTool {
-    type Params = DuckDuckGoSearchToolParams;
-    fn name(&self) -> &'static str {
-        self.tool.name
-    }
-    fn description(&self) -> &'static str {
-        self.tool.description
+pub type DuckDuckGoSearchTool = WebSearchTool<DuckDuckGoProvider>;
+
+impl WebSearchTool<DuckDuckGoProvider> {
+    pub fn new() -> Self {
+        Self::with_provider(DuckDuckGoProvider::default())
     }
-    fn forward(&self, arguments: DuckDuckGoSearchToolParams) -> Result<String> {
-        let query = arguments.query;
-        let results = self.forward(&query)?;
-        let results_string = results
-            .iter()
-            .map(|r| format!("[{}]({}) \n{}", r.title, r.url, r.snippet))
-            .collect::<Vec<_>>()
-            .join("\n\n");
-        Ok(results_string)
+}
+
+impl Default for WebSearchTool<DuckDuckGoProvider> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::MockServer;
+    use crate::tools::TimeRange;
+
+    const RESULTS_HTML: &str = r#"
+<html><body>
+  <div class="result results_links web-result">
+    <h2 class="result__title"><a class="result__a" href="//duckduckgo.com/l/?uddg=paris">Paris - Wikipedia</a></h2>
+    <a class="result__url" href="//duckduckgo.com/l/?uddg=paris"> en.wikipedia.org/wiki/Paris </a>
+    <a class="result__snippet">Paris is the <b>capital</b> of France.</a>
+  </div>
+  <div class="result result--ad">
+    <h2 class="result__title"><a class="result__a">Sponsored</a></h2>
+    <a class="result__snippet">No url on this one.</a>
+  </div>
+</body></html>"#;
+
+    #[test]
+    fn test_parse_duckduckgo_results() {
+        let results = parse_duckduckgo_results(RESULTS_HTML).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Paris - Wikipedia".to_string(),
+                snippet: "Paris is the capital of France.".to_string(),
+                url: "en.wikipedia.org/wiki/Paris".to_string(),
+                ..Default::default()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_duckduckgo_provider_sends_query() {
+        let server = MockServer::start(vec![MockServer::response(200, &[], RESULTS_HTML)]);
+        let provider = DuckDuckGoProvider {
+            base_url: server.url(),
+        };
+        let results = provider
+            .search(
+                &SearchQuery::new("capital of France").with_filter_year("2024"),
+                &WebClient::default(),
+            )
+            .unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(server.requests()[0].path, "/?q=capital+of+France+2024");
+    }
+
+    #[test]
+    fn test_duckduckgo_params() {
+        let query = SearchQuery::new("rust")
+            .with_site("rust-lang.org")
+            .with_region("de")
+            .with_language("de")
+            .with_safe_search(SafeSearch::Off)
+            .with_time_range(TimeRange::Month)
+            .with_page(2);
+        let params = duckduckgo_params(&query)
+            .into_iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            vec![
+                "q=site:rust-lang.org rust",
+                "kl=de-de",
+                "kp=-2",
+                "df=m",
+                "s=30",
+                "dc=31"
+            ]
+        );
+        let params = duckduckgo_params(&SearchQuery::new("rust").with_region("wt-wt"));
+        assert_eq!(params[1], ("kl", "wt-wt".to_string()));
+    }
 
     #[test]
     fn test_duckduckgo_search_tool() {
         let tool = DuckDuckGoSearchTool::new();
         let query = "What is the capital of France?";
-        let result = tool.forward(query).unwrap();
+        let result = tool.search(&SearchQuery::new(query)).unwrap();
         assert!(result.iter().any(|r| r.snippet.contains("Paris")));
     }
 }
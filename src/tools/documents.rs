@@ -0,0 +1,263 @@
+//! This module converts non-HTML documents to text for the visit website tool.
+//!
+//! The kind of document is taken from the `Content-Type` header, falling back to the file extension of the url.
+//! PDF text extraction needs the `pdf` feature.
+
+use anyhow::{anyhow, Result};
+use reqwest::Url;
+
+/// The number of CSV rows shown in the table preview.
+pub const CSV_PREVIEW_ROWS: usize = 50;
+
+/// The kinds of documents the visit website tool can read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DocumentKind {
+    Html,
+    Pdf,
+    Json,
+    /// Comma or tab separated values, with the delimiter.
+    Csv(u8),
+    Text,
+    /// A type that cannot be read as text, with its mime type.
+    Binary(String),
+}
+
+/// Picks the kind of document from the content type, or from the url if the content type is missing or generic.
+pub fn document_kind(content_type: Option<&str>, url: &Url) -> DocumentKind {
+    let mime = content_type
+        .and_then(|content_type| content_type.split(';').next())
+        .map(|mime| mime.trim().to_lowercase())
+        .unwrap_or_default();
+    let kind = match mime.as_str() {
+        "" | "application/octet-stream" | "binary/octet-stream" => None,
+        "text/html" | "application/xhtml+xml" => Some(DocumentKind::Html),
+        "application/pdf" | "application/x-pdf" => Some(DocumentKind::Pdf),
+        "application/json" | "text/json" => Some(DocumentKind::Json),
+        "text/csv" | "application/csv" => Some(DocumentKind::Csv(b',')),
+        "text/tab-separated-values" => Some(DocumentKind::Csv(b'\t')),
+        "application/xml"
+        | "application/javascript"
+        | "application/x-yaml"
+        | "application/yaml" => Some(DocumentKind::Text),
+        mime if mime.ends_with("+json") => Some(DocumentKind::Json),
+        mime if mime.ends_with("+xml") => Some(DocumentKind::Text),
+        mime if mime.starts_with("text/") => Some(DocumentKind::Text),
+        mime => Some(DocumentKind::Binary(mime.to_string())),
+    };
+    if let Some(kind) = kind {
+        return kind;
+    }
+    let extension = url
+        .path()
+        .rsplit_once('.')
+        .map(|(_, extension)| extension.to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "pdf" => DocumentKind::Pdf,
+        "json" => DocumentKind::Json,
+        "csv" => DocumentKind::Csv(b','),
+        "tsv" => DocumentKind::Csv(b'\t'),
+        "txt" | "md" | "xml" | "yaml" | "yml" => DocumentKind::Text,
+        "" | "html" | "htm" => DocumentKind::Html,
+        _ if mime.is_empty() => DocumentKind::Html,
+        _ => DocumentKind::Binary(mime),
+    }
+}
+
+/// Extracts the text of a PDF.
+///
+/// `pdf_extract` panics on some malformed PDFs, e.g. a page without a `/MediaBox`, so the panic is caught and
+/// reported as an error instead of taking the agent down.
+#[cfg(feature = "pdf")]
+pub fn pdf_to_text(bytes: &[u8]) -> Result<String> {
+    match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem(bytes)) {
+        Ok(Ok(text)) => Ok(text.trim().to_string()),
+        Ok(Err(e)) => Err(anyhow!("Failed to extract text from the PDF: {}", e)),
+        Err(_) => Err(anyhow!(
+            "Failed to extract text from the PDF: the file is malformed"
+        )),
+    }
+}
+
+/// Extracts the text of a PDF.
+#[cfg(not(feature = "pdf"))]
+pub fn pdf_to_text(_bytes: &[u8]) -> Result<String> {
+    Err(anyhow!(
+        "Reading PDFs requires the `pdf` feature of smolagents-rs."
+    ))
+}
+
+/// Pretty-prints JSON in a code block. Invalid JSON is returned unchanged.
+pub fn json_to_markdown(text: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => format!(
+            "```json\n{}\n```",
+            serde_json::to_string_pretty(&value).unwrap_or_else(|_| text.to_string())
+        ),
+        Err(_) => text.to_string(),
+    }
+}
+
+/// Renders the first `max_rows` rows of a CSV document as a markdown table. The first row is the header.
+pub fn csv_to_markdown(text: &str, delimiter: u8, max_rows: usize) -> Result<String, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to parse the CSV: {}", e))?
+        .iter()
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    if headers.is_empty() {
+        return Ok("The CSV file is empty.".to_string());
+    }
+
+    let mut rows = Vec::new();
+    let mut total = 0;
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to parse the CSV: {}", e))?;
+        total += 1;
+        if rows.len() < max_rows {
+            rows.push(record.iter().map(str::to_string).collect());
+        }
+    }
+
+    let mut markdown = markdown_table(&headers, &rows);
+    if total > max_rows {
+        markdown.push_str(&format!(
+            "\n\nShowing the first {} of {} rows.",
+            max_rows, total
+        ));
+    }
+    Ok(markdown)
+}
+
+/// Renders a markdown table. Short rows are padded with empty cells, and pipes and newlines in cells are escaped.
+pub fn markdown_table(headers: &[String], rows: &[Vec<String>]) -> String {
+    let cell = |value: &String| value.replace('|', "\\|").replace(['\r', '\n'], " ");
+    let mut table = vec![
+        format!(
+            "| {} |",
+            headers.iter().map(cell).collect::<Vec<_>>().join(" | ")
+        ),
+        format!("|{}", " --- |".repeat(headers.len())),
+    ];
+    for row in rows {
+        let mut cells = row.iter().map(cell).collect::<Vec<_>>();
+        cells.resize(headers.len(), String::new());
+        table.push(format!("| {} |", cells.join(" | ")));
+    }
+    table.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_document_kind() {
+        let url = Url::parse("https://example.com/report").unwrap();
+        let pdf_url = Url::parse("https://example.com/report.PDF").unwrap();
+        assert_eq!(
+            document_kind(Some("text/html; charset=utf-8"), &url),
+            DocumentKind::Html
+        );
+        assert_eq!(
+            document_kind(Some("application/pdf"), &url),
+            DocumentKind::Pdf
+        );
+        assert_eq!(
+            document_kind(Some("application/octet-stream"), &pdf_url),
+            DocumentKind::Pdf
+        );
+        assert_eq!(
+            document_kind(Some("application/ld+json"), &url),
+            DocumentKind::Json
+        );
+        assert_eq!(
+            document_kind(Some("text/csv"), &url),
+            DocumentKind::Csv(b',')
+        );
+        assert_eq!(document_kind(Some("text/plain"), &url), DocumentKind::Text);
+        assert_eq!(
+            document_kind(Some("image/png"), &url),
+            DocumentKind::Binary("image/png".to_string())
+        );
+        assert_eq!(document_kind(None, &url), DocumentKind::Html);
+    }
+
+    #[test]
+    fn test_csv_to_markdown() {
+        let csv = "city,population\nParis,2100000\n\"New York, NY\",8300000\nA|B\n";
+        assert_eq!(
+            csv_to_markdown(csv, b',', 2).unwrap(),
+            "| city | population |\n| --- | --- |\n| Paris | 2100000 |\n| New York, NY | 8300000 |\n\nShowing the first 2 of 3 rows."
+        );
+        assert_eq!(
+            csv_to_markdown("a\tb\n1\t2\n", b'\t', 10).unwrap(),
+            "| a | b |\n| --- | --- |\n| 1 | 2 |"
+        );
+    }
+
+    #[test]
+    fn test_json_to_markdown() {
+        assert_eq!(
+            json_to_markdown(r#"{"a":[1,2]}"#),
+            "```json\n{\n  \"a\": [\n    1,\n    2\n  ]\n}\n```"
+        );
+        assert_eq!(json_to_markdown("not json"), "not json");
+    }
+
+    /// Builds a one page PDF saying "Hello PDF", with the given entries in the page dictionary.
+    #[cfg(feature = "pdf")]
+    fn hello_pdf(page_entries: &str) -> String {
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!("<< /Type /Page /Parent 2 0 R {} /Resources << /Font << /F1 4 0 R >> >> /Contents 5 0 R >>", page_entries),
+            "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_string(),
+            {
+                let stream = "BT /F1 24 Tf 72 720 Td (Hello PDF) Tj ET";
+                format!("<< /Length {} >>\nstream\n{}\nendstream", stream.len(), stream)
+            },
+        ];
+        let mut pdf = "%PDF-1.4\n".to_string();
+        let mut offsets = Vec::new();
+        for (i, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", i + 1, object));
+        }
+        let xref = pdf.len();
+        pdf.push_str(&format!(
+            "xref\n0 {}\n0000000000 65535 f \n",
+            objects.len() + 1
+        ));
+        for offset in offsets {
+            pdf.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        pdf.push_str(&format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            objects.len() + 1,
+            xref
+        ));
+        pdf
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_pdf_to_text() {
+        let pdf = hello_pdf("/MediaBox [0 0 612 792]");
+        assert_eq!(pdf_to_text(pdf.as_bytes()).unwrap(), "Hello PDF");
+        assert!(pdf_to_text(b"not a pdf").is_err());
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_pdf_to_text_does_not_panic_on_malformed_pdfs() {
+        let pdf = hello_pdf("");
+        let error = pdf_to_text(pdf.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("malformed"));
+    }
+}
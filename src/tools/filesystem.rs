@@ -0,0 +1,1035 @@
+//! This module contains the filesystem tools: `read_file`, `write_file`, `list_directory`, `search_files` and
+//! `apply_patch`.
+//!
+//! Every tool is confined to a [`Workspace`] directory. Paths are resolved relative to the workspace root, and paths
+//! that leave it, through `..` or a symlink, are refused. [`Workspace::tools`] returns the tools as a tool group,
+//! which also makes them callable as functions in the code agent's python interpreter.
+//!
+//! ```rust,no_run
+//! use smolagents_rs::tools::{AnyTool, Workspace};
+//!
+//! let workspace = Workspace::new("./my-project").unwrap().with_read_only(true);
+//! let tools: Vec<Box<dyn AnyTool>> = workspace.tools();
+//! ```
+
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Component, Path, PathBuf};
+
+use regex::RegexBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::patch::{apply_hunks, parse_patch};
+use super::tool_traits::{AnyTool, Tool};
+use anyhow::{anyhow, Result};
+
+/// The default size limit of files read, written and searched.
+pub const DEFAULT_MAX_FILE_BYTES: usize = 1024 * 1024;
+/// The default number of entries listed and matches found.
+pub const DEFAULT_MAX_RESULTS: usize = 200;
+/// Directories that are listed but never entered when listing or searching recursively.
+const SKIPPED_DIRECTORIES: [&str; 1] = [".git"];
+
+/// A directory the filesystem tools are confined to.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    root: PathBuf,
+    /// The largest file that is read whole, written or searched, in bytes.
+    pub max_file_bytes: usize,
+    /// The largest number of directory entries listed and of search matches returned.
+    pub max_results: usize,
+    /// Whether writing is refused. Read-only workspaces do not offer `write_file` and `apply_patch`.
+    pub read_only: bool,
+}
+
+impl Workspace {
+    /// Creates a workspace rooted at an existing directory.
+    pub fn new(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref();
+        let canonical = root
+            .canonicalize()
+            .map_err(|e| anyhow!("Invalid workspace root {}: {}", root.display(), e))?;
+        if !canonical.is_dir() {
+            return Err(anyhow!(
+                "Invalid workspace root {}: not a directory",
+                root.display()
+            ));
+        }
+        Ok(Self {
+            root: canonical,
+            max_file_bytes: DEFAULT_MAX_FILE_BYTES,
+            max_results: DEFAULT_MAX_RESULTS,
+            read_only: false,
+        })
+    }
+
+    pub fn with_max_file_bytes(mut self, max_file_bytes: usize) -> Self {
+        self.max_file_bytes = max_file_bytes;
+        self
+    }
+
+    pub fn with_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = max_results;
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    /// The canonical path of the workspace root.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The filesystem tools for this workspace. Read-only workspaces only get the tools that read.
+    pub fn tools(&self) -> Vec<Box<dyn AnyTool>> {
+        let mut tools: Vec<Box<dyn AnyTool>> = vec![
+            Box::new(ReadFileTool::new(self.clone())),
+            Box::new(ListDirectoryTool::new(self.clone())),
+            Box::new(SearchFilesTool::new(self.clone())),
+        ];
+        if !self.read_only {
+            tools.push(Box::new(WriteFileTool::new(self.clone())));
+            tools.push(Box::new(ApplyPatchTool::new(self.clone())));
+        }
+        tools
+    }
+
+    /// Resolves a path given by the model to a path inside the workspace.
+    ///
+    /// Relative paths are relative to the root. Absolute paths must be inside the root. The path does not need to
+    /// exist, but its existing part must not lead outside the workspace through a symlink.
+    pub fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let outside = || anyhow!("The path {} is outside the workspace", path);
+        let requested = Path::new(path.trim());
+        let relative = match requested.is_absolute() {
+            true => requested.strip_prefix(&self.root).map_err(|_| outside())?,
+            false => requested,
+        };
+        let mut resolved = self.root.clone();
+        for component in relative.components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::CurDir => {}
+                Component::ParentDir if resolved != self.root => {
+                    resolved.pop();
+                }
+                _ => return Err(outside()),
+            }
+        }
+
+        // Symlinks in the existing part of the path may point outside the workspace.
+        let mut existing = resolved.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().ok_or_else(outside)?;
+        }
+        let canonical = existing
+            .canonicalize()
+            .map_err(|e| anyhow!("Failed to resolve {}: {}", path, e))?;
+        if !canonical.starts_with(&self.root) {
+            return Err(outside());
+        }
+        match resolved.strip_prefix(existing) {
+            // Joining an empty path would add a trailing separator.
+            Ok(rest) if rest.as_os_str().is_empty() => Ok(canonical),
+            Ok(rest) => Ok(canonical.join(rest)),
+            Err(_) => Err(outside()),
+        }
+    }
+
+    /// The path relative to the root, with `/` separators, as shown to the model.
+    pub fn display_path(&self, path: &Path) -> String {
+        let relative = path.strip_prefix(&self.root).unwrap_or(path);
+        let display = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        match display.is_empty() {
+            true => ".".to_string(),
+            false => display,
+        }
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        match self.read_only {
+            true => Err(anyhow!("The workspace is read-only")),
+            false => Ok(()),
+        }
+    }
+
+    /// Collects the entries of a directory, sorted by path, recursing into subdirectories if asked.
+    ///
+    /// Symlinks that point outside the workspace are left out, since listing or reading them follows the link.
+    /// Symlinked directories are listed but not entered.
+    fn walk(&self, directory: &Path, recursive: bool, entries: &mut Vec<PathBuf>) -> Result<()> {
+        let mut children = fs::read_dir(directory)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .collect::<Vec<_>>();
+        children.sort();
+        for child in children {
+            if child.is_symlink()
+                && !child
+                    .canonicalize()
+                    .is_ok_and(|target| target.starts_with(&self.root))
+            {
+                continue;
+            }
+            let enter = recursive
+                && child.is_dir()
+                && !child.is_symlink()
+                && !child.file_name().is_some_and(|name| {
+                    SKIPPED_DIRECTORIES.contains(&name.to_string_lossy().as_ref())
+                });
+            entries.push(child.clone());
+            if enter {
+                self.walk(&child, recursive, entries)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "ReadFileToolParams")]
+pub struct ReadFileToolParams {
+    #[schemars(description = "The path of the file, relative to the workspace root")]
+    path: String,
+    #[schemars(description = "Optionally the first line to read, starting at 1")]
+    offset: Option<usize>,
+    #[schemars(description = "Optionally the number of lines to read")]
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ReadFileTool {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    workspace: Workspace,
+}
+
+impl ReadFileTool {
+    pub fn new(workspace: Workspace) -> Self {
+        ReadFileTool {
+            tool: BaseTool {
+                name: "read_file",
+                description: "Reads a text file in the workspace. Long files are read in parts: pass `offset` and `limit` to choose the lines to read.",
+            },
+            workspace,
+        }
+    }
+}
+
+impl Tool for ReadFileTool {
+    type Params = ReadFileToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: ReadFileToolParams) -> Result<String> {
+        let path = self.workspace.resolve(&arguments.path)?;
+        if path.is_dir() {
+            return Err(anyhow!(
+                "{} is a directory. Use list_directory to see its content",
+                arguments.path
+            ));
+        }
+        let file =
+            File::open(&path).map_err(|e| anyhow!("Failed to read {}: {}", arguments.path, e))?;
+
+        let start = arguments.offset.unwrap_or(1).max(1);
+        let limit = arguments.limit.unwrap_or(usize::MAX);
+        let max_bytes = self.workspace.max_file_bytes;
+        let mut lines = Vec::new();
+        let mut bytes = 0;
+        let mut total = 0;
+        let mut truncated = false;
+        let mut cut_line = false;
+        for line in BufReader::new(file).lines() {
+            let mut line =
+                line.map_err(|_| anyhow!("{} is not a UTF-8 text file", arguments.path))?;
+            total += 1;
+            if total < start || truncated || lines.len() >= limit {
+                continue;
+            }
+            // Stop at the size limit, but always show at least one line, cut to the limit if needed.
+            if !lines.is_empty() && bytes + line.len() + 1 > max_bytes {
+                truncated = true;
+                continue;
+            }
+            if line.len() > max_bytes {
+                let mut end = max_bytes;
+                while !line.is_char_boundary(end) {
+                    end -= 1;
+                }
+                line.truncate(end);
+                cut_line = true;
+            }
+            bytes += line.len() + 1;
+            lines.push(line);
+        }
+
+        if lines.is_empty() {
+            return Ok(match total {
+                0 => format!("{} is empty.", arguments.path),
+                total => format!(
+                    "Line {} does not exist. {} has {} lines.",
+                    start, arguments.path, total
+                ),
+            });
+        }
+        let end = start + lines.len() - 1;
+        let mut output = lines.join("\n");
+        if start > 1 || end < total {
+            output.push_str(&format!(
+                "\n\n[Showing lines {}-{} of {}.",
+                start, end, total
+            ));
+            if end < total {
+                output.push_str(&format!(
+                    " Call read_file with offset={} to read more.",
+                    end + 1
+                ));
+            }
+            output.push(']');
+        }
+        if cut_line {
+            output.push_str(&format!(
+                "\n\n[Line {} is longer than the limit of {} bytes and was cut.]",
+                start, max_bytes
+            ));
+        }
+        Ok(output)
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "WriteFileToolParams")]
+pub struct WriteFileToolParams {
+    #[schemars(description = "The path of the file, relative to the workspace root")]
+    path: String,
+    #[schemars(description = "The content to write")]
+    content: String,
+    #[schemars(description = "Optionally append to the file instead of replacing it")]
+    append: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WriteFileTool {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    workspace: Workspace,
+}
+
+impl WriteFileTool {
+    pub fn new(workspace: Workspace) -> Self {
+        WriteFileTool {
+            tool: BaseTool {
+                name: "write_file",
+                description: "Writes a text file in the workspace, replacing its content or appending to it. Missing directories are created.",
+            },
+            workspace,
+        }
+    }
+}
+
+impl Tool for WriteFileTool {
+    type Params = WriteFileToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: WriteFileToolParams) -> Result<String> {
+        self.workspace.check_writable()?;
+        let path = self.workspace.resolve(&arguments.path)?;
+        let append = arguments.append.unwrap_or(false);
+        let existing = match append && path.exists() {
+            true => fs::metadata(&path)?.len() as usize,
+            false => 0,
+        };
+        if existing + arguments.content.len() > self.workspace.max_file_bytes {
+            return Err(anyhow!(
+                "The file would be larger than the limit of {} bytes",
+                self.workspace.max_file_bytes
+            ));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let result = match append {
+            true => fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&path)
+                .and_then(|mut file| {
+                    std::io::Write::write_all(&mut file, arguments.content.as_bytes())
+                }),
+            false => fs::write(&path, &arguments.content),
+        };
+        result.map_err(|e| anyhow!("Failed to write {}: {}", arguments.path, e))?;
+        Ok(format!(
+            "Wrote {} bytes to {}.",
+            arguments.content.len(),
+            self.workspace.display_path(&path)
+        ))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "ListDirectoryToolParams")]
+pub struct ListDirectoryToolParams {
+    #[schemars(
+        description = "Optionally the directory to list, relative to the workspace root. Defaults to the root"
+    )]
+    path: Option<String>,
+    #[schemars(description = "Optionally list the subdirectories too")]
+    recursive: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ListDirectoryTool {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    workspace: Workspace,
+}
+
+impl ListDirectoryTool {
+    pub fn new(workspace: Workspace) -> Self {
+        ListDirectoryTool {
+            tool: BaseTool {
+                name: "list_directory",
+                description: "Lists the files and directories in a directory of the workspace, with the size of each file. Directories end with '/'.",
+            },
+            workspace,
+        }
+    }
+}
+
+impl Tool for ListDirectoryTool {
+    type Params = ListDirectoryToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: ListDirectoryToolParams) -> Result<String> {
+        let requested = arguments.path.unwrap_or_else(|| ".".to_string());
+        let directory = self.workspace.resolve(&requested)?;
+        if !directory.is_dir() {
+            return Err(anyhow!("{} is not a directory", requested));
+        }
+        let mut entries = Vec::new();
+        self.workspace.walk(
+            &directory,
+            arguments.recursive.unwrap_or(false),
+            &mut entries,
+        )?;
+        if entries.is_empty() {
+            return Ok(format!("{} is empty.", requested));
+        }
+
+        let total = entries.len();
+        let mut lines = entries
+            .iter()
+            .take(self.workspace.max_results)
+            .map(|entry| {
+                let display = self.workspace.display_path(entry);
+                match fs::metadata(entry) {
+                    Ok(metadata) if metadata.is_dir() => format!("{}/", display),
+                    Ok(metadata) => format!("{} ({} bytes)", display, metadata.len()),
+                    Err(_) => display,
+                }
+            })
+            .collect::<Vec<_>>();
+        if total > self.workspace.max_results {
+            lines.push(format!(
+                "\n[Showing the first {} of {} entries.]",
+                self.workspace.max_results, total
+            ));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "SearchFilesToolParams")]
+pub struct SearchFilesToolParams {
+    #[schemars(description = "The regular expression to search for")]
+    pattern: String,
+    #[schemars(
+        description = "Optionally the file or directory to search, relative to the workspace root. Defaults to the root"
+    )]
+    path: Option<String>,
+    #[schemars(description = "Optionally ignore case")]
+    ignore_case: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SearchFilesTool {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    workspace: Workspace,
+}
+
+impl SearchFilesTool {
+    pub fn new(workspace: Workspace) -> Self {
+        SearchFilesTool {
+            tool: BaseTool {
+                name: "search_files",
+                description: "Searches the text files of the workspace for a regular expression, like grep. Returns each matching line as 'path:line: text'.",
+            },
+            workspace,
+        }
+    }
+}
+
+impl Tool for SearchFilesTool {
+    type Params = SearchFilesToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: SearchFilesToolParams) -> Result<String> {
+        let regex = RegexBuilder::new(&arguments.pattern)
+            .case_insensitive(arguments.ignore_case.unwrap_or(false))
+            .build()
+            .map_err(|e| anyhow!("Invalid regular expression {}: {}", arguments.pattern, e))?;
+        let requested = arguments.path.unwrap_or_else(|| ".".to_string());
+        let path = self.workspace.resolve(&requested)?;
+        let files = match path.is_dir() {
+            true => {
+                let mut entries = Vec::new();
+                self.workspace.walk(&path, true, &mut entries)?;
+                entries
+            }
+            false => vec![path],
+        };
+
+        let mut matches = Vec::new();
+        let mut truncated = false;
+        'files: for file in files.iter().filter(|file| file.is_file()) {
+            // Large and binary files are skipped.
+            let too_large = fs::metadata(file).map_or(true, |metadata| {
+                metadata.len() as usize > self.workspace.max_file_bytes
+            });
+            if too_large {
+                continue;
+            }
+            let Ok(text) = fs::read_to_string(file) else {
+                continue;
+            };
+            for (number, line) in text.lines().enumerate() {
+                if !regex.is_match(line) {
+                    continue;
+                }
+                if matches.len() >= self.workspace.max_results {
+                    truncated = true;
+                    break 'files;
+                }
+                matches.push(format!(
+                    "{}:{}: {}",
+                    self.workspace.display_path(file),
+                    number + 1,
+                    line.trim()
+                ));
+            }
+        }
+        if matches.is_empty() {
+            return Ok(format!("No matches found for '{}'.", arguments.pattern));
+        }
+        if truncated {
+            matches.push(format!(
+                "\n[Showing the first {} matches.]",
+                self.workspace.max_results
+            ));
+        }
+        Ok(matches.join("\n"))
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "ApplyPatchToolParams")]
+pub struct ApplyPatchToolParams {
+    #[schemars(
+        description = "The patch in the unified diff format, with '---' and '+++' file headers and '@@' hunks"
+    )]
+    patch: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ApplyPatchTool {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    workspace: Workspace,
+}
+
+impl ApplyPatchTool {
+    pub fn new(workspace: Workspace) -> Self {
+        ApplyPatchTool {
+            tool: BaseTool {
+                name: "apply_patch",
+                description: "Applies a patch in the unified diff format to files in the workspace. Files can be changed, created (from /dev/null) and deleted (to /dev/null). Either all files are changed or none.",
+            },
+            workspace,
+        }
+    }
+}
+
+impl Tool for ApplyPatchTool {
+    type Params = ApplyPatchToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: ApplyPatchToolParams) -> Result<String> {
+        self.workspace.check_writable()?;
+        let files = parse_patch(&arguments.patch)?;
+
+        // Apply every file in memory first, so a failing hunk leaves the workspace untouched.
+        let mut changes = Vec::new();
+        for file in &files {
+            let original = match &file.old_path {
+                Some(old_path) => {
+                    let path = self.workspace.resolve(old_path)?;
+                    fs::read_to_string(&path)
+                        .map_err(|e| anyhow!("Failed to read {}: {}", old_path, e))?
+                }
+                None => String::new(),
+            };
+            let change = match &file.new_path {
+                Some(new_path) => {
+                    let text = apply_hunks(&original, &file.hunks)
+                        .map_err(|e| anyhow!("Failed to patch {}: {}", new_path, e))?;
+                    if text.len() > self.workspace.max_file_bytes {
+                        return Err(anyhow!(
+                            "{} would be larger than the limit of {} bytes",
+                            new_path,
+                            self.workspace.max_file_bytes
+                        ));
+                    }
+                    let path = self.workspace.resolve(new_path)?;
+                    if file.old_path.is_none() && fs::symlink_metadata(&path).is_ok() {
+                        return Err(anyhow!(
+                            "Cannot create {} since it already exists",
+                            new_path
+                        ));
+                    }
+                    (path, Some(text))
+                }
+                None => {
+                    let old_path = file.old_path.as_deref().unwrap_or_default();
+                    (self.workspace.resolve(old_path)?, None)
+                }
+            };
+            changes.push((file, change));
+        }
+
+        // Write the new contents next to their files before touching any file, then move them in place.
+        let mut staged = Vec::new();
+        for (_, (path, text)) in &changes {
+            let Some(text) = text else {
+                continue;
+            };
+            let staging = staging_path(path);
+            let result = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|_| fs::write(&staging, text));
+            if let Err(e) = result {
+                for staging in staged {
+                    let _ = fs::remove_file(staging);
+                }
+                return Err(anyhow!(
+                    "Failed to write {}: {}",
+                    self.workspace.display_path(path),
+                    e
+                ));
+            }
+            staged.push(staging);
+        }
+
+        let mut summary = Vec::new();
+        for (file, (path, text)) in changes {
+            let display = self.workspace.display_path(&path);
+            match text {
+                Some(_) => {
+                    fs::rename(staging_path(&path), &path)?;
+                    summary.push(match &file.old_path {
+                        None => format!("created {}", display),
+                        Some(old_path)
+                            if old_path != file.new_path.as_deref().unwrap_or_default() =>
+                        {
+                            fs::remove_file(self.workspace.resolve(old_path)?)?;
+                            format!("moved {} to {}", old_path, display)
+                        }
+                        Some(_) => format!("updated {}", display),
+                    });
+                }
+                None => {
+                    fs::remove_file(&path)?;
+                    summary.push(format!("deleted {}", display));
+                }
+            }
+        }
+        Ok(format!("Applied the patch: {}.", summary.join(", ")))
+    }
+}
+
+/// The temporary file a patched file is written to before it replaces the file.
+fn staging_path(path: &Path) -> PathBuf {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!(".{}.patch", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> (tempfile::TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("src")).unwrap();
+        fs::write(
+            dir.path().join("src/main.rs"),
+            "fn main() {\n    println!(\"Hello\");\n}\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("notes.txt"), "one\ntwo\nthree\n").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, workspace)
+    }
+
+    #[test]
+    fn test_workspace_resolve() {
+        let (dir, workspace) = workspace();
+        let root = workspace.root().to_path_buf();
+        assert_eq!(
+            workspace.resolve("src/../notes.txt").unwrap(),
+            root.join("notes.txt")
+        );
+        assert_eq!(
+            workspace.resolve("new/file.txt").unwrap(),
+            root.join("new/file.txt")
+        );
+        assert_eq!(
+            workspace
+                .resolve(root.join("src").to_str().unwrap())
+                .unwrap(),
+            root.join("src")
+        );
+        for path in ["../outside.txt", "src/../../outside.txt", "/etc/passwd"] {
+            let error = workspace.resolve(path).unwrap_err();
+            assert!(
+                error.to_string().contains("is outside the workspace"),
+                "{}",
+                path
+            );
+        }
+
+        #[cfg(unix)]
+        {
+            let outside = tempfile::tempdir().unwrap();
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+            assert!(workspace.resolve("link/secret.txt").is_err());
+        }
+        assert!(Workspace::new(dir.path().join("notes.txt")).is_err());
+    }
+
+    #[test]
+    fn test_read_and_write_file() {
+        let (_dir, workspace) = workspace();
+        let read = ReadFileTool::new(workspace.clone());
+        let write = WriteFileTool::new(workspace.clone());
+        let read_params =
+            |path: &str, offset: Option<usize>, limit: Option<usize>| ReadFileToolParams {
+                path: path.to_string(),
+                offset,
+                limit,
+            };
+
+        assert_eq!(
+            read.forward(read_params("notes.txt", None, None)).unwrap(),
+            "one\ntwo\nthree"
+        );
+        assert_eq!(
+            read.forward(read_params("notes.txt", Some(2), Some(1)))
+                .unwrap(),
+            "two\n\n[Showing lines 2-2 of 3. Call read_file with offset=3 to read more.]"
+        );
+        assert!(read.forward(read_params("src", None, None)).is_err());
+
+        let output = write
+            .forward(WriteFileToolParams {
+                path: "docs/guide.md".to_string(),
+                content: "# Guide\n".to_string(),
+                append: None,
+            })
+            .unwrap();
+        assert_eq!(output, "Wrote 8 bytes to docs/guide.md.");
+        write
+            .forward(WriteFileToolParams {
+                path: "docs/guide.md".to_string(),
+                content: "More.\n".to_string(),
+                append: Some(true),
+            })
+            .unwrap();
+        assert_eq!(
+            fs::read_to_string(workspace.root().join("docs/guide.md")).unwrap(),
+            "# Guide\nMore.\n"
+        );
+
+        let small = WriteFileTool::new(workspace.clone().with_max_file_bytes(4));
+        assert!(small
+            .forward(WriteFileToolParams {
+                path: "big.txt".to_string(),
+                content: "too big".to_string(),
+                append: None,
+            })
+            .is_err());
+        let small = ReadFileTool::new(workspace.clone().with_max_file_bytes(8));
+        assert_eq!(
+            small.forward(read_params("notes.txt", None, None)).unwrap(),
+            "one\ntwo\n\n[Showing lines 1-2 of 3. Call read_file with offset=3 to read more.]"
+        );
+        fs::write(workspace.root().join("long.txt"), "é".repeat(10)).unwrap();
+        assert_eq!(
+            small.forward(read_params("long.txt", None, None)).unwrap(),
+            "éééé\n\n[Line 1 is longer than the limit of 8 bytes and was cut.]"
+        );
+    }
+
+    #[test]
+    fn test_read_only_workspace() {
+        let (_dir, workspace) = workspace();
+        let workspace = workspace.with_read_only(true);
+        let names = workspace
+            .tools()
+            .iter()
+            .map(|tool| tool.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["read_file", "list_directory", "search_files"]);
+        let error = WriteFileTool::new(workspace)
+            .forward(WriteFileToolParams {
+                path: "notes.txt".to_string(),
+                content: String::new(),
+                append: None,
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "The workspace is read-only");
+    }
+
+    #[test]
+    fn test_list_directory_and_search_files() {
+        let (dir, workspace) = workspace();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(dir.path().join(".git/config"), "println").unwrap();
+        let list = ListDirectoryTool::new(workspace.clone());
+        let output = list
+            .forward(ListDirectoryToolParams {
+                path: None,
+                recursive: Some(true),
+            })
+            .unwrap();
+        assert_eq!(
+            output,
+            ".git/\nnotes.txt (14 bytes)\nsrc/\nsrc/main.rs (37 bytes)"
+        );
+
+        let search = SearchFilesTool::new(workspace.clone());
+        let output = search
+            .forward(SearchFilesToolParams {
+                pattern: "PRINTLN|^two".to_string(),
+                path: None,
+                ignore_case: Some(true),
+            })
+            .unwrap();
+        assert_eq!(
+            output,
+            "notes.txt:2: two\nsrc/main.rs:2: println!(\"Hello\");"
+        );
+        let output = SearchFilesTool::new(workspace.with_max_results(1))
+            .forward(SearchFilesToolParams {
+                pattern: "o".to_string(),
+                path: Some("notes.txt".to_string()),
+                ignore_case: None,
+            })
+            .unwrap();
+        assert_eq!(output, "notes.txt:1: one\n\n[Showing the first 1 matches.]");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlinked_files_outside_the_workspace_are_skipped() {
+        let (dir, workspace) = workspace();
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("shadow"), "secret").unwrap();
+        std::os::unix::fs::symlink(outside.path().join("shadow"), dir.path().join("x")).unwrap();
+        std::os::unix::fs::symlink(dir.path().join("notes.txt"), dir.path().join("alias.txt"))
+            .unwrap();
+
+        let output = ListDirectoryTool::new(workspace.clone())
+            .forward(ListDirectoryToolParams {
+                path: None,
+                recursive: None,
+            })
+            .unwrap();
+        assert_eq!(output, "alias.txt (14 bytes)\nnotes.txt (14 bytes)\nsrc/");
+
+        let search = |pattern: &str| {
+            SearchFilesTool::new(workspace.clone())
+                .forward(SearchFilesToolParams {
+                    pattern: pattern.to_string(),
+                    path: None,
+                    ignore_case: None,
+                })
+                .unwrap()
+        };
+        assert_eq!(search("secret"), "No matches found for 'secret'.");
+        assert_eq!(search("three"), "alias.txt:3: three\nnotes.txt:3: three");
+        assert!(ReadFileTool::new(workspace)
+            .forward(ReadFileToolParams {
+                path: "x".to_string(),
+                offset: None,
+                limit: None,
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let (_dir, workspace) = workspace();
+        let tool = ApplyPatchTool::new(workspace.clone());
+        let patch = "--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"Hello\");
++    println!(\"Hello, world!\");
+ }
+--- a/notes.txt
++++ /dev/null
+@@ -1,3 +0,0 @@
+-one
+-two
+-three
+--- /dev/null
++++ b/docs/new.md
+@@ -0,0 +1 @@
++New file
+";
+        let output = tool
+            .forward(ApplyPatchToolParams {
+                patch: patch.to_string(),
+            })
+            .unwrap();
+        assert_eq!(
+            output,
+            "Applied the patch: updated src/main.rs, deleted notes.txt, created docs/new.md."
+        );
+        let root = workspace.root();
+        assert!(fs::read_to_string(root.join("src/main.rs"))
+            .unwrap()
+            .contains("Hello, world!"));
+        assert!(!root.join("notes.txt").exists());
+        assert_eq!(
+            fs::read_to_string(root.join("docs/new.md")).unwrap(),
+            "New file\n"
+        );
+
+        // A hunk that does not match leaves every file untouched.
+        let patch = "--- /dev/null
++++ b/other.md
+@@ -0,0 +1 @@
++Other
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -1 +1 @@
+-fn missing() {
++fn found() {
+";
+        let error = tool
+            .forward(ApplyPatchToolParams {
+                patch: patch.to_string(),
+            })
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Failed to patch src/main.rs"));
+        assert!(!root.join("other.md").exists());
+
+        // So does a file that cannot be written, or creating a file that exists.
+        let patch = "--- a/src/main.rs
++++ b/src/main.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"Hello, world!\");
++    println!(\"Bye\");
+ }
+--- /dev/null
++++ b/docs/new.md/child.md
+@@ -0,0 +1 @@
++Child
+";
+        let error = tool
+            .forward(ApplyPatchToolParams {
+                patch: patch.to_string(),
+            })
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Failed to write docs/new.md/child.md"));
+        assert!(!fs::read_to_string(root.join("src/main.rs"))
+            .unwrap()
+            .contains("Bye"));
+        assert!(!root.join("src/.main.rs.patch").exists());
+
+        let patch = "--- /dev/null
++++ b/docs/new.md
+@@ -0,0 +1 @@
++Replaced
+";
+        let error = tool
+            .forward(ApplyPatchToolParams {
+                patch: patch.to_string(),
+            })
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Cannot create docs/new.md since it already exists"
+        );
+        assert_eq!(
+            fs::read_to_string(root.join("docs/new.md")).unwrap(),
+            "New file\n"
+        );
+    }
+
+    #[cfg(feature = "code-agent")]
+    #[test]
+    fn test_filesystem_tools_in_python_interpreter() {
+        use crate::local_python_interpreter::LocalPythonInterpreter;
+
+        let (_dir, workspace) = workspace();
+        let mut interpreter = LocalPythonInterpreter::new(workspace.tools());
+        let (result, _) = interpreter
+            .forward("write_file(path='hello.txt', content='Hi')\nprint(read_file('hello.txt'))")
+            .unwrap();
+        assert_eq!(result, "Hi");
+        let (result, _) = interpreter
+            .forward("print(read_file('../secret.txt'))")
+            .unwrap();
+        assert!(result.contains("is outside the workspace"));
+    }
+}
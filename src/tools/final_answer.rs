@@ -1,11 +1,18 @@
 //! This module contains the final answer tool. The model uses this tool to provide a final answer to the problem.
 
+use std::fmt::Debug;
+use std::marker::PhantomData;
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Schema, SchemaObject};
 use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::base::BaseTool;
 use super::tool_traits::Tool;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 #[derive(Debug, Deserialize, JsonSchema)]
 #[schemars(title = "FinalAnswerToolParams")]
@@ -44,9 +51,119 @@ impl Tool for FinalAnswerTool {
     }
 }
 
+/// The parameters of a [`StructuredFinalAnswerTool`]. The schema of `answer` is generated from `T`.
+#[derive(Debug, Deserialize)]
+#[serde(bound = "")]
+pub struct StructuredFinalAnswerToolParams<T> {
+    answer: Value,
+    #[serde(skip)]
+    _answer_type: PhantomData<fn() -> T>,
+}
+
+impl<T: JsonSchema> JsonSchema for StructuredFinalAnswerToolParams<T> {
+    fn schema_name() -> String {
+        format!("StructuredFinalAnswerToolParams_for_{}", T::schema_name())
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut answer = gen.subschema_for::<T>();
+        if let Schema::Object(answer) = &mut answer {
+            answer.metadata().description = Some("The final answer to the problem".to_string());
+        }
+        let mut schema = SchemaObject {
+            instance_type: Some(InstanceType::Object.into()),
+            ..Default::default()
+        };
+        schema.metadata().title = Some("FinalAnswerToolParams".to_string());
+        let object = schema.object();
+        object.properties.insert("answer".to_string(), answer);
+        object.required.insert("answer".to_string());
+        schema.into()
+    }
+}
+
+/// A final answer tool whose answer must deserialize into `T`.
+///
+/// The answer is returned as JSON text. Answers that do not match `T` fail with an error
+/// that the agent feeds back to the model so it can retry.
+pub struct StructuredFinalAnswerTool<T> {
+    pub tool: BaseTool,
+    _answer_type: PhantomData<fn() -> T>,
+}
+
+impl<T> StructuredFinalAnswerTool<T> {
+    pub fn new() -> Self {
+        StructuredFinalAnswerTool {
+            tool: BaseTool {
+                name: "final_answer",
+                description: "Provides a final answer to the given problem. The answer must match the given schema.",
+            },
+            _answer_type: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for StructuredFinalAnswerTool<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for StructuredFinalAnswerTool<T> {
+    fn clone(&self) -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Debug for StructuredFinalAnswerTool<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("StructuredFinalAnswerTool")
+            .field("tool", &self.tool)
+            .field("answer_type", &std::any::type_name::<T>())
+            .finish()
+    }
+}
+
+impl<T: JsonSchema + DeserializeOwned> Tool for StructuredFinalAnswerTool<T> {
+    type Params = StructuredFinalAnswerToolParams<T>;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+
+    fn forward(&self, arguments: StructuredFinalAnswerToolParams<T>) -> Result<String> {
+        parse_structured_answer::<T>(&arguments.answer.to_string()).map_err(|e| {
+            anyhow!(
+                "The final answer does not match the expected schema: {}. Call final_answer again with an answer matching the schema.",
+                e
+            )
+        })?;
+        Ok(arguments.answer.to_string())
+    }
+}
+
+/// Parses a final answer into `T`. The answer is read as JSON, falling back to a plain string.
+pub fn parse_structured_answer<T: DeserializeOwned>(answer: &str) -> Result<T> {
+    match serde_json::from_str::<T>(answer) {
+        Ok(value) => Ok(value),
+        Err(e) => serde_json::from_value::<T>(Value::String(answer.to_string()))
+            .map_err(|_| anyhow!("{}", e)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::AnyTool;
+    use serde_json::json;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    struct City {
+        name: String,
+        population: u64,
+    }
 
     #[test]
     fn test_final_answer_tool() {
@@ -57,4 +174,30 @@ mod tests {
         let result = tool.forward(arguments).unwrap();
         assert_eq!(result, "The answer is 42");
     }
+
+    #[test]
+    fn test_structured_final_answer_tool() {
+        let tool = StructuredFinalAnswerTool::<City>::new();
+        let properties = json!(tool.tool_info().function.parameters.schema)["properties"].clone();
+        assert_eq!(
+            properties["answer"]["properties"]["population"]["type"],
+            "integer"
+        );
+
+        let answer = tool
+            .forward_json(json!({"answer": {"name": "Paris", "population": 2100000}}))
+            .unwrap();
+        assert_eq!(
+            parse_structured_answer::<City>(&answer).unwrap(),
+            City {
+                name: "Paris".to_string(),
+                population: 2100000,
+            }
+        );
+        assert!(tool
+            .forward_json(json!({"answer": {"name": "Paris"}}))
+            .unwrap_err()
+            .to_string()
+            .contains("`answer.population` is required"));
+    }
 }
@@ -1,150 +1,199 @@
-//! This module contains the Google search tool.
+//! This module contains the Google search tool. Searches are made through SerpAPI.
 
-use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+use serde_json::{json, Value};
 
-use super::base::BaseTool;
-use super::tool_traits::Tool;
-use anyhow::Result;
+use super::web_client::WebClient;
+use super::web_search::{
+    fetch, SafeSearch, SearchProvider, SearchQuery, SearchResult, WebSearchTool,
+};
+use anyhow::{anyhow, Result};
 
-#[derive(Deserialize, JsonSchema)]
-#[schemars(title = "GoogleSearchToolParams")]
-pub struct GoogleSearchToolParams {
-    #[schemars(description = "The query to search for")]
-    query: String,
-    #[schemars(description = "Optionally restrict results to a certain year")]
-    filter_year: Option<String>,
-}
-
-#[derive(Debug, Serialize, Default, Clone)]
-pub struct GoogleSearchTool {
-    pub tool: BaseTool,
+/// Searches Google through SerpAPI.
+#[derive(Debug, Clone)]
+pub struct SerpApiProvider {
     pub api_key: String,
+    pub base_url: String,
 }
 
-impl GoogleSearchTool {
+impl SerpApiProvider {
+    /// Creates the provider. The API key is read from `SERPAPI_API_KEY` if not given.
     pub fn new(api_key: Option<String>) -> Self {
-        let api_key = api_key.unwrap_or(std::env::var("SERPAPI_API_KEY").unwrap());
-
-        GoogleSearchTool {
-            tool: BaseTool {
-                name: "google_search",
-                description: "Performs a google web search for your query then returns a string of the top search results.",
-            },
+        let api_key =
+            api_key.unwrap_or_else(|| std::env::var("SERPAPI_API_KEY").unwrap_or_default());
+        Self {
             api_key,
-        }
-    }
-
-    fn forward(&self, query: &str, filter_year: Option<&str>) -> String {
-        let params = {
-            let mut params = json!({
-                "engine": "google",
-                "q": query,
-                "api_key": self.api_key,
-                "google_domain": "google.com",
-            });
-
-            if let Some(year) = filter_year {
-                params["tbs"] = json!(format!("cdr:1,cd_min:01/01/{},cd_max:12/31/{}", year, year));
-            }
-
-            params
-        };
-
-        let client = reqwest::blocking::Client::new();
-        let response = client
-            .get("https://serpapi.com/search.json")
-            .query(&params)
-            .send();
-        match response {
-            Ok(resp) => {
-                if resp.status().is_success() {
-                    let results: serde_json::Value = resp.json().unwrap();
-                    if results.get("organic_results").is_none() {
-                        if filter_year.is_some() {
-                            return format!("'organic_results' key not found for query: '{}' with filtering on year={}. Use a less restrictive query or do not filter on year.", query, filter_year.unwrap());
-                        } else {
-                            return format!("'organic_results' key not found for query: '{}'. Use a less restrictive query.", query);
-                        }
-                    }
-
-                    let organic_results =
-                        results.get("organic_results").unwrap().as_array().unwrap();
-                    if organic_results.is_empty() {
-                        let _ = if filter_year.is_some() {
-                            format!(" with filter year={}", filter_year.unwrap())
-                        } else {
-                            "".to_string()
-                        };
-                        return format!("No results found for '{}'. Try with a more general query, or remove the year filter.", query);
-                    }
-
-                    let mut web_snippets = Vec::new();
-                    for (idx, page) in organic_results.iter().enumerate() {
-                        let date_published = page.get("date").map_or("".to_string(), |d| {
-                            format!("\nDate published: {}", d.as_str().unwrap_or(""))
-                        });
-                        let source = page.get("source").map_or("".to_string(), |s| {
-                            format!("\nSource: {}", s.as_str().unwrap_or(""))
-                        });
-                        let snippet = page.get("snippet").map_or("".to_string(), |s| {
-                            format!("\n{}", s.as_str().unwrap_or(""))
-                        });
-
-                        let redacted_version = format!(
-                            "{}. [{}]({}){}{}\n{}",
-                            idx,
-                            page.get("title").unwrap().as_str().unwrap(),
-                            page.get("link").unwrap().as_str().unwrap(),
-                            date_published,
-                            source,
-                            snippet
-                        );
-                        let redacted_version =
-                            redacted_version.replace("Your browser can't play this video.", "");
-                        web_snippets.push(redacted_version);
-                    }
-
-                    format!("## Search Results\n{}", web_snippets.join("\n\n"))
-                } else {
-                    format!(
-                        "Failed to fetch search results: HTTP {}, Error: {}",
-                        resp.status(),
-                        resp.text().unwrap()
-                    )
-                }
-            }
-            Err(e) => format!("Failed to make the request: {}", e),
+            base_url: "https://serpapi.com/search.json".to_string(),
         }
     }
 }
 
-impl Tool for GoogleSearchTool {
-    type Params = GoogleSearchToolParams;
+impl SearchProvider for SerpApiProvider {
     fn name(&self) -> &'static str {
-        self.tool.name
+        "google_search"
     }
     fn description(&self) -> &'static str {
-        self.tool.description
+        "Performs a google web search for your query then returns a string of the top search results."
+    }
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>> {
+        let request = client
+            .get(&self.base_url)
+            .query(&serpapi_params(query, &self.api_key));
+        parse_serpapi_results(&fetch(client, request)?)
     }
+}
 
-    fn forward(&self, arguments: GoogleSearchToolParams) -> Result<String> {
-        let query = arguments.query;
-        let filter_year = arguments.filter_year;
-        Ok(self.forward(&query, filter_year.as_deref()))
+/// Maps the query to SerpAPI's Google parameters. The year filter takes precedence over the time range.
+pub fn serpapi_params(query: &SearchQuery, api_key: &str) -> Value {
+    let mut params = json!({
+        "engine": "google",
+        "q": query.query_text(),
+        "api_key": api_key,
+        "google_domain": "google.com",
+    });
+    if let Some(year) = &query.filter_year {
+        params["tbs"] = json!(format!("cdr:1,cd_min:01/01/{},cd_max:12/31/{}", year, year));
+    } else if let Some(time_range) = query.time_range {
+        params["tbs"] = json!(format!("qdr:{}", time_range.letter()));
+    }
+    if let Some(count) = query.count {
+        params["num"] = json!(count);
+    }
+    let offset = query.offset(query.count.unwrap_or(10));
+    if offset > 0 {
+        params["start"] = json!(offset);
+    }
+    if let Some(region) = &query.region {
+        params["gl"] = json!(region.to_lowercase());
+    }
+    if let Some(language) = &query.language {
+        params["hl"] = json!(language.to_lowercase());
+    }
+    if let Some(safe_search) = query.safe_search {
+        params["safe"] = json!(match safe_search {
+            SafeSearch::Off => "off",
+            SafeSearch::Moderate | SafeSearch::Strict => "active",
+        });
+    }
+    params
+}
+
+/// Parses the organic results of a SerpAPI Google search response.
+pub fn parse_serpapi_results(body: &str) -> Result<Vec<SearchResult>> {
+    let results: Value = serde_json::from_str(body)?;
+    if let Some(error) = results.get("error").and_then(|e| e.as_str()) {
+        return Err(anyhow!("SerpAPI returned an error: {}", error));
+    }
+    let Some(organic_results) = results.get("organic_results").and_then(|r| r.as_array()) else {
+        return Ok(Vec::new());
+    };
+    Ok(organic_results
+        .iter()
+        .filter_map(|page| {
+            let text = |key: &str| page.get(key).and_then(|v| v.as_str()).map(str::to_string);
+            Some(SearchResult {
+                title: text("title")?,
+                url: text("link")?,
+                snippet: text("snippet")
+                    .unwrap_or_default()
+                    .replace("Your browser can't play this video.", ""),
+                date: text("date"),
+                source: text("source"),
+            })
+        })
+        .collect())
+}
+
+pub type GoogleSearchTool = WebSearchTool<SerpApiProvider>;
+
+impl WebSearchTool<SerpApiProvider> {
+    pub fn new(api_key: Option<String>) -> Self {
+        Self::with_provider(SerpApiProvider::new(api_key))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tools::{TimeRange, Tool};
+
+    #[test]
+    fn test_parse_serpapi_results() {
+        let body = r#"{
+            "search_metadata": {"status": "Success"},
+            "organic_results": [
+                {
+                    "position": 1,
+                    "title": "Paris - Wikipedia",
+                    "link": "https://en.wikipedia.org/wiki/Paris",
+                    "snippet": "Paris is the capital of France.",
+                    "source": "Wikipedia",
+                    "date": "Jan 2, 2024"
+                },
+                {"position": 2, "snippet": "A result without a title or link."}
+            ]
+        }"#;
+        let results = parse_serpapi_results(body).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Paris - Wikipedia".to_string(),
+                snippet: "Paris is the capital of France.".to_string(),
+                url: "https://en.wikipedia.org/wiki/Paris".to_string(),
+                date: Some("Jan 2, 2024".to_string()),
+                source: Some("Wikipedia".to_string()),
+            }]
+        );
+        assert!(parse_serpapi_results(r#"{"search_metadata": {}}"#)
+            .unwrap()
+            .is_empty());
+        assert!(parse_serpapi_results(r#"{"error": "Invalid API key."}"#).is_err());
+    }
+
+    #[test]
+    fn test_given_api_key_does_not_need_the_env_var() {
+        let provider = SerpApiProvider::new(Some("key".to_string()));
+        assert_eq!(provider.api_key, "key");
+    }
+
+    #[test]
+    fn test_serpapi_params() {
+        let query = SearchQuery::new("rust")
+            .with_site("rust-lang.org")
+            .with_count(20)
+            .with_page(3)
+            .with_region("US")
+            .with_language("en")
+            .with_safe_search(SafeSearch::Strict)
+            .with_time_range(TimeRange::Week);
+        assert_eq!(
+            serpapi_params(&query, "key"),
+            json!({
+                "engine": "google",
+                "q": "site:rust-lang.org rust",
+                "api_key": "key",
+                "google_domain": "google.com",
+                "tbs": "qdr:w",
+                "num": 20,
+                "start": 40,
+                "gl": "us",
+                "hl": "en",
+                "safe": "active"
+            })
+        );
+        let query = SearchQuery::new("rust")
+            .with_filter_year("2020")
+            .with_time_range(TimeRange::Day);
+        assert_eq!(
+            serpapi_params(&query, "key")["tbs"],
+            "cdr:1,cd_min:01/01/2020,cd_max:12/31/2020"
+        );
+    }
 
     #[test]
     fn test_google_search_tool() {
         let tool = GoogleSearchTool::new(None);
         let query = "What is the capital of France?";
-        let result = tool.forward(query, None);
+        let result = Tool::forward(&tool, SearchQuery::new(query)).unwrap();
         assert!(result.contains("Paris"));
     }
 }
@@ -0,0 +1,478 @@
+//! This module contains the HTTP request tool. The model uses this tool to call JSON and REST APIs.
+//!
+//! The tool can be limited to a list of hosts and to read-only methods. API keys and other credentials are
+//! configured as secret headers: they are added to the https requests sent to their host and redacted from the
+//! responses, so they never appear in the prompt. Redirects are not followed, so secret headers are never sent to
+//! another host. Secret headers are only sent over plain http to the hosts allowed with
+//! [`HttpRequestTool::with_http_secret_hosts`], e.g. a local server.
+//!
+//! ```rust,no_run
+//! use smolagents_rs::tools::HttpRequestTool;
+//!
+//! let api_key = std::env::var("GITHUB_TOKEN").unwrap();
+//! let tool = HttpRequestTool::new()
+//!     .with_allowed_hosts(&["api.github.com"])
+//!     .with_secret_header("api.github.com", "Authorization", &format!("Bearer {}", api_key));
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+
+use reqwest::Method;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::tool_traits::Tool;
+use super::web_client::{domain_matches, WebClient, WebClientConfig, WebResponse};
+use anyhow::{anyhow, Result};
+
+/// The default number of characters of the response body shown to the model.
+pub const DEFAULT_MAX_BODY_CHARS: usize = 10_000;
+/// The response headers shown to the model by default.
+pub const DEFAULT_RESPONSE_HEADERS: [&str; 6] = [
+    "content-type",
+    "location",
+    "retry-after",
+    "link",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+];
+/// The methods allowed in read-only mode.
+const READ_ONLY_METHODS: [&str; 3] = ["GET", "HEAD", "OPTIONS"];
+
+/// A header added to the requests sent to a host, whose value is never shown to the model.
+#[derive(Clone)]
+pub struct SecretHeader {
+    /// The host the header is sent to, including its subdomains.
+    pub host: String,
+    pub name: String,
+    value: String,
+}
+
+impl fmt::Debug for SecretHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SecretHeader")
+            .field("host", &self.host)
+            .field("name", &self.name)
+            .field("value", &"[REDACTED]")
+            .finish()
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct HttpRequestTool {
+    pub tool: BaseTool,
+    /// The hosts, and their subdomains, that requests can be sent to. Any host is allowed if empty.
+    pub allowed_hosts: Vec<String>,
+    /// Whether only `GET`, `HEAD` and `OPTIONS` requests are allowed.
+    pub read_only: bool,
+    /// The response headers shown to the model, in lowercase.
+    pub response_headers: Vec<String>,
+    /// The largest number of characters of the response body shown to the model.
+    pub max_body_chars: usize,
+    /// The hosts, and their subdomains, that secret headers are also sent to over plain http.
+    pub http_secret_hosts: Vec<String>,
+    #[serde(skip)]
+    secret_headers: Vec<SecretHeader>,
+    #[serde(skip)]
+    client: WebClient,
+}
+
+impl Default for HttpRequestTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpRequestTool {
+    pub fn new() -> Self {
+        HttpRequestTool {
+            tool: BaseTool {
+                name: "http_request",
+                description: "Sends an HTTP request to an API and returns the status, the main response headers and the body, pretty-printed if it is JSON. Credentials are added automatically for the hosts that need them: never put API keys in the request.",
+            },
+            allowed_hosts: Vec::new(),
+            read_only: false,
+            response_headers: DEFAULT_RESPONSE_HEADERS
+                .iter()
+                .map(|header| header.to_string())
+                .collect(),
+            max_body_chars: DEFAULT_MAX_BODY_CHARS,
+            http_secret_hosts: Vec::new(),
+            secret_headers: Vec::new(),
+            client: Self::no_redirects(WebClientConfig::default())
+                .expect("the default web client config is valid"),
+        }
+    }
+
+    fn no_redirects(config: WebClientConfig) -> Result<WebClient> {
+        WebClient::new(config.with_max_redirects(0))
+    }
+
+    /// Sends requests with the settings of this client, except that redirects are never followed.
+    pub fn with_web_client(mut self, client: &WebClient) -> Result<Self> {
+        self.client = Self::no_redirects(client.config().clone())?;
+        Ok(self)
+    }
+
+    pub fn with_allowed_hosts(mut self, hosts: &[&str]) -> Self {
+        self.allowed_hosts = hosts.iter().map(|host| host.to_string()).collect();
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn with_response_headers(mut self, headers: &[&str]) -> Self {
+        self.response_headers = headers.iter().map(|header| header.to_lowercase()).collect();
+        self
+    }
+
+    pub fn with_max_body_chars(mut self, max_body_chars: usize) -> Self {
+        self.max_body_chars = max_body_chars;
+        self
+    }
+
+    /// Adds the header to every https request sent to the host or its subdomains. The header replaces any header of
+    /// the same name set by the model, and its value is redacted from the responses.
+    ///
+    /// Requests to the host over plain http fail, unless the host is allowed with [`Self::with_http_secret_hosts`].
+    pub fn with_secret_header(mut self, host: &str, name: &str, value: &str) -> Self {
+        self.secret_headers.push(SecretHeader {
+            host: host.to_string(),
+            name: name.to_string(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Also sends the secret headers over plain http to these hosts and their subdomains, e.g. `localhost`. Anyone on
+    /// the network between the tool and the host can read them.
+    pub fn with_http_secret_hosts(mut self, hosts: &[&str]) -> Self {
+        self.http_secret_hosts = hosts.iter().map(|host| host.to_string()).collect();
+        self
+    }
+
+    fn check_request(&self, method: &Method, url: &reqwest::Url) -> Result<()> {
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(anyhow!(
+                "Only http and https urls are supported, got {}",
+                url
+            ));
+        }
+        if self.read_only && !READ_ONLY_METHODS.contains(&method.as_str()) {
+            return Err(anyhow!(
+                "Only {} requests are allowed",
+                READ_ONLY_METHODS.join(", ")
+            ));
+        }
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        if !self.allowed_hosts.is_empty()
+            && !self
+                .allowed_hosts
+                .iter()
+                .any(|allowed| domain_matches(&host, allowed))
+        {
+            return Err(anyhow!(
+                "Requests to {} are not allowed. Allowed hosts: {}",
+                host,
+                self.allowed_hosts.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Sends the request and returns the response formatted for the model.
+    pub fn send(
+        &self,
+        method: &str,
+        url: &str,
+        query: &HashMap<String, String>,
+        headers: &HashMap<String, String>,
+        body: Option<&serde_json::Value>,
+    ) -> Result<String> {
+        let method = Method::from_bytes(method.trim().to_uppercase().as_bytes())
+            .map_err(|_| anyhow!("Invalid HTTP method: {}", method))?;
+        let url = reqwest::Url::parse(url).map_err(|e| anyhow!("Invalid url {}: {}", url, e))?;
+        self.check_request(&method, &url)?;
+
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        let secrets = self
+            .secret_headers
+            .iter()
+            .filter(|secret| domain_matches(&host, &secret.host))
+            .collect::<Vec<_>>();
+        if !secrets.is_empty()
+            && url.scheme() != "https"
+            && !self
+                .http_secret_hosts
+                .iter()
+                .any(|allowed| domain_matches(&host, allowed))
+        {
+            return Err(anyhow!(
+                "Requests to {} need credentials, which are only sent over https. Use an https url",
+                host
+            ));
+        }
+        let mut request = self.client.request(method.clone(), url);
+        if !query.is_empty() {
+            request = request.query(query);
+        }
+        for (name, value) in headers {
+            if !secrets
+                .iter()
+                .any(|secret| secret.name.eq_ignore_ascii_case(name))
+            {
+                request = request.header(name, value);
+            }
+        }
+        for secret in &secrets {
+            request = request.header(&secret.name, &secret.value);
+        }
+        request = match body {
+            None | Some(serde_json::Value::Null) => request,
+            Some(serde_json::Value::String(text)) => request.body(text.clone()),
+            Some(json) => request.json(json),
+        };
+
+        let response = self.client.send(request)?;
+        Ok(self.format_response(&method, &response))
+    }
+
+    fn format_response(&self, method: &Method, response: &WebResponse) -> String {
+        let mut output = format!("Status: {}", response.status);
+        for name in &self.response_headers {
+            for value in response.headers.get_all(name.as_str()) {
+                output.push_str(&format!(
+                    "\n{}: {}",
+                    name,
+                    self.redact(String::from_utf8_lossy(value.as_bytes()).into_owned())
+                ));
+            }
+        }
+        if method == Method::HEAD || response.body.is_empty() {
+            return output;
+        }
+
+        let Ok(text) = std::str::from_utf8(&response.body) else {
+            output.push_str(&format!(
+                "\n\nThe body is binary data of {} bytes.",
+                response.body.len()
+            ));
+            return output;
+        };
+        let body = serde_json::from_str::<serde_json::Value>(text)
+            .ok()
+            .and_then(|json| serde_json::to_string_pretty(&json).ok())
+            .unwrap_or_else(|| text.to_string());
+        // Redact before truncating, so no part of a secret is left at the cut.
+        let body = self.redact(body);
+        let length = body.chars().count();
+        output.push_str("\n\n");
+        if length > self.max_body_chars {
+            output.extend(body.chars().take(self.max_body_chars));
+            output.push_str(&format!(
+                "\n\n[Truncated: showing the first {} of {} characters.]",
+                self.max_body_chars, length
+            ));
+        } else {
+            output.push_str(&body);
+        }
+        output
+    }
+
+    /// Replaces the values of the secret headers, as they are and escaped in JSON strings, in case the API echoes
+    /// them back.
+    fn redact(&self, mut output: String) -> String {
+        for secret in &self.secret_headers {
+            if secret.value.is_empty() {
+                continue;
+            }
+            output = output.replace(&secret.value, "[REDACTED]");
+            let escaped = serde_json::to_string(&secret.value).unwrap_or_default();
+            let escaped = escaped.trim_matches('"');
+            if escaped != secret.value {
+                output = output.replace(escaped, "[REDACTED]");
+            }
+        }
+        output
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "HttpRequestToolParams")]
+pub struct HttpRequestToolParams {
+    #[schemars(description = "The url of the request")]
+    url: String,
+    #[schemars(description = "The HTTP method, GET by default")]
+    method: Option<String>,
+    #[schemars(description = "The query parameters, added to the url")]
+    params: Option<HashMap<String, String>>,
+    #[schemars(description = "The request headers")]
+    headers: Option<HashMap<String, String>>,
+    #[schemars(
+        description = "The request body. JSON objects and arrays are sent as JSON, strings as they are"
+    )]
+    body: Option<serde_json::Value>,
+}
+
+impl Tool for HttpRequestTool {
+    type Params = HttpRequestToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: HttpRequestToolParams) -> Result<String> {
+        self.send(
+            arguments.method.as_deref().unwrap_or("GET"),
+            &arguments.url,
+            &arguments.params.unwrap_or_default(),
+            &arguments.headers.unwrap_or_default(),
+            arguments.body.as_ref(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+    use serde_json::json;
+
+    fn params(url: String, method: &str) -> HttpRequestToolParams {
+        HttpRequestToolParams {
+            url,
+            method: Some(method.to_string()),
+            params: None,
+            headers: None,
+            body: None,
+        }
+    }
+
+    #[test]
+    fn test_http_request_tool() {
+        let server = MockServer::start(vec![
+            MockServer::response(
+                201,
+                &[
+                    ("Content-Type", "application/json"),
+                    ("Location", "/items/7"),
+                    ("X-Internal", "hidden"),
+                ],
+                r#"{"id":7,"name":"lamp"}"#,
+            ),
+            MockServer::response(200, &[("Content-Type", "text/plain")], "0123456789"),
+        ]);
+        let tool = HttpRequestTool::new().with_max_body_chars(4);
+
+        let mut arguments = params(format!("{}/items", server.url()), "post");
+        arguments.params = Some(HashMap::from([("dry".to_string(), "no".to_string())]));
+        arguments.headers = Some(HashMap::from([(
+            "X-Request-Id".to_string(),
+            "abc".to_string(),
+        )]));
+        arguments.body = Some(json!({"name": "lamp"}));
+        assert_eq!(
+            Tool::forward(&tool.clone().with_max_body_chars(100), arguments).unwrap(),
+            "Status: 201 Created\ncontent-type: application/json\nlocation: /items/7\n\n{\n  \"id\": 7,\n  \"name\": \"lamp\"\n}"
+        );
+        assert_eq!(
+            Tool::forward(&tool, params(server.url(), "GET")).unwrap(),
+            "Status: 200 OK\ncontent-type: text/plain\n\n0123\n\n[Truncated: showing the first 4 of 10 characters.]"
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/items?dry=no");
+        assert_eq!(requests[0].header("x-request-id"), Some("abc"));
+        assert_eq!(requests[0].header("content-type"), Some("application/json"));
+        assert_eq!(requests[0].body, r#"{"name":"lamp"}"#);
+    }
+
+    #[test]
+    fn test_http_request_tool_secret_headers() {
+        let server = MockServer::start(vec![
+            MockServer::response(
+                200,
+                &[],
+                r#"{"headers": {"authorization": "Bearer s3cret"}}"#,
+            ),
+            MockServer::response(200, &[], r#"{"key": "a\\b"}"#),
+            MockServer::response(200, &[], "0123Bearer s3cret"),
+        ]);
+        let tool = HttpRequestTool::new()
+            .with_allowed_hosts(&["127.0.0.1"])
+            .with_secret_header("127.0.0.1", "Authorization", "Bearer s3cret")
+            .with_secret_header("127.0.0.1", "X-Key", "a\\b")
+            .with_secret_header("api.example.com", "X-Api-Key", "other");
+        assert!(!format!("{:?}", tool).contains("s3cret"));
+        assert_eq!(
+            Tool::forward(&tool, params(server.url(), "GET"))
+                .unwrap_err()
+                .to_string(),
+            "Requests to 127.0.0.1 need credentials, which are only sent over https. Use an https url"
+        );
+        let tool = tool.with_http_secret_hosts(&["127.0.0.1"]);
+
+        let mut arguments = params(server.url(), "GET");
+        arguments.headers = Some(HashMap::from([(
+            "authorization".to_string(),
+            "Bearer guess".to_string(),
+        )]));
+        let output = Tool::forward(&tool, arguments).unwrap();
+        assert!(
+            output.ends_with("{\n  \"headers\": {\n    \"authorization\": \"[REDACTED]\"\n  }\n}")
+        );
+
+        assert_eq!(
+            Tool::forward(&tool, params(server.url(), "GET")).unwrap(),
+            "Status: 200 OK\n\n{\n  \"key\": \"[REDACTED]\"\n}"
+        );
+        assert_eq!(
+            Tool::forward(
+                &tool.clone().with_max_body_chars(8),
+                params(server.url(), "GET")
+            )
+            .unwrap(),
+            "Status: 200 OK\n\n0123[RED\n\n[Truncated: showing the first 8 of 14 characters.]"
+        );
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[0].header("authorization"), Some("Bearer s3cret"));
+        assert_eq!(requests[0].header("x-api-key"), None);
+    }
+
+    #[test]
+    fn test_http_request_tool_restrictions() {
+        let tool = HttpRequestTool::new()
+            .with_allowed_hosts(&["example.com"])
+            .with_read_only(true);
+        let error = |url: &str, method: &str| {
+            Tool::forward(&tool, params(url.to_string(), method))
+                .unwrap_err()
+                .to_string()
+        };
+        assert_eq!(
+            error("https://evil.com/", "GET"),
+            "Requests to evil.com are not allowed. Allowed hosts: example.com"
+        );
+        assert_eq!(
+            error("https://api.example.com/items", "DELETE"),
+            "Only GET, HEAD, OPTIONS requests are allowed"
+        );
+        assert_eq!(
+            error("file:///etc/passwd", "GET"),
+            "Only http and https urls are supported, got file:///etc/passwd"
+        );
+        assert_eq!(
+            error("https://example.com/", "GE T"),
+            "Invalid HTTP method: GE T"
+        );
+    }
+}
@@ -2,21 +2,59 @@
 //! You can also implement your own tools by implementing the `Tool` trait.
 
 pub mod base;
+pub mod bing_search;
+pub mod brave_search;
+pub mod cache;
 pub mod ddg_search;
+pub mod documents;
+pub mod filesystem;
 pub mod final_answer;
 pub mod google_search;
+pub mod http_request;
+pub mod output;
+pub mod patch;
+pub mod policy;
+pub mod readability;
+pub mod registry;
+pub mod retriever;
+pub mod searxng_search;
+pub mod shell;
 pub mod tool_traits;
+pub mod validation;
 pub mod visit_website;
+pub mod web_client;
+pub mod web_search;
+pub mod wikipedia;
 
 #[cfg(feature = "code-agent")]
 pub mod python_interpreter;
+#[cfg(feature = "sql")]
+pub mod sql;
 
 pub use base::*;
+pub use bing_search::*;
+pub use brave_search::*;
+pub use cache::*;
 pub use ddg_search::*;
+pub use documents::*;
+pub use filesystem::*;
 pub use final_answer::*;
 pub use google_search::*;
+pub use http_request::*;
+pub use output::*;
+pub use policy::*;
+pub use registry::*;
+pub use retriever::*;
+pub use searxng_search::*;
+pub use shell::*;
 pub use tool_traits::*;
+pub use validation::*;
 pub use visit_website::*;
+pub use web_client::*;
+pub use web_search::*;
+pub use wikipedia::*;
 
 #[cfg(feature = "code-agent")]
 pub use python_interpreter::*;
+#[cfg(feature = "sql")]
+pub use sql::*;
@@ -0,0 +1,238 @@
+//! This module contains the rich outputs of tools: text, structured JSON, images and files.
+//!
+//! A tool returns a [`ToolOutput`] from [`Tool::forward_output`]. The function calling agent shows the model
+//! [`ToolOutput::render`], the code agent turns JSON into native python values, and the images and files become
+//! [`Artifact`]s that are kept with the step and returned by [`Agent::run_with_artifacts`].
+//!
+//! [`Tool::forward_output`]: super::tool_traits::Tool::forward_output
+//! [`Agent::run_with_artifacts`]: crate::agents::Agent::run_with_artifacts
+
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A file produced by a tool, such as a generated chart.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Artifact {
+    /// The file name, e.g. `chart.png`.
+    pub name: String,
+    pub mime_type: String,
+    /// The content, serialized as base64.
+    #[serde(with = "base64_data")]
+    pub data: Vec<u8>,
+}
+
+mod base64_data {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(data: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(data))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        STANDARD.decode(text).map_err(serde::de::Error::custom)
+    }
+}
+
+impl Artifact {
+    pub fn new(name: impl Into<String>, mime_type: impl Into<String>, data: Vec<u8>) -> Self {
+        Artifact {
+            name: name.into(),
+            mime_type: mime_type.into(),
+            data,
+        }
+    }
+
+    /// Reads a file, guessing its MIME type from the extension.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        Ok(Artifact::new(name, mime_type_for(path), data))
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.mime_type.starts_with("image/")
+    }
+
+    /// Writes the artifact into the directory under its name and returns the path.
+    pub fn save(&self, dir: impl AsRef<Path>) -> Result<PathBuf> {
+        let name = Path::new(&self.name)
+            .file_name()
+            .ok_or_else(|| anyhow::anyhow!("Invalid artifact name '{}'", self.name))?;
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(name);
+        std::fs::write(&path, &self.data)?;
+        Ok(path)
+    }
+}
+
+/// The MIME type of a file, from its extension.
+pub fn mime_type_for(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "txt" | "md" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// What a tool call returns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ToolOutput {
+    Text(String),
+    /// Structured data, which the code agent receives as python lists and dicts.
+    Json(Value),
+    /// Structured data for the code agent, shown to the model as the given text instead of as JSON.
+    JsonWithText {
+        json: Value,
+        text: String,
+    },
+    Image(Artifact),
+    File(Artifact),
+}
+
+impl ToolOutput {
+    /// The text shown to the model. Images and files are described, their content is only kept as an artifact.
+    pub fn render(&self) -> String {
+        match self {
+            ToolOutput::Text(text) | ToolOutput::JsonWithText { text, .. } => text.clone(),
+            ToolOutput::Json(value) => {
+                serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+            }
+            ToolOutput::Image(artifact) => format!(
+                "[Image artifact: {} ({}, {} bytes)]",
+                artifact.name,
+                artifact.mime_type,
+                artifact.data.len()
+            ),
+            ToolOutput::File(artifact) => format!(
+                "[File artifact: {} ({}, {} bytes)]",
+                artifact.name,
+                artifact.mime_type,
+                artifact.data.len()
+            ),
+        }
+    }
+
+    pub fn artifact(&self) -> Option<&Artifact> {
+        match self {
+            ToolOutput::Image(artifact) | ToolOutput::File(artifact) => Some(artifact),
+            _ => None,
+        }
+    }
+
+    /// Wraps a file as an image or a file output, depending on its MIME type.
+    pub fn from_artifact(artifact: Artifact) -> Self {
+        if artifact.is_image() {
+            ToolOutput::Image(artifact)
+        } else {
+            ToolOutput::File(artifact)
+        }
+    }
+}
+
+impl From<String> for ToolOutput {
+    fn from(text: String) -> Self {
+        ToolOutput::Text(text)
+    }
+}
+
+impl From<&str> for ToolOutput {
+    fn from(text: &str) -> Self {
+        ToolOutput::Text(text.to_string())
+    }
+}
+
+impl From<Value> for ToolOutput {
+    fn from(value: Value) -> Self {
+        ToolOutput::Json(value)
+    }
+}
+
+impl From<Artifact> for ToolOutput {
+    fn from(artifact: Artifact) -> Self {
+        ToolOutput::from_artifact(artifact)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        assert_eq!(ToolOutput::from("hello").render(), "hello");
+        assert_eq!(
+            ToolOutput::from(json!({"a": [1, 2]})).render(),
+            "{\n  \"a\": [\n    1,\n    2\n  ]\n}"
+        );
+        let chart = ToolOutput::from(Artifact::new("chart.png", "image/png", vec![0; 16]));
+        assert_eq!(
+            chart.render(),
+            "[Image artifact: chart.png (image/png, 16 bytes)]"
+        );
+        assert_eq!(chart.artifact().unwrap().name, "chart.png");
+        let report = ToolOutput::from(Artifact::new("report.csv", "text/csv", b"a,b".to_vec()));
+        assert_eq!(
+            report.render(),
+            "[File artifact: report.csv (text/csv, 3 bytes)]"
+        );
+        assert!(ToolOutput::from("hello").artifact().is_none());
+    }
+
+    #[test]
+    fn test_artifact_data_is_base64() {
+        let output = ToolOutput::from(Artifact::new(
+            "a.bin",
+            "application/octet-stream",
+            b"hi!".to_vec(),
+        ));
+        let json = serde_json::to_value(&output).unwrap();
+        assert_eq!(json["File"]["data"], "aGkh");
+        assert_eq!(serde_json::from_value::<ToolOutput>(json).unwrap(), output);
+        assert!(serde_json::from_value::<Artifact>(
+            json!({"name": "a", "mime_type": "text/plain", "data": "%%"})
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_artifact_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("plot.PNG");
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        let artifact = Artifact::from_file(&path).unwrap();
+        assert_eq!(
+            artifact,
+            Artifact::new("plot.PNG", "image/png", vec![1, 2, 3])
+        );
+
+        let saved = artifact.save(dir.path().join("out")).unwrap();
+        assert_eq!(std::fs::read(saved).unwrap(), vec![1, 2, 3]);
+        assert!(Artifact::new("..", "text/plain", vec![])
+            .save(dir.path())
+            .is_err());
+    }
+}
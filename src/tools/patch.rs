@@ -0,0 +1,266 @@
+//! This module parses and applies unified diffs, as used by the `apply_patch` tool.
+//!
+//! Hunks are matched by their context and removed lines rather than only by line number, so a patch still applies
+//! when the file has shifted since the diff was written.
+
+use anyhow::{anyhow, Result};
+
+/// The changes to one file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePatch {
+    /// The path before the change, or `None` if the file is created.
+    pub old_path: Option<String>,
+    /// The path after the change, or `None` if the file is deleted.
+    pub new_path: Option<String>,
+    pub hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hunk {
+    /// The line the hunk starts at in the original file, starting at 1.
+    pub old_start: usize,
+    pub lines: Vec<HunkLine>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum HunkLine {
+    Context(String),
+    Remove(String),
+    Add(String),
+}
+
+impl Hunk {
+    /// The lines the hunk expects in the original file.
+    fn old_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Remove(text) => Some(text.as_str()),
+                HunkLine::Add(_) => None,
+            })
+            .collect()
+    }
+
+    /// The lines that replace them.
+    fn new_lines(&self) -> Vec<&str> {
+        self.lines
+            .iter()
+            .filter_map(|line| match line {
+                HunkLine::Context(text) | HunkLine::Add(text) => Some(text.as_str()),
+                HunkLine::Remove(_) => None,
+            })
+            .collect()
+    }
+}
+
+/// Parses a path from a `---` or `+++` header, dropping timestamps and the `a/` or `b/` prefix.
+fn parse_path(header: &str) -> Option<String> {
+    let path = header.split('\t').next().unwrap_or_default().trim();
+    if path == "/dev/null" {
+        return None;
+    }
+    let path = path
+        .strip_prefix("a/")
+        .or_else(|| path.strip_prefix("b/"))
+        .unwrap_or(path);
+    Some(path.to_string())
+}
+
+/// Parses the old start line and the old and new line counts of a `@@ -1,3 +1,4 @@` header.
+fn parse_hunk_header(header: &str) -> Result<(usize, usize, usize)> {
+    let invalid = || anyhow!("Invalid hunk header: {}", header);
+    let ranges = header
+        .strip_prefix("@@ ")
+        .and_then(|rest| rest.split(" @@").next())
+        .ok_or_else(invalid)?;
+    let mut ranges = ranges.split_whitespace();
+    let mut parse_range = |sign: char| -> Result<(usize, usize)> {
+        let range = ranges
+            .next()
+            .and_then(|range| range.strip_prefix(sign))
+            .ok_or_else(invalid)?;
+        let (start, count) = range.split_once(',').unwrap_or((range, "1"));
+        Ok((
+            start.parse().map_err(|_| invalid())?,
+            count.parse().map_err(|_| invalid())?,
+        ))
+    };
+    let (old_start, old_count) = parse_range('-')?;
+    let (_, new_count) = parse_range('+')?;
+    Ok((old_start, old_count, new_count))
+}
+
+/// Parses a unified diff with one or more files.
+pub fn parse_patch(patch: &str) -> Result<Vec<FilePatch>> {
+    let lines = patch.lines().collect::<Vec<_>>();
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        if let Some(old_header) = line.strip_prefix("--- ") {
+            let new_header = lines
+                .get(i + 1)
+                .and_then(|line| line.strip_prefix("+++ "))
+                .ok_or_else(|| anyhow!("Expected a '+++' line after '{}'", line))?;
+            files.push(FilePatch {
+                old_path: parse_path(old_header),
+                new_path: parse_path(new_header),
+                hunks: Vec::new(),
+            });
+            i += 2;
+        } else if line.starts_with("@@") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| anyhow!("Found a hunk before the '---' and '+++' file headers"))?;
+            let (old_start, mut old_remaining, mut new_remaining) = parse_hunk_header(line)?;
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+            };
+            i += 1;
+            while old_remaining > 0 || new_remaining > 0 {
+                let Some(line) = lines.get(i) else {
+                    return Err(anyhow!("The hunk '{}' ends before all its lines", line));
+                };
+                i += 1;
+                let (kind, text) = match line.chars().next() {
+                    Some('\\') => continue,
+                    Some(kind) => (kind, &line[kind.len_utf8()..]),
+                    // Some editors strip the space of empty context lines.
+                    None => (' ', ""),
+                };
+                match kind {
+                    ' ' => {
+                        old_remaining = old_remaining.saturating_sub(1);
+                        new_remaining = new_remaining.saturating_sub(1);
+                        hunk.lines.push(HunkLine::Context(text.to_string()));
+                    }
+                    '-' => {
+                        old_remaining = old_remaining.saturating_sub(1);
+                        hunk.lines.push(HunkLine::Remove(text.to_string()));
+                    }
+                    '+' => {
+                        new_remaining = new_remaining.saturating_sub(1);
+                        hunk.lines.push(HunkLine::Add(text.to_string()));
+                    }
+                    _ => return Err(anyhow!("Invalid line in hunk: {}", line)),
+                }
+            }
+            file.hunks.push(hunk);
+        } else {
+            // Skip `diff --git`, `index` and other lines between files.
+            i += 1;
+        }
+    }
+    if files.is_empty() {
+        return Err(anyhow!(
+            "The patch contains no files. Use the unified diff format with '---' and '+++' headers."
+        ));
+    }
+    Ok(files)
+}
+
+/// Applies the hunks to the text of a file.
+///
+/// Each hunk is matched at its line number if possible, and otherwise at the closest position after the
+/// previous hunk.
+pub fn apply_hunks(original: &str, hunks: &[Hunk]) -> Result<String> {
+    let lines = original.lines().collect::<Vec<_>>();
+    let mut result: Vec<&str> = Vec::new();
+    let mut cursor = 0;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let old_lines = hunk.old_lines();
+        let position = if old_lines.is_empty() {
+            // A pure insertion comes after line `old_start`.
+            hunk.old_start.clamp(cursor, lines.len())
+        } else {
+            let expected = hunk.old_start.saturating_sub(1);
+            let last = lines.len().checked_sub(old_lines.len());
+            let mut candidates = match last {
+                Some(last) if last >= cursor => (cursor..=last).collect::<Vec<_>>(),
+                _ => Vec::new(),
+            };
+            candidates.sort_by_key(|position| position.abs_diff(expected));
+            candidates
+                .into_iter()
+                .find(|&position| lines[position..position + old_lines.len()] == old_lines[..])
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Hunk {} does not match the file. These lines were not found:\n{}",
+                        index + 1,
+                        old_lines.join("\n")
+                    )
+                })?
+        };
+        result.extend(&lines[cursor..position]);
+        result.extend(hunk.new_lines());
+        cursor = position + old_lines.len();
+    }
+    result.extend(&lines[cursor..]);
+
+    let mut text = result.join("\n");
+    if !text.is_empty() && (original.is_empty() || original.ends_with('\n')) {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PATCH: &str = "diff --git a/src/lib.rs b/src/lib.rs
+index 83db48f..bf269f4 100644
+--- a/src/lib.rs
++++ b/src/lib.rs
+@@ -1,3 +1,3 @@
+ fn main() {
+-    println!(\"Hello\");
++    println!(\"Hello, world!\");
+ }
+--- /dev/null
++++ b/README.md
+@@ -0,0 +1,2 @@
++# Title
++
+";
+
+    #[test]
+    fn test_parse_patch() {
+        let files = parse_patch(PATCH).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].old_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(files[0].new_path.as_deref(), Some("src/lib.rs"));
+        assert_eq!(
+            files[0].hunks[0].lines[1],
+            HunkLine::Remove("    println!(\"Hello\");".to_string())
+        );
+        assert_eq!(files[1].old_path, None);
+        assert_eq!(
+            files[1].hunks[0].lines,
+            vec![
+                HunkLine::Add("# Title".to_string()),
+                HunkLine::Add(String::new())
+            ]
+        );
+        assert!(parse_patch("just some text").is_err());
+        assert!(parse_patch("--- a/x\n+++ b/x\n@@ -1,2 +1,2 @@\n a\n").is_err());
+    }
+
+    #[test]
+    fn test_apply_hunks() {
+        let files = parse_patch(PATCH).unwrap();
+        let original = "// header\nfn main() {\n    println!(\"Hello\");\n}\n";
+        // The hunk says line 1, but the file has an extra line at the top.
+        assert_eq!(
+            apply_hunks(original, &files[0].hunks).unwrap(),
+            "// header\nfn main() {\n    println!(\"Hello, world!\");\n}\n"
+        );
+        assert_eq!(apply_hunks("", &files[1].hunks).unwrap(), "# Title\n\n");
+
+        let error = apply_hunks("fn other() {}\n", &files[0].hunks).unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Hunk 1 does not match the file"));
+    }
+}
@@ -0,0 +1,569 @@
+//! This module contains execution policies for tools: a timeout, retries with backoff and a circuit breaker.
+//!
+//! Wrap a tool in a [`PolicyTool`] to apply a [`ToolPolicy`] every time an agent calls it, as a function call or from
+//! the code agent's python interpreter. Timeouts, retries and a disabled tool are reported to the model in the
+//! error observation, so it can change its approach.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use smolagents_rs::tools::{AnyTool, PolicyTool, ToolPolicy, VisitWebsiteTool};
+//!
+//! let policy = ToolPolicy::default()
+//!     .with_timeout(Some(Duration::from_secs(20)))
+//!     .with_failure_threshold(Some(2));
+//! let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(PolicyTool::new(VisitWebsiteTool::new(), policy))];
+//! ```
+//!
+//! Errors marked with [`RetryableError`] and connection failures or timeouts of HTTP requests are retried. Calls that
+//! time out are only retried if [`ToolPolicy::retry_on_timeout`] is set: a timed out call keeps running in the
+//! background, with its result ignored, so repeating a call with side effects could run them twice.
+//!
+//! Policies are opt-in: tools that are not wrapped run without a timeout, retries or circuit breaker, since only the
+//! caller knows which tools are slow or flaky and which are safe to repeat. Wrap the tools that call the network or
+//! run long computations.
+
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::output::ToolOutput;
+use super::tool_traits::{parse_arguments, AnyTool, Tool, ToolInfo};
+use crate::errors::AgentError;
+use crate::models::retry::RetryPolicy;
+
+/// The default time a tool call may take.
+pub const DEFAULT_TOOL_TIMEOUT: Duration = Duration::from_secs(60);
+/// The default number of failed calls in a row after which a tool is disabled.
+pub const DEFAULT_FAILURE_THRESHOLD: usize = 3;
+
+/// An error that may not happen again if the call is repeated, such as a rate limit. A [`PolicyTool`] retries
+/// calls that fail with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryableError(pub String);
+
+impl fmt::Display for RetryableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for RetryableError {}
+
+/// Whether a failed call may succeed if it is repeated.
+pub fn is_retryable(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| {
+        cause.is::<RetryableError>()
+            || cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_timeout() || e.is_connect())
+    })
+}
+
+/// How a [`PolicyTool`] runs the calls of its tool.
+#[derive(Debug, Clone)]
+pub struct ToolPolicy {
+    /// The time a call may take, or `None` to wait for as long as the tool runs.
+    pub timeout: Option<Duration>,
+    /// How often and after how long retryable failures are retried.
+    pub retry: RetryPolicy,
+    /// The number of failed calls in a row after which the tool is disabled for the rest of the run, or `None` to
+    /// never disable it.
+    pub failure_threshold: Option<usize>,
+    /// Whether calls that time out are retried. Only set it for idempotent tools, as the timed out call keeps running.
+    pub retry_on_timeout: bool,
+}
+
+impl Default for ToolPolicy {
+    fn default() -> Self {
+        ToolPolicy {
+            timeout: Some(DEFAULT_TOOL_TIMEOUT),
+            retry: RetryPolicy::new(2),
+            failure_threshold: Some(DEFAULT_FAILURE_THRESHOLD),
+            retry_on_timeout: false,
+        }
+    }
+}
+
+impl ToolPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn with_failure_threshold(mut self, failure_threshold: Option<usize>) -> Self {
+        self.failure_threshold = failure_threshold;
+        self
+    }
+
+    pub fn with_retry_on_timeout(mut self, retry_on_timeout: bool) -> Self {
+        self.retry_on_timeout = retry_on_timeout;
+        self
+    }
+}
+
+/// Why an attempt to call the tool failed.
+enum Failure {
+    Timeout,
+    Error(anyhow::Error),
+}
+
+impl Failure {
+    fn is_retryable(&self, policy: &ToolPolicy) -> bool {
+        match self {
+            Failure::Timeout => policy.retry_on_timeout,
+            Failure::Error(e) => is_retryable(e),
+        }
+    }
+}
+
+/// A tool that runs its calls under a [`ToolPolicy`]. Clones share the circuit breaker.
+///
+/// [`PolicyTool::new`] wraps a tool by value. [`PolicyTool::boxed`] wraps any `Box<dyn AnyTool>`, such as a cached
+/// tool, a cassette tool or a tool taken from a registry. A boxed tool only reports its errors as messages, so they
+/// count for the circuit breaker but are not retried; its timed out calls are retried if the policy allows it.
+#[derive(Debug, Clone)]
+pub struct PolicyTool<T> {
+    tool: T,
+    policy: ToolPolicy,
+    /// The number of failed calls in a row.
+    failures: Arc<Mutex<usize>>,
+}
+
+impl<T> PolicyTool<T> {
+    pub fn tool(&self) -> &T {
+        &self.tool
+    }
+
+    pub fn policy(&self) -> &ToolPolicy {
+        &self.policy
+    }
+
+    /// Whether the tool has failed too often and is disabled until the next run.
+    pub fn is_disabled(&self) -> bool {
+        let failures = *self.failures.lock().unwrap();
+        self.policy
+            .failure_threshold
+            .is_some_and(|threshold| failures >= threshold)
+    }
+
+    /// Runs `attempt` with the retries and the circuit breaker of the policy. `attempt` returns an error for calls
+    /// that can not succeed, such as invalid arguments, which are reported at once and do not count as failures.
+    fn call_with_policy(
+        &self,
+        name: &str,
+        attempt: impl Fn() -> Result<Result<ToolOutput, Failure>, AgentError>,
+    ) -> Result<ToolOutput, AgentError> {
+        if self.is_disabled() {
+            return Err(AgentError::Execution(format!(
+                "The tool {} is disabled for the rest of the run because it failed {} times in a row. Use another tool or approach.",
+                name,
+                self.failures.lock().unwrap()
+            )));
+        }
+
+        let mut retry = 0;
+        let failure = loop {
+            match attempt()? {
+                Ok(output) => {
+                    *self.failures.lock().unwrap() = 0;
+                    return Ok(output);
+                }
+                Err(failure)
+                    if failure.is_retryable(&self.policy)
+                        && retry < self.policy.retry.max_retries =>
+                {
+                    let wait = self.policy.retry.backoff(retry);
+                    log::debug!(
+                        "Tool {} failed, retrying in {:?} ({}/{})",
+                        name,
+                        wait,
+                        retry + 1,
+                        self.policy.retry.max_retries
+                    );
+                    std::thread::sleep(wait);
+                    retry += 1;
+                }
+                Err(failure) => break failure,
+            }
+        };
+
+        let mut message = match failure {
+            Failure::Timeout => format!(
+                "The tool {} timed out after {:?}",
+                name,
+                self.policy.timeout.unwrap_or_default()
+            ),
+            Failure::Error(e) => e.to_string(),
+        };
+        if retry > 0 {
+            message.push_str(&format!(" (failed {} attempts)", retry + 1));
+        }
+        let mut failures = self.failures.lock().unwrap();
+        *failures += 1;
+        if self
+            .policy
+            .failure_threshold
+            .is_some_and(|threshold| *failures >= threshold)
+        {
+            message.push_str(&format!(
+                ". The tool failed {} times in a row and is disabled for the rest of the run.",
+                failures
+            ));
+        }
+        Err(AgentError::Execution(message))
+    }
+}
+
+/// Runs the call in another thread if there is a timeout, and waits for it at most that long.
+fn run_with_timeout<R: Send + 'static>(
+    timeout: Option<Duration>,
+    call: impl FnOnce() -> R + Send + 'static,
+) -> Result<R, Failure> {
+    let Some(timeout) = timeout else {
+        return Ok(call());
+    };
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = sender.send(call());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        Err(RecvTimeoutError::Timeout) => Err(Failure::Timeout),
+        Err(RecvTimeoutError::Disconnected) => Err(Failure::Error(anyhow::anyhow!(
+            "The tool stopped without a result"
+        ))),
+    }
+}
+
+impl<T: Tool + Clone + Send + 'static> PolicyTool<T>
+where
+    T::Params: Send + 'static,
+{
+    pub fn new(tool: T, policy: ToolPolicy) -> Self {
+        PolicyTool {
+            tool,
+            policy,
+            failures: Arc::default(),
+        }
+    }
+
+    /// Calls the tool once, in another thread if there is a timeout.
+    fn attempt(&self, arguments: T::Params) -> Result<ToolOutput, Failure> {
+        let tool = self.tool.clone();
+        run_with_timeout(self.policy.timeout, move || {
+            Tool::forward_output(&tool, arguments)
+        })?
+        .map_err(Failure::Error)
+    }
+}
+
+impl<T: Tool + Clone + Send + 'static> AnyTool for PolicyTool<T>
+where
+    T::Params: Send + 'static,
+{
+    fn name(&self) -> &'static str {
+        Tool::name(&self.tool)
+    }
+
+    fn description(&self) -> &'static str {
+        Tool::description(&self.tool)
+    }
+
+    fn forward_json(&self, json_args: serde_json::Value) -> Result<String, AgentError> {
+        self.forward_json_output(json_args)
+            .map(|output| output.render())
+    }
+
+    fn forward_json_output(&self, json_args: serde_json::Value) -> Result<ToolOutput, AgentError> {
+        self.call_with_policy(Tool::name(&self.tool), || {
+            let arguments = parse_arguments(&self.tool, &json_args)?;
+            Ok(self.attempt(arguments))
+        })
+    }
+
+    fn tool_info(&self) -> ToolInfo {
+        ToolInfo::new::<T::Params, T>(&self.tool)
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTool> {
+        Box::new(self.clone())
+    }
+
+    fn reset(&self) {
+        *self.failures.lock().unwrap() = 0;
+    }
+
+    fn cacheable(&self) -> bool {
+        Tool::cacheable(&self.tool)
+    }
+}
+
+impl PolicyTool<Box<dyn AnyTool>> {
+    /// Runs the calls of any tool under the policy, e.g. one wrapped in a [`ToolCache`].
+    ///
+    /// [`ToolCache`]: super::cache::ToolCache
+    pub fn boxed(tool: Box<dyn AnyTool>, policy: ToolPolicy) -> Self {
+        PolicyTool {
+            tool,
+            policy,
+            failures: Arc::default(),
+        }
+    }
+}
+
+impl AnyTool for PolicyTool<Box<dyn AnyTool>> {
+    fn name(&self) -> &'static str {
+        self.tool.name()
+    }
+
+    fn description(&self) -> &'static str {
+        self.tool.description()
+    }
+
+    fn forward_json(&self, json_args: serde_json::Value) -> Result<String, AgentError> {
+        self.forward_json_output(json_args)
+            .map(|output| output.render())
+    }
+
+    fn forward_json_output(&self, json_args: serde_json::Value) -> Result<ToolOutput, AgentError> {
+        self.call_with_policy(self.tool.name(), || {
+            let tool = self.tool.clone_box();
+            let json_args = json_args.clone();
+            match run_with_timeout(self.policy.timeout, move || {
+                tool.forward_json_output(json_args)
+            }) {
+                Ok(Ok(output)) => Ok(Ok(output)),
+                Ok(Err(e @ AgentError::Parsing(_))) => Err(e),
+                Ok(Err(e)) => Ok(Err(Failure::Error(anyhow::anyhow!(e.to_string())))),
+                Err(failure) => Ok(Err(failure)),
+            }
+        })
+    }
+
+    fn tool_info(&self) -> ToolInfo {
+        self.tool.tool_info()
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTool> {
+        Box::new(PolicyTool {
+            tool: self.tool.clone_box(),
+            policy: self.policy.clone(),
+            failures: self.failures.clone(),
+        })
+    }
+
+    fn reset(&self) {
+        *self.failures.lock().unwrap() = 0;
+        self.tool.reset();
+    }
+
+    fn cacheable(&self) -> bool {
+        self.tool.cacheable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::openai::FunctionCall;
+    use crate::tools::{BaseTool, MemoryCache, ToolCache, ToolGroup};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Sleeps for the given milliseconds, or fails, counting its calls.
+    #[derive(Debug, Clone)]
+    struct FlakyTool {
+        tool: BaseTool,
+        calls: Arc<AtomicUsize>,
+        /// The number of first calls that fail with a retryable error.
+        retryable_failures: usize,
+    }
+
+    #[derive(Deserialize, JsonSchema)]
+    struct FlakyToolParams {
+        sleep_ms: u64,
+        fail: bool,
+    }
+
+    impl Tool for FlakyTool {
+        type Params = FlakyToolParams;
+        fn name(&self) -> &'static str {
+            self.tool.name
+        }
+        fn description(&self) -> &'static str {
+            self.tool.description
+        }
+        fn forward(&self, arguments: FlakyToolParams) -> anyhow::Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(arguments.sleep_ms));
+            if call < self.retryable_failures {
+                return Err(RetryableError("rate limited".to_string()).into());
+            }
+            if arguments.fail {
+                return Err(anyhow::anyhow!("bad input"));
+            }
+            Ok(format!("done after {} calls", call + 1))
+        }
+    }
+
+    fn flaky_tool(
+        retryable_failures: usize,
+        policy: ToolPolicy,
+    ) -> (PolicyTool<FlakyTool>, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tool = FlakyTool {
+            tool: BaseTool {
+                name: "flaky",
+                description: "A flaky tool",
+            },
+            calls: calls.clone(),
+            retryable_failures,
+        };
+        (PolicyTool::new(tool, policy), calls)
+    }
+
+    fn call(
+        tools: &Vec<Box<dyn AnyTool>>,
+        sleep_ms: u64,
+        fail: bool,
+    ) -> Result<String, AgentError> {
+        tools.call(&FunctionCall {
+            name: "flaky".to_string(),
+            arguments: json!({"sleep_ms": sleep_ms, "fail": fail}),
+        })
+    }
+
+    fn fast_retries(max_retries: usize) -> RetryPolicy {
+        RetryPolicy::new(max_retries).initial_backoff(Duration::from_millis(1))
+    }
+
+    #[test]
+    fn test_policy_tool_retries() {
+        let policy = ToolPolicy::new().with_retry_policy(fast_retries(2));
+        let (tool, calls) = flaky_tool(2, policy.clone());
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert_eq!(call(&tools, 0, false).unwrap(), "done after 3 calls");
+
+        let (tool, calls_exhausted) = flaky_tool(5, policy);
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert_eq!(
+            call(&tools, 0, false).unwrap_err().message(),
+            "rate limited (failed 3 attempts)"
+        );
+        // Errors that are not retryable are reported at once.
+        let (tool, _) = flaky_tool(0, ToolPolicy::new().with_retry_policy(fast_retries(2)));
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert_eq!(call(&tools, 0, true).unwrap_err().message(), "bad input");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(calls_exhausted.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn test_policy_tool_timeout() {
+        let policy = ToolPolicy::new()
+            .with_timeout(Some(Duration::from_millis(50)))
+            .with_retry_policy(fast_retries(1));
+        let (tool, calls) = flaky_tool(0, policy.clone());
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert_eq!(
+            call(&tools, 200, false).unwrap_err().message(),
+            "The tool flaky timed out after 50ms"
+        );
+        assert!(call(&tools, 0, false).is_ok());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (tool, calls) = flaky_tool(0, policy.with_retry_on_timeout(true));
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert_eq!(
+            call(&tools, 200, false).unwrap_err().message(),
+            "The tool flaky timed out after 50ms (failed 2 attempts)"
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_policy_tool_circuit_breaker() {
+        let policy = ToolPolicy::new()
+            .with_retry_policy(RetryPolicy::none())
+            .with_failure_threshold(Some(2));
+        let (tool, calls) = flaky_tool(0, policy);
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(tool)];
+        assert!(call(&tools, 0, true).is_err());
+        assert!(call(&tools, 0, false).is_ok());
+        assert_eq!(call(&tools, 0, true).unwrap_err().message(), "bad input");
+        assert_eq!(
+            call(&tools, 0, true).unwrap_err().message(),
+            "bad input. The tool failed 2 times in a row and is disabled for the rest of the run."
+        );
+        let clone = tools[0].clone_box();
+        assert_eq!(
+            clone.forward_json(json!({"sleep_ms": 0, "fail": false})).unwrap_err().message(),
+            "The tool flaky is disabled for the rest of the run because it failed 2 times in a row. Use another tool or approach."
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // Starting a new task resets the clones as well.
+        tools[0].reset();
+        assert!(clone
+            .forward_json(json!({"sleep_ms": 0, "fail": false}))
+            .is_ok());
+        assert!(matches!(
+            clone.forward_json(json!({"sleep_ms": "soon"})),
+            Err(AgentError::Parsing(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_tool_boxed() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let tool = FlakyTool {
+            tool: BaseTool {
+                name: "flaky",
+                description: "A flaky tool",
+            },
+            calls: calls.clone(),
+            retryable_failures: 0,
+        };
+        let cached = ToolCache::new(MemoryCache::new()).wrap(Box::new(tool));
+        let policy = ToolPolicy::new()
+            .with_timeout(Some(Duration::from_millis(50)))
+            .with_retry_policy(fast_retries(2))
+            .with_failure_threshold(Some(2));
+        let tools: Vec<Box<dyn AnyTool>> = vec![Box::new(PolicyTool::boxed(cached, policy))];
+
+        // Successful calls are cached behind the policy.
+        assert_eq!(call(&tools, 0, false).unwrap(), "done after 1 calls");
+        assert_eq!(call(&tools, 0, false).unwrap(), "done after 1 calls");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // Errors of boxed tools are not retried, but count for the circuit breaker.
+        assert_eq!(call(&tools, 0, true).unwrap_err().message(), "bad input");
+        assert_eq!(
+            call(&tools, 200, false).unwrap_err().message(),
+            "The tool flaky timed out after 50ms. The tool failed 2 times in a row and is disabled for the rest of the run."
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert!(tools[0]
+            .clone_box()
+            .forward_json(json!({"sleep_ms": 0, "fail": false}))
+            .is_err());
+        tools[0].reset();
+        assert!(call(&tools, 0, false).is_ok());
+        assert!(matches!(
+            tools[0].forward_json(json!({"sleep_ms": "soon"})),
+            Err(AgentError::Parsing(_))
+        ));
+    }
+}
@@ -0,0 +1,409 @@
+//! This module extracts the main content of a webpage, similar to Mozilla's Readability.
+//!
+//! Paragraphs are scored by their length and number of commas, and the score is added to their parent and
+//! grandparent. The block with the highest score, discounted by its link density, is taken as the article.
+//! Boilerplate such as cookie banners, sidebars and share buttons is removed from the article before it is
+//! converted to markdown.
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use ego_tree::{NodeId, NodeRef};
+use htmd::HtmlToMarkdown;
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+
+/// Articles with less text than this are not trusted and the whole page is used instead.
+const MIN_ARTICLE_CHARS: usize = 200;
+/// Paragraphs shorter than this are not scored.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+/// Tags that never contain article content.
+const SKIP_TAGS: &[&str] = &[
+    "script", "style", "noscript", "header", "nav", "footer", "aside", "form", "button", "iframe",
+    "svg", "select", "input", "textarea", "template", "dialog",
+];
+const VOID_TAGS: &[&str] = &["br", "hr", "img", "wbr"];
+/// Attributes that are kept when the article is serialized.
+const KEPT_ATTRIBUTES: &[&str] = &["href", "src", "alt", "title"];
+
+/// The main content of a webpage and its metadata.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Article {
+    pub title: Option<String>,
+    pub byline: Option<String>,
+    pub published: Option<String>,
+    /// The cleaned HTML of the main content.
+    pub content: String,
+}
+
+impl Article {
+    /// The text of the article without markup.
+    pub fn text(&self) -> String {
+        Html::parse_fragment(&self.content)
+            .root_element()
+            .text()
+            .collect::<String>()
+    }
+
+    /// The article as markdown, preceded by its metadata.
+    pub fn to_markdown(&self) -> String {
+        let mut metadata = Vec::new();
+        if let Some(title) = &self.title {
+            metadata.push(format!("Title: {}", title));
+        }
+        if let Some(byline) = &self.byline {
+            metadata.push(format!("Byline: {}", byline));
+        }
+        if let Some(published) = &self.published {
+            metadata.push(format!("Published: {}", published));
+        }
+        let converter = HtmlToMarkdown::builder().build();
+        let content = converter
+            .convert(&self.content)
+            .unwrap_or_else(|_| self.text());
+        if metadata.is_empty() {
+            return content;
+        }
+        format!("{}\n\n---\n\n{}", metadata.join("\n"), content)
+    }
+}
+
+/// Extracts the main content of the page. Returns `None` if no block stands out as the article.
+pub fn extract_article(html: &str) -> Option<Article> {
+    let document = Html::parse_document(html);
+    let best = best_candidate(&document)?;
+
+    let mut content = String::new();
+    for child in best.children() {
+        write_clean(child, &mut content);
+    }
+    let article = Article {
+        title: extract_title(&document),
+        byline: extract_byline(&document),
+        published: extract_published(&document),
+        content,
+    };
+    if article.text().trim().chars().count() < MIN_ARTICLE_CHARS {
+        return None;
+    }
+    Some(article)
+}
+
+fn unlikely_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)comment|sidebar|footer|banner|\bads?\b|advert|promo|related|share|social|nav|menu|breadcrumb|sponsor|widget|masthead|pager|pagination")
+            .unwrap()
+    })
+}
+
+/// Blocks that are removed even if they also look like content, e.g. `cookie-consent-content`.
+fn boilerplate_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)cookie|consent|gdpr|popup|modal|newsletter|subscribe|paywall").unwrap()
+    })
+}
+
+fn positive_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"(?i)article|content|main|body|post|entry|story|text|blog").unwrap()
+    })
+}
+
+fn class_and_id(element: &scraper::node::Element) -> String {
+    format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.id().unwrap_or_default()
+    )
+}
+
+/// Whether the element is boilerplate that should never be part of the article.
+fn is_boilerplate(element: &scraper::node::Element) -> bool {
+    if SKIP_TAGS.contains(&element.name()) {
+        return true;
+    }
+    if matches!(
+        element.attr("role"),
+        Some("dialog" | "navigation" | "complementary")
+    ) || element.attr("aria-modal") == Some("true")
+        || element.attr("hidden").is_some()
+    {
+        return true;
+    }
+    let names = class_and_id(element);
+    if boilerplate_pattern().is_match(&names) {
+        return true;
+    }
+    unlikely_pattern().is_match(&names) && !positive_pattern().is_match(&names)
+}
+
+/// A bonus or penalty from the element's class and id.
+fn class_weight(element: &scraper::node::Element) -> f64 {
+    let names = class_and_id(element);
+    let mut weight = 0.0;
+    if positive_pattern().is_match(&names) {
+        weight += 25.0;
+    }
+    if unlikely_pattern().is_match(&names) {
+        weight -= 25.0;
+    }
+    if matches!(element.name(), "article" | "main") || element.attr("role") == Some("main") {
+        weight += 25.0;
+    }
+    weight
+}
+
+fn text_len(element: ElementRef) -> usize {
+    element.text().map(|text| text.trim().chars().count()).sum()
+}
+
+/// The share of the element's text that is inside links.
+pub fn link_density(element: ElementRef) -> f64 {
+    let total = text_len(element);
+    if total == 0 {
+        return 0.0;
+    }
+    let links = Selector::parse("a").unwrap();
+    let link_text = element.select(&links).map(text_len).sum::<usize>();
+    link_text as f64 / total as f64
+}
+
+/// Whether any ancestor of the node is boilerplate.
+fn inside_boilerplate(node: NodeRef<Node>) -> bool {
+    node.ancestors()
+        .filter_map(|ancestor| ancestor.value().as_element())
+        .any(is_boilerplate)
+}
+
+fn best_candidate(document: &Html) -> Option<ElementRef<'_>> {
+    let paragraphs = Selector::parse("p, pre, td, blockquote").unwrap();
+    let mut scores: HashMap<NodeId, f64> = HashMap::new();
+    for paragraph in document.select(&paragraphs) {
+        if inside_boilerplate(*paragraph) {
+            continue;
+        }
+        let text = paragraph.text().collect::<String>();
+        let length = text.trim().chars().count();
+        if length < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (length as f64 / 100.0).min(3.0);
+        let ancestors = paragraph
+            .ancestors()
+            .filter(|ancestor| ancestor.value().is_element())
+            .take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let share = if level == 0 { score } else { score / 2.0 };
+            *scores.entry(ancestor.id()).or_insert(0.0) += share;
+        }
+    }
+
+    scores
+        .into_iter()
+        .filter_map(|(id, score)| {
+            let element = ElementRef::wrap(document.tree.get(id)?)?;
+            if matches!(element.value().name(), "html" | "body") {
+                return None;
+            }
+            let score = (score + class_weight(element.value())) * (1.0 - link_density(element));
+            Some((element, score))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(element, _)| element)
+}
+
+fn escape(text: &str, quote: bool) -> String {
+    let text = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    if quote {
+        text.replace('"', "&quot;")
+    } else {
+        text
+    }
+}
+
+/// Serializes the node, leaving out boilerplate and blocks that are mostly links.
+fn write_clean(node: NodeRef<Node>, out: &mut String) {
+    match node.value() {
+        Node::Text(text) => out.push_str(&escape(text, false)),
+        Node::Element(element) => {
+            if is_boilerplate(element) {
+                return;
+            }
+            let Some(element_ref) = ElementRef::wrap(node) else {
+                return;
+            };
+            let name = element.name();
+            if matches!(name, "div" | "section" | "ul" | "ol" | "table")
+                && text_len(element_ref) < MIN_ARTICLE_CHARS
+                && link_density(element_ref) > 0.5
+            {
+                return;
+            }
+            out.push('<');
+            out.push_str(name);
+            for (key, value) in element.attrs() {
+                if KEPT_ATTRIBUTES.contains(&key) {
+                    out.push_str(&format!(" {}=\"{}\"", key, escape(value, true)));
+                }
+            }
+            out.push('>');
+            if VOID_TAGS.contains(&name) {
+                return;
+            }
+            for child in node.children() {
+                write_clean(child, out);
+            }
+            out.push_str(&format!("</{}>", name));
+        }
+        _ => {}
+    }
+}
+
+fn meta_content(document: &Html, selectors: &str) -> Option<String> {
+    let selector = Selector::parse(selectors).ok()?;
+    document
+        .select(&selector)
+        .filter_map(|element| element.value().attr("content"))
+        .map(|content| content.trim().to_string())
+        .find(|content| !content.is_empty())
+}
+
+fn first_text(document: &Html, selectors: &str) -> Option<String> {
+    let selector = Selector::parse(selectors).ok()?;
+    document
+        .select(&selector)
+        .map(|element| {
+            element
+                .text()
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .find(|text| !text.is_empty())
+}
+
+fn extract_title(document: &Html) -> Option<String> {
+    meta_content(
+        document,
+        r#"meta[property="og:title"], meta[name="twitter:title"]"#,
+    )
+    .or_else(|| first_text(document, "title"))
+    .or_else(|| first_text(document, "h1"))
+}
+
+fn extract_byline(document: &Html) -> Option<String> {
+    meta_content(
+        document,
+        r#"meta[name="author"], meta[property="article:author"]"#,
+    )
+    .filter(|author| !author.starts_with("http"))
+    .or_else(|| {
+        first_text(
+            document,
+            r#"[rel="author"], [itemprop="author"], .byline, .author"#,
+        )
+    })
+}
+
+fn extract_published(document: &Html) -> Option<String> {
+    meta_content(
+        document,
+        r#"meta[property="article:published_time"], meta[itemprop="datePublished"], meta[name="date"], meta[name="pubdate"]"#,
+    )
+    .or_else(|| {
+        let selector = Selector::parse("time[datetime]").ok()?;
+        document
+            .select(&selector)
+            .filter_map(|element| element.value().attr("datetime"))
+            .map(str::to_string)
+            .next()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARTICLE_HTML: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+  <title>Rust 2024 is here | Example News</title>
+  <meta property="og:title" content="Rust 2024 is here">
+  <meta name="author" content="Jane Doe">
+  <meta property="article:published_time" content="2025-02-20T10:00:00Z">
+</head>
+<body>
+  <div id="cookie-consent-content"><p>We use cookies to improve your experience, please accept all cookies.</p><button>Accept</button></div>
+  <nav><a href="/">Home</a><a href="/news">News</a></nav>
+  <div class="layout">
+    <div class="sidebar">
+      <p>Popular: <a href="/a">Ten crates you should know about</a>, <a href="/b">Why we rewrote it</a></p>
+    </div>
+    <article class="post">
+      <h1>Rust 2024 is here</h1>
+      <p>The Rust 2024 edition was released today, bringing async closures, new prelude items, and a number of smaller changes to the language.</p>
+      <p>Migrating is mostly automatic: running cargo fix with the edition flag rewrites the code that needs to change, and the compiler explains the rest.</p>
+      <div class="share-buttons"><a href="/share/x">Share on X</a> <a href="/share/mail">Email</a></div>
+      <p>As always, editions are opt-in, so existing crates keep compiling with the edition they declare in their manifest.</p>
+    </article>
+    <div class="ad-banner"><p>Buy our premium plan today, it has everything you need, and more, for less.</p></div>
+  </div>
+  <footer>Copyright Example News</footer>
+</body>
+</html>"#;
+
+    #[test]
+    fn test_extract_article() {
+        let article = extract_article(ARTICLE_HTML).unwrap();
+        assert_eq!(article.title.as_deref(), Some("Rust 2024 is here"));
+        assert_eq!(article.byline.as_deref(), Some("Jane Doe"));
+        assert_eq!(article.published.as_deref(), Some("2025-02-20T10:00:00Z"));
+
+        let text = article.text();
+        assert!(text.contains("async closures"));
+        assert!(text.contains("editions are opt-in"));
+        for boilerplate in [
+            "cookies",
+            "Popular",
+            "Share on X",
+            "premium plan",
+            "Copyright",
+        ] {
+            assert!(
+                !text.contains(boilerplate),
+                "{} was not removed",
+                boilerplate
+            );
+        }
+
+        let markdown = article.to_markdown();
+        assert!(markdown.starts_with(
+            "Title: Rust 2024 is here\nByline: Jane Doe\nPublished: 2025-02-20T10:00:00Z\n\n---\n\n"
+        ));
+        assert!(markdown.contains("# Rust 2024 is here"));
+    }
+
+    #[test]
+    fn test_extract_article_without_content() {
+        let html = "<html><body><nav><a href='/'>Home</a></nav><p>Short.</p></body></html>";
+        assert!(extract_article(html).is_none());
+    }
+
+    #[test]
+    fn test_link_density() {
+        let html = Html::parse_fragment("<div>Read <a href='/'>more news</a></div>");
+        let div = html
+            .select(&Selector::parse("div").unwrap())
+            .next()
+            .unwrap();
+        assert!((link_density(div) - 9.0 / 13.0).abs() < 1e-9);
+    }
+}
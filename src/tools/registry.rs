@@ -0,0 +1,559 @@
+//! This module contains the tool registry, a set of tools that can change while an agent runs.
+//!
+//! Tools are registered under unique names, optionally in a namespace such as `web`, which makes `web.search` out of
+//! a tool named `search`. Registering a name twice is an error instead of the first tool silently winning. Tools and
+//! whole namespaces can be disabled and enabled again at any time, and a [`ToolSelector`] can narrow down the tools
+//! offered to the model in each step, which keeps the prompt small when there are hundreds of them.
+//!
+//! The registry is a handle: clones share the same tools, so a registry given to an agent can still be changed.
+//!
+//! ```rust
+//! use smolagents_rs::tools::{DuckDuckGoSearchTool, KeywordToolSelector, ToolRegistry, VisitWebsiteTool};
+//!
+//! let registry = ToolRegistry::new().with_selector(KeywordToolSelector::new(10));
+//! registry.register_in("web", Box::new(DuckDuckGoSearchTool::new())).unwrap();
+//! registry.register_in("web", Box::new(VisitWebsiteTool::new())).unwrap();
+//! registry.disable("web.visit_website").unwrap();
+//! assert_eq!(registry.enabled_names(), vec!["web.duckduckgo_search"]);
+//! ```
+//!
+//! Some APIs, such as OpenAI's, only accept letters, digits, `_` and `-` in function names, and python code can not
+//! call a dotted name. Use [`ToolRegistry::with_separator`] to build names like `web_search` instead.
+
+use std::fmt;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, bail, Result};
+
+use super::base::intern;
+use super::output::ToolOutput;
+use super::retriever::Bm25Index;
+use super::tool_traits::{AnyTool, ToolGroup, ToolInfo};
+use crate::errors::AgentError;
+use crate::models::openai::FunctionCall;
+use crate::models::types::Message;
+
+/// The default separator between a namespace and a tool name.
+pub const DEFAULT_NAMESPACE_SEPARATOR: &str = ".";
+
+/// A tool registered under another name.
+#[derive(Debug)]
+struct NamespacedTool {
+    name: &'static str,
+    tool: Box<dyn AnyTool>,
+}
+
+impl AnyTool for NamespacedTool {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.tool.description()
+    }
+
+    fn forward_json(&self, json_args: serde_json::Value) -> Result<String, AgentError> {
+        self.tool.forward_json(json_args)
+    }
+
+    fn forward_json_output(&self, json_args: serde_json::Value) -> Result<ToolOutput, AgentError> {
+        self.tool.forward_json_output(json_args)
+    }
+
+    fn tool_info(&self) -> ToolInfo {
+        let mut info = self.tool.tool_info();
+        info.function.name = self.name;
+        info
+    }
+
+    fn clone_box(&self) -> Box<dyn AnyTool> {
+        Box::new(NamespacedTool {
+            name: self.name,
+            tool: self.tool.clone_box(),
+        })
+    }
+
+    fn reset(&self) {
+        self.tool.reset()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.tool.cacheable()
+    }
+}
+
+/// What a [`ToolSelector`] knows about the step it chooses tools for.
+#[derive(Debug, Clone, Copy)]
+pub struct SelectionContext<'a> {
+    pub task: &'a str,
+    pub step: usize,
+    /// The messages the model receives in this step.
+    pub messages: &'a [Message],
+}
+
+/// Chooses which of the enabled tools of a registry are offered to the model in a step.
+pub trait ToolSelector: Send + Sync {
+    /// Returns the names of the tools to offer.
+    fn select(&self, context: &SelectionContext, tools: &[ToolInfo]) -> Vec<String>;
+}
+
+impl<F: Fn(&SelectionContext, &[ToolInfo]) -> Vec<String> + Send + Sync> ToolSelector for F {
+    fn select(&self, context: &SelectionContext, tools: &[ToolInfo]) -> Vec<String> {
+        self(context, tools)
+    }
+}
+
+/// Offers the tools whose names and descriptions match the task and the latest message best, ranked with BM25.
+#[derive(Debug, Clone)]
+pub struct KeywordToolSelector {
+    max_tools: usize,
+    always: Vec<String>,
+}
+
+impl KeywordToolSelector {
+    pub fn new(max_tools: usize) -> Self {
+        KeywordToolSelector {
+            max_tools,
+            always: Vec::new(),
+        }
+    }
+
+    /// Always offers the tool, on top of the best matches.
+    pub fn with_always(mut self, name: &str) -> Self {
+        self.always.push(name.to_string());
+        self
+    }
+}
+
+impl ToolSelector for KeywordToolSelector {
+    fn select(&self, context: &SelectionContext, tools: &[ToolInfo]) -> Vec<String> {
+        let texts = tools
+            .iter()
+            .map(|tool| format!("{} {}", tool.function.name, tool.function.description))
+            .collect::<Vec<_>>();
+        let latest = context
+            .messages
+            .last()
+            .map(|message| message.content.as_str())
+            .unwrap_or_default();
+        let query = format!("{} {}", context.task, latest);
+        let mut matches = Bm25Index::new(&texts)
+            .search(&query, self.max_tools)
+            .into_iter()
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        // Without any match, the first tools are better than none.
+        if matches.is_empty() {
+            matches = (0..tools.len().min(self.max_tools)).collect();
+        }
+        let mut names = self.always.clone();
+        names.extend(
+            matches
+                .into_iter()
+                .map(|i| tools[i].function.name.to_string()),
+        );
+        names
+    }
+}
+
+struct Entry {
+    tool: Box<dyn AnyTool>,
+    namespace: Option<String>,
+    enabled: bool,
+}
+
+#[derive(Default)]
+struct RegistryState {
+    entries: Vec<Entry>,
+    separator: Option<String>,
+    selector: Option<Arc<dyn ToolSelector>>,
+}
+
+/// A set of uniquely named tools that can be added, removed, enabled and disabled at runtime. Clones share the tools.
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    state: Arc<RwLock<RegistryState>>,
+}
+
+impl fmt::Debug for ToolRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = self.state.read().unwrap();
+        let tools = state
+            .entries
+            .iter()
+            .map(|entry| (entry.tool.name(), entry.enabled))
+            .collect::<Vec<_>>();
+        f.debug_struct("ToolRegistry")
+            .field("tools", &tools)
+            .field("selector", &state.selector.is_some())
+            .finish()
+    }
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the separator between a namespace and a tool name, `.` by default.
+    pub fn with_separator(self, separator: &str) -> Self {
+        self.state.write().unwrap().separator = Some(separator.to_string());
+        self
+    }
+
+    /// Chooses the tools offered in each step. Without a selector, all the enabled tools are offered.
+    pub fn with_selector(self, selector: impl ToolSelector + 'static) -> Self {
+        self.state.write().unwrap().selector = Some(Arc::new(selector));
+        self
+    }
+
+    /// Registers a tool under its own name and returns the name.
+    pub fn register(&self, tool: Box<dyn AnyTool>) -> Result<&'static str> {
+        self.insert(None, tool)
+    }
+
+    /// Registers a tool in a namespace and returns its namespaced name, such as `web.search`.
+    pub fn register_in(&self, namespace: &str, tool: Box<dyn AnyTool>) -> Result<&'static str> {
+        if namespace.is_empty() {
+            bail!("The namespace of {} is empty", tool.name());
+        }
+        self.insert(Some(namespace), tool)
+    }
+
+    fn insert(&self, namespace: Option<&str>, tool: Box<dyn AnyTool>) -> Result<&'static str> {
+        let mut state = self.state.write().unwrap();
+        let separator = state
+            .separator
+            .clone()
+            .unwrap_or_else(|| DEFAULT_NAMESPACE_SEPARATOR.to_string());
+        let name = match namespace {
+            Some(namespace) => intern(format!("{}{}{}", namespace, separator, tool.name())),
+            None => tool.name(),
+        };
+        if state.entries.iter().any(|entry| entry.tool.name() == name) {
+            bail!("A tool named {} is already registered", name);
+        }
+        let tool: Box<dyn AnyTool> = match namespace {
+            Some(_) => Box::new(NamespacedTool { name, tool }),
+            None => tool,
+        };
+        state.entries.push(Entry {
+            tool,
+            namespace: namespace.map(str::to_string),
+            enabled: true,
+        });
+        Ok(name)
+    }
+
+    /// Removes a tool and returns it.
+    pub fn unregister(&self, name: &str) -> Option<Box<dyn AnyTool>> {
+        let mut state = self.state.write().unwrap();
+        let index = state
+            .entries
+            .iter()
+            .position(|entry| entry.tool.name() == name)?;
+        Some(state.entries.remove(index).tool)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.state
+            .read()
+            .unwrap()
+            .entries
+            .iter()
+            .any(|entry| entry.tool.name() == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The names of all the registered tools, in registration order.
+    pub fn names(&self) -> Vec<&'static str> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .map(|entry| entry.tool.name())
+            .collect()
+    }
+
+    /// The names of the enabled tools, in registration order.
+    pub fn enabled_names(&self) -> Vec<&'static str> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| entry.tool.name())
+            .collect()
+    }
+
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .any(|entry| entry.enabled && entry.tool.name() == name)
+    }
+
+    pub fn set_enabled(&self, name: &str, enabled: bool) -> Result<()> {
+        let mut state = self.state.write().unwrap();
+        let entry = state
+            .entries
+            .iter_mut()
+            .find(|entry| entry.tool.name() == name)
+            .ok_or_else(|| anyhow!("No tool named {} is registered", name))?;
+        entry.enabled = enabled;
+        Ok(())
+    }
+
+    pub fn enable(&self, name: &str) -> Result<()> {
+        self.set_enabled(name, true)
+    }
+
+    pub fn disable(&self, name: &str) -> Result<()> {
+        self.set_enabled(name, false)
+    }
+
+    /// Enables or disables all the tools of a namespace and returns how many there are.
+    pub fn set_namespace_enabled(&self, namespace: &str, enabled: bool) -> usize {
+        let mut state = self.state.write().unwrap();
+        let mut count = 0;
+        for entry in &mut state.entries {
+            if entry.namespace.as_deref() == Some(namespace) {
+                entry.enabled = enabled;
+                count += 1;
+            }
+        }
+        count
+    }
+
+    /// Copies of the enabled tools.
+    pub fn enabled_tools(&self) -> Vec<Box<dyn AnyTool>> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| entry.tool.clone_box())
+            .collect()
+    }
+
+    /// The enabled tools to offer in a step, as chosen by the selector.
+    pub fn select(&self, context: &SelectionContext) -> Vec<ToolInfo> {
+        let tools = self.tool_info();
+        let Some(selector) = self.state.read().unwrap().selector.clone() else {
+            return tools;
+        };
+        let selected = selector.select(context, &tools);
+        tools
+            .into_iter()
+            .filter(|tool| selected.iter().any(|name| name == tool.function.name))
+            .collect()
+    }
+
+    /// Clears the run state of every tool, see [`AnyTool::reset`].
+    pub fn reset(&self) {
+        for entry in &self.state.read().unwrap().entries {
+            entry.tool.reset();
+        }
+    }
+}
+
+impl ToolGroup for ToolRegistry {
+    fn call(&self, arguments: &FunctionCall) -> Result<String, AgentError> {
+        self.call_output(arguments).map(|output| output.render())
+    }
+
+    /// Calls an enabled tool. Disabled tools are reported to the model, so it can use another one.
+    fn call_output(&self, arguments: &FunctionCall) -> Result<ToolOutput, AgentError> {
+        let tool = {
+            let state = self.state.read().unwrap();
+            let entry = state
+                .entries
+                .iter()
+                .find(|entry| entry.tool.name() == arguments.name)
+                .ok_or_else(|| AgentError::Execution("Tool not found".to_string()))?;
+            if !entry.enabled {
+                return Err(AgentError::Execution(format!(
+                    "The tool {} is disabled. Use another tool.",
+                    arguments.name
+                )));
+            }
+            // The call runs without the lock, so the tool may change the registry.
+            entry.tool.clone_box()
+        };
+        tool.forward_json_output(arguments.arguments.clone())
+    }
+
+    fn tool_info(&self) -> Vec<ToolInfo> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| entry.tool.tool_info())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{BaseTool, Tool};
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Deserialize, JsonSchema)]
+    struct NamedToolParams {
+        text: String,
+    }
+
+    /// Returns its name and the text.
+    #[derive(Debug, Clone)]
+    struct NamedTool {
+        tool: BaseTool,
+    }
+
+    fn named(name: &'static str, description: &'static str) -> Box<dyn AnyTool> {
+        Box::new(NamedTool {
+            tool: BaseTool { name, description },
+        })
+    }
+
+    impl Tool for NamedTool {
+        type Params = NamedToolParams;
+        fn name(&self) -> &'static str {
+            self.tool.name
+        }
+        fn description(&self) -> &'static str {
+            self.tool.description
+        }
+        fn forward(&self, arguments: NamedToolParams) -> anyhow::Result<String> {
+            Ok(format!("{}: {}", self.tool.name, arguments.text))
+        }
+    }
+
+    fn call(name: &str) -> FunctionCall {
+        FunctionCall {
+            name: name.to_string(),
+            arguments: json!({"text": "hi"}),
+        }
+    }
+
+    #[test]
+    fn test_namespaces_and_duplicates() {
+        let registry = ToolRegistry::new();
+        assert_eq!(
+            registry
+                .register_in("web", named("search", "Searches the web."))
+                .unwrap(),
+            "web.search"
+        );
+        registry
+            .register_in("docs", named("search", "Searches the docs."))
+            .unwrap();
+        registry.register(named("search", "Searches.")).unwrap();
+        let error = registry
+            .register_in("web", named("search", "Searches again."))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "A tool named web.search is already registered"
+        );
+        assert!(registry.register_in("", named("x", "X.")).is_err());
+
+        assert_eq!(
+            registry.names(),
+            vec!["web.search", "docs.search", "search"]
+        );
+        let info = registry.tool_info();
+        assert_eq!(info[0].function.name, "web.search");
+        assert_eq!(info[0].function.description, "Searches the web.");
+        assert_eq!(registry.call(&call("docs.search")).unwrap(), "search: hi");
+
+        let registry = ToolRegistry::new().with_separator("_");
+        assert_eq!(
+            registry
+                .register_in("web", named("search", "Searches."))
+                .unwrap(),
+            "web_search"
+        );
+        assert!(registry.unregister("web_search").is_some());
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn test_enable_and_disable() {
+        let registry = ToolRegistry::new();
+        registry.register_in("web", named("search", "S.")).unwrap();
+        registry.register_in("web", named("visit", "V.")).unwrap();
+        registry.register(named("echo", "E.")).unwrap();
+
+        registry.disable("echo").unwrap();
+        assert!(!registry.is_enabled("echo"));
+        assert!(registry.disable("missing").is_err());
+        assert_eq!(
+            registry.call(&call("echo")).unwrap_err().message(),
+            "The tool echo is disabled. Use another tool."
+        );
+        assert_eq!(registry.set_namespace_enabled("web", false), 2);
+        assert!(registry.enabled_names().is_empty());
+
+        // Clones share the tools.
+        let clone = registry.clone();
+        clone.enable("echo").unwrap();
+        assert_eq!(registry.enabled_names(), vec!["echo"]);
+        assert_eq!(registry.call(&call("echo")).unwrap(), "echo: hi");
+        assert_eq!(registry.enabled_tools().len(), 1);
+    }
+
+    #[test]
+    fn test_selectors() {
+        let registry =
+            ToolRegistry::new().with_selector(KeywordToolSelector::new(2).with_always("notes"));
+        registry
+            .register(named("weather", "Gets the weather forecast for a city."))
+            .unwrap();
+        registry
+            .register(named("stocks", "Gets the stock price of a company."))
+            .unwrap();
+        registry
+            .register(named("translate", "Translates text into another language."))
+            .unwrap();
+        registry
+            .register(named("notes", "Reads my notes."))
+            .unwrap();
+        let messages = vec![Message {
+            role: crate::models::types::MessageRole::User,
+            content: "Also translate it to French.".to_string(),
+        }];
+        let context = SelectionContext {
+            task: "What is the weather forecast in Paris?",
+            step: 0,
+            messages: &messages,
+        };
+        let names = registry
+            .select(&context)
+            .iter()
+            .map(|tool| tool.function.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["weather", "translate", "notes"]);
+
+        let registry = registry.with_selector(|context: &SelectionContext, tools: &[ToolInfo]| {
+            tools
+                .iter()
+                .skip(context.step)
+                .map(|tool| tool.function.name.to_string())
+                .collect()
+        });
+        let context = SelectionContext { step: 3, ..context };
+        assert_eq!(registry.select(&context)[0].function.name, "notes");
+    }
+}
@@ -0,0 +1,525 @@
+//! This module contains the retriever tool. The model uses this tool to find passages in a collection of documents.
+//!
+//! The text and markdown files of a directory are split into chunks of about [`DEFAULT_CHUNK_CHARS`] characters and
+//! indexed with BM25, so no model is needed to search them. An [`EmbeddingModel`] can be added to also rank the
+//! chunks by the similarity of their embeddings; both rankings are then merged with reciprocal rank fusion.
+//!
+//! ```rust,no_run
+//! use smolagents_rs::models::ollama::OllamaModelBuilder;
+//! use smolagents_rs::tools::RetrieverTool;
+//!
+//! let embeddings = OllamaModelBuilder::new().model_id("nomic-embed-text").build();
+//! let retriever = RetrieverTool::from_directory("./docs")
+//!     .unwrap()
+//!     .with_embedding_model(embeddings)
+//!     .unwrap();
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::tool_traits::Tool;
+use crate::models::model_traits::EmbeddingModel;
+use crate::models::vector_store::VectorStore;
+use anyhow::{anyhow, Result};
+
+/// The default size of a chunk, in characters.
+pub const DEFAULT_CHUNK_CHARS: usize = 1000;
+/// The default number of passages returned by a query.
+pub const DEFAULT_TOP_K: usize = 5;
+/// The extensions of the files indexed by [`load_directory`].
+pub const INDEXED_EXTENSIONS: [&str; 5] = ["txt", "md", "markdown", "rst", "text"];
+/// The constant of reciprocal rank fusion, which damps the weight of the first ranks.
+const RRF_K: f32 = 60.0;
+
+/// A passage of a document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    /// The path of the document, relative to the indexed directory.
+    pub source: String,
+    pub text: String,
+}
+
+/// Splits a text into lowercase alphanumeric terms.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// A BM25 index of a list of texts.
+#[derive(Debug, Clone, Default)]
+pub struct Bm25Index {
+    /// The term frequencies of each text.
+    term_frequencies: Vec<HashMap<String, usize>>,
+    /// The number of terms of each text.
+    lengths: Vec<usize>,
+    /// The number of texts that contain each term.
+    document_frequencies: HashMap<String, usize>,
+    average_length: f32,
+}
+
+impl Bm25Index {
+    const K1: f32 = 1.5;
+    const B: f32 = 0.75;
+
+    pub fn new<S: AsRef<str>>(texts: &[S]) -> Self {
+        let mut index = Bm25Index::default();
+        for text in texts {
+            let terms = tokenize(text.as_ref());
+            let mut frequencies = HashMap::new();
+            for term in &terms {
+                *frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            for term in frequencies.keys() {
+                *index.document_frequencies.entry(term.clone()).or_insert(0) += 1;
+            }
+            index.lengths.push(terms.len());
+            index.term_frequencies.push(frequencies);
+        }
+        let total = index.lengths.iter().sum::<usize>();
+        index.average_length = total as f32 / index.lengths.len().max(1) as f32;
+        index
+    }
+
+    /// The indices and scores of the `k` texts that match the query best, best first. Texts without any of the query
+    /// terms are left out.
+    pub fn search(&self, query: &str, k: usize) -> Vec<(usize, f32)> {
+        let count = self.lengths.len() as f32;
+        let mut terms = tokenize(query);
+        terms.sort();
+        terms.dedup();
+        let mut scores = self
+            .term_frequencies
+            .iter()
+            .zip(&self.lengths)
+            .enumerate()
+            .filter_map(|(i, (frequencies, &length))| {
+                let score = terms
+                    .iter()
+                    .filter_map(|term| {
+                        let frequency = *frequencies.get(term)? as f32;
+                        let document_frequency = self.document_frequencies[term] as f32;
+                        let idf = ((count - document_frequency + 0.5) / (document_frequency + 0.5)
+                            + 1.0)
+                            .ln();
+                        let norm =
+                            1.0 - Self::B + Self::B * length as f32 / self.average_length.max(1.0);
+                        Some(idf * frequency * (Self::K1 + 1.0) / (frequency + Self::K1 * norm))
+                    })
+                    .sum::<f32>();
+                (score > 0.0).then_some((i, score))
+            })
+            .collect::<Vec<_>>();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(k);
+        scores
+    }
+}
+
+/// Splits a text into chunks of at most `max_chars` characters.
+///
+/// Paragraphs are kept together when they fit, and longer paragraphs are split between words.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut push = |current: &mut String, piece: &str| {
+        let separator = if current.is_empty() { 0 } else { 2 };
+        if !current.is_empty()
+            && current.chars().count() + separator + piece.chars().count() > max_chars
+        {
+            chunks.push(std::mem::take(current));
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(piece);
+    };
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.chars().count() <= max_chars {
+            push(&mut current, paragraph);
+            continue;
+        }
+        let mut piece = String::new();
+        for word in paragraph.split_whitespace() {
+            if !piece.is_empty() && piece.chars().count() + 1 + word.chars().count() > max_chars {
+                push(&mut current, &std::mem::take(&mut piece));
+            }
+            if !piece.is_empty() {
+                piece.push(' ');
+            }
+            piece.push_str(word);
+        }
+        if !piece.is_empty() {
+            push(&mut current, &piece);
+        }
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Reads the text and markdown files of a directory and its subdirectories, skipping hidden ones, and splits them
+/// into chunks of at most `max_chars` characters. Symbolic links to directories are not followed, so links looping
+/// back to a parent do not recurse forever, and files that are not valid UTF-8 are decoded lossily with a warning.
+pub fn load_directory(directory: impl AsRef<Path>, max_chars: usize) -> Result<Vec<Chunk>> {
+    fn visit(
+        root: &Path,
+        directory: &Path,
+        max_chars: usize,
+        chunks: &mut Vec<Chunk>,
+    ) -> Result<()> {
+        let mut entries = fs::read_dir(directory)
+            .map_err(|e| anyhow!("Failed to read {}: {}", directory.display(), e))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                if !entry.file_type()?.is_symlink() {
+                    visit(root, &path, max_chars, chunks)?;
+                }
+                continue;
+            }
+            let indexed = path
+                .extension()
+                .and_then(|extension| extension.to_str())
+                .is_some_and(|extension| {
+                    INDEXED_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+                });
+            if !indexed {
+                continue;
+            }
+            let bytes =
+                fs::read(&path).map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+            let text = match String::from_utf8(bytes) {
+                Ok(text) => text,
+                Err(e) => {
+                    log::warn!(
+                        "{} is not valid UTF-8, invalid bytes are replaced",
+                        path.display()
+                    );
+                    String::from_utf8_lossy(e.as_bytes()).into_owned()
+                }
+            };
+            let source = path
+                .strip_prefix(root)
+                .unwrap_or(&path)
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            chunks.extend(chunk_text(&text, max_chars).into_iter().map(|text| Chunk {
+                source: source.clone(),
+                text,
+            }));
+        }
+        Ok(())
+    }
+
+    let directory = directory.as_ref();
+    let mut chunks = Vec::new();
+    visit(directory, directory, max_chars, &mut chunks)?;
+    Ok(chunks)
+}
+
+#[derive(Debug, Clone)]
+struct EmbeddingIndex {
+    model: Arc<dyn EmbeddingModel>,
+    /// The indices of the chunks, stored with their embeddings.
+    store: Arc<VectorStore<usize>>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct RetrieverTool {
+    pub tool: BaseTool,
+    /// The number of passages returned when the model does not ask for a number.
+    pub top_k: usize,
+    #[serde(skip)]
+    chunks: Arc<Vec<Chunk>>,
+    #[serde(skip)]
+    bm25: Arc<Bm25Index>,
+    #[serde(skip)]
+    embeddings: Option<EmbeddingIndex>,
+}
+
+impl RetrieverTool {
+    pub fn new(chunks: Vec<Chunk>) -> Self {
+        let bm25 = Bm25Index::new(&chunks.iter().map(|chunk| &chunk.text).collect::<Vec<_>>());
+        RetrieverTool {
+            tool: BaseTool {
+                name: "retriever",
+                description: "Searches the indexed documents and returns the passages most relevant to the query, with the files they come from. Use keywords or a short affirmative statement rather than a question.",
+            },
+            top_k: DEFAULT_TOP_K,
+            chunks: Arc::new(chunks),
+            bm25: Arc::new(bm25),
+            embeddings: None,
+        }
+    }
+
+    /// Indexes the text and markdown files of a directory, in chunks of [`DEFAULT_CHUNK_CHARS`] characters.
+    pub fn from_directory(directory: impl AsRef<Path>) -> Result<Self> {
+        Ok(Self::new(load_directory(directory, DEFAULT_CHUNK_CHARS)?))
+    }
+
+    pub fn with_top_k(mut self, top_k: usize) -> Self {
+        self.top_k = top_k;
+        self
+    }
+
+    /// Embeds every chunk with the model, so that queries are also ranked by embedding similarity.
+    pub fn with_embedding_model(mut self, model: impl EmbeddingModel + 'static) -> Result<Self> {
+        let texts = self
+            .chunks
+            .iter()
+            .map(|chunk| chunk.text.clone())
+            .collect::<Vec<_>>();
+        let mut store = VectorStore::new();
+        store.add(&model, &texts, (0..texts.len()).collect())?;
+        self.embeddings = Some(EmbeddingIndex {
+            model: Arc::new(model),
+            store: Arc::new(store),
+        });
+        Ok(self)
+    }
+
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// The `k` chunks most relevant to the query, best first.
+    pub fn retrieve(&self, query: &str, k: usize) -> Result<Vec<&Chunk>> {
+        let Some(embeddings) = &self.embeddings else {
+            return Ok(self
+                .bm25
+                .search(query, k)
+                .into_iter()
+                .map(|(i, _)| &self.chunks[i])
+                .collect());
+        };
+
+        let by_similarity = embeddings
+            .store
+            .search_text(embeddings.model.as_ref(), query, self.chunks.len())?
+            .into_iter()
+            .map(|(&i, score)| (i, score))
+            .collect::<Vec<_>>();
+
+        let mut fused = vec![0.0; self.chunks.len()];
+        let bm25 = self.bm25.search(query, self.chunks.len());
+        for ranking in [bm25, by_similarity] {
+            for (rank, (i, _)) in ranking.into_iter().enumerate() {
+                fused[i] += 1.0 / (RRF_K + rank as f32 + 1.0);
+            }
+        }
+        let mut ranked = (0..self.chunks.len()).collect::<Vec<_>>();
+        ranked.sort_by(|&a, &b| fused[b].total_cmp(&fused[a]).then(a.cmp(&b)));
+        Ok(ranked
+            .into_iter()
+            .take(k)
+            .map(|i| &self.chunks[i])
+            .collect())
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "RetrieverToolParams")]
+pub struct RetrieverToolParams {
+    #[schemars(description = "The query to search the documents for")]
+    query: String,
+    #[schemars(description = "The number of passages to return")]
+    top_k: Option<usize>,
+}
+
+impl Tool for RetrieverTool {
+    type Params = RetrieverToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn cacheable(&self) -> bool {
+        true
+    }
+    fn forward(&self, arguments: RetrieverToolParams) -> Result<String> {
+        let top_k = arguments.top_k.unwrap_or(self.top_k);
+        let passages = self.retrieve(&arguments.query, top_k)?;
+        if passages.is_empty() {
+            return Ok(format!(
+                "No passages match '{}'. Try other keywords.",
+                arguments.query
+            ));
+        }
+        Ok(passages
+            .iter()
+            .enumerate()
+            .map(|(i, chunk)| {
+                format!(
+                    "===== Passage {} from {} =====\n{}",
+                    i + 1,
+                    chunk.source,
+                    chunk.text
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::AgentError;
+
+    /// Embeds a text as the number of times it mentions cats and dogs.
+    #[derive(Debug)]
+    struct WordCountEmbeddings;
+
+    impl EmbeddingModel for WordCountEmbeddings {
+        fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, AgentError> {
+            Ok(texts
+                .iter()
+                .map(|text| {
+                    let terms = tokenize(text);
+                    [&["cat", "kitten"][..], &["dog", "puppy"][..]]
+                        .iter()
+                        .map(|words| {
+                            let count = terms.iter().filter(|term| words.contains(&term.as_str()));
+                            count.count() as f32
+                        })
+                        .collect()
+                })
+                .collect())
+        }
+    }
+
+    fn documents() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("guides")).unwrap();
+        fs::create_dir_all(dir.path().join(".git")).unwrap();
+        fs::write(
+            dir.path().join("animals.md"),
+            "# Cats\n\nA cat sleeps most of the day.\n\n# Dogs\n\nA dog needs a walk every day.",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("guides/rust.txt"),
+            "Rust has no garbage collector.",
+        )
+        .unwrap();
+        fs::write(dir.path().join("image.png"), [0x89, b'P', b'N', b'G']).unwrap();
+        fs::write(dir.path().join(".git/HEAD"), "ref: refs/heads/main").unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_chunk_text() {
+        assert_eq!(
+            chunk_text("One.\n\nTwo.\n\n\n\nThree is longer.", 16),
+            vec!["One.\n\nTwo.", "Three is longer."]
+        );
+        assert_eq!(
+            chunk_text("alpha beta gamma delta", 11),
+            vec!["alpha beta", "gamma delta"]
+        );
+        assert!(chunk_text("  \n\n ", 10).is_empty());
+    }
+
+    #[test]
+    fn test_bm25_index() {
+        let index = Bm25Index::new(&[
+            "the cat sat on the mat",
+            "the dog chased the cat",
+            "the dog barked",
+        ]);
+        let results = index.search("Dog barked", 10);
+        assert_eq!(
+            results.iter().map(|(i, _)| *i).collect::<Vec<_>>(),
+            vec![2, 1]
+        );
+        assert!(results[0].1 > results[1].1);
+        assert!(index.search("giraffe", 10).is_empty());
+        assert_eq!(index.search("the", 1).len(), 1);
+    }
+
+    #[test]
+    fn test_load_directory_skips_symlinked_directories_and_decodes_invalid_utf8() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("latin1.txt"), b"caf\xe9 au lait").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(dir.path(), dir.path().join("loop")).unwrap();
+        let chunks = load_directory(dir.path(), 100).unwrap();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].source, "latin1.txt");
+        assert_eq!(chunks[0].text, "caf\u{fffd} au lait");
+    }
+
+    #[test]
+    fn test_retriever_tool() {
+        let dir = documents();
+        let chunks = load_directory(dir.path(), 40).unwrap();
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| chunk.source.as_str())
+                .collect::<Vec<_>>(),
+            vec!["animals.md", "animals.md", "guides/rust.txt"]
+        );
+
+        let tool = RetrieverTool::new(chunks).with_top_k(1);
+        let search = |query: &str, top_k: Option<usize>| {
+            Tool::forward(
+                &tool,
+                RetrieverToolParams {
+                    query: query.to_string(),
+                    top_k,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            search("how does rust collect garbage?", None),
+            "===== Passage 1 from guides/rust.txt =====\nRust has no garbage collector."
+        );
+        assert_eq!(
+            search("walk the dog", Some(3)),
+            "===== Passage 1 from animals.md =====\n# Dogs\n\nA dog needs a walk every day.\n\n===== Passage 2 from animals.md =====\n# Cats\n\nA cat sleeps most of the day."
+        );
+        assert_eq!(
+            search("giraffe", None),
+            "No passages match 'giraffe'. Try other keywords."
+        );
+        assert!(RetrieverTool::from_directory(dir.path().join("missing")).is_err());
+    }
+
+    #[test]
+    fn test_retriever_tool_with_embeddings() {
+        let dir = documents();
+        let tool = RetrieverTool::from_directory(dir.path())
+            .unwrap()
+            .with_embedding_model(WordCountEmbeddings)
+            .unwrap();
+        assert_eq!(tool.chunks().len(), 2);
+        // No document mentions kittens, but their embeddings are close to the one of cats.
+        assert!(tool.bm25.search("kitten", 2).is_empty());
+        let passages = tool.retrieve("kitten", 2).unwrap();
+        assert_eq!(passages[0].source, "animals.md");
+        assert_eq!(passages[1].source, "guides/rust.txt");
+        let passages = tool.retrieve("rust garbage", 1).unwrap();
+        assert_eq!(passages[0].source, "guides/rust.txt");
+    }
+}
@@ -0,0 +1,172 @@
+//! This module contains the SearxNG search tool. SearxNG is a self-hosted metasearch engine.
+
+use serde_json::Value;
+
+use super::web_client::WebClient;
+use super::web_search::{
+    fetch, SafeSearch, SearchProvider, SearchQuery, SearchResult, TimeRange, WebSearchTool,
+};
+use anyhow::Result;
+
+/// Searches a SearxNG instance through its JSON API.
+///
+/// The instance must have the `json` format enabled. SearxNG cannot filter by year,
+/// so the year is added to the query instead.
+#[derive(Debug, Clone)]
+pub struct SearxNgProvider {
+    pub base_url: String,
+}
+
+impl SearxNgProvider {
+    /// Creates the provider for the instance at `base_url`, e.g. `http://localhost:8888`.
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+        }
+    }
+}
+
+impl SearchProvider for SearxNgProvider {
+    fn name(&self) -> &'static str {
+        "searxng_search"
+    }
+    fn description(&self) -> &'static str {
+        "Performs a web search for your query with SearxNG then returns a string of the top search results."
+    }
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>> {
+        let request = client
+            .get(format!("{}/search", self.base_url))
+            .query(&searxng_params(query));
+        parse_searxng_results(&fetch(client, request)?)
+    }
+}
+
+/// Maps the query to the parameters of the SearxNG search API.
+pub fn searxng_params(query: &SearchQuery) -> Vec<(&'static str, String)> {
+    let mut params = vec![
+        ("q", query.query_with_year()),
+        ("format", "json".to_string()),
+    ];
+    if let Some(page) = query.page {
+        params.push(("pageno", page.to_string()));
+    }
+    let language = match (&query.language, &query.region) {
+        (Some(language), Some(region)) => Some(format!(
+            "{}-{}",
+            language.to_lowercase(),
+            region.to_uppercase()
+        )),
+        (Some(language), None) => Some(language.to_lowercase()),
+        (None, _) => None,
+    };
+    if let Some(language) = language {
+        params.push(("language", language));
+    }
+    if let Some(safe_search) = query.safe_search {
+        let level = match safe_search {
+            SafeSearch::Off => "0",
+            SafeSearch::Moderate => "1",
+            SafeSearch::Strict => "2",
+        };
+        params.push(("safesearch", level.to_string()));
+    }
+    if let Some(time_range) = query.time_range {
+        let range = match time_range {
+            TimeRange::Day => "day",
+            TimeRange::Week => "week",
+            TimeRange::Month => "month",
+            TimeRange::Year => "year",
+        };
+        params.push(("time_range", range.to_string()));
+    }
+    params
+}
+
+/// Parses a SearxNG JSON search response.
+pub fn parse_searxng_results(body: &str) -> Result<Vec<SearchResult>> {
+    let response: Value = serde_json::from_str(body)?;
+    let Some(results) = response.get("results").and_then(|r| r.as_array()) else {
+        return Ok(Vec::new());
+    };
+    Ok(results
+        .iter()
+        .filter_map(|result| {
+            let text = |key: &str| {
+                result
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .filter(|v| !v.is_empty())
+                    .map(str::to_string)
+            };
+            Some(SearchResult {
+                title: text("title")?,
+                url: text("url")?,
+                snippet: text("content").unwrap_or_default(),
+                date: text("publishedDate"),
+                source: text("engine"),
+            })
+        })
+        .collect())
+}
+
+pub type SearxNgSearchTool = WebSearchTool<SearxNgProvider>;
+
+impl WebSearchTool<SearxNgProvider> {
+    pub fn new(base_url: &str) -> Self {
+        Self::with_provider(SearxNgProvider::new(base_url))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+
+    const RESULTS_JSON: &str = r#"{
+        "query": "capital of France",
+        "number_of_results": 0,
+        "results": [
+            {
+                "url": "https://en.wikipedia.org/wiki/Paris",
+                "title": "Paris - Wikipedia",
+                "content": "Paris is the capital of France.",
+                "engine": "wikipedia",
+                "publishedDate": null
+            },
+            {"url": "https://example.com", "title": "", "content": "No title"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_searxng_results() {
+        let results = parse_searxng_results(RESULTS_JSON).unwrap();
+        assert_eq!(
+            results,
+            vec![SearchResult {
+                title: "Paris - Wikipedia".to_string(),
+                snippet: "Paris is the capital of France.".to_string(),
+                url: "https://en.wikipedia.org/wiki/Paris".to_string(),
+                date: None,
+                source: Some("wikipedia".to_string()),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_searxng_provider_sends_query() {
+        let server = MockServer::start(vec![MockServer::response(200, &[], RESULTS_JSON)]);
+        let tool = SearxNgSearchTool::new(&format!("{}/", server.url()));
+        let query = SearchQuery::new("capital of France")
+            .with_page(2)
+            .with_language("fr")
+            .with_region("fr")
+            .with_safe_search(SafeSearch::Moderate)
+            .with_time_range(TimeRange::Year);
+        let results = tool.search(&query).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            server.requests()[0].path,
+            "/search?q=capital+of+France&format=json&pageno=2&language=fr-FR&safesearch=1&time_range=year"
+        );
+    }
+}
@@ -0,0 +1,565 @@
+//! This module contains the shell tool. The model uses this tool to run commands such as `cargo test` or
+//! `git status`.
+//!
+//! Commands are split into arguments and run directly, without a shell, so pipes, redirections and variable
+//! expansion are not available. On unix, each command runs in its own process group, which is killed as a whole
+//! when the command times out. Background processes the command leaves behind are killed when it exits.
+//!
+//! The allow list is the way to restrict what the model can run. The deny list only guards against mistakes: it
+//! catches denied commands behind wrappers such as `env` or `sudo` and options such as `git -C . push`, but it is not
+//! a security boundary, since an allowed program can still run a denied command, e.g. `sh -c`, `xargs` or a script.
+
+use std::collections::VecDeque;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::tool_traits::Tool;
+use anyhow::{anyhow, Result};
+
+/// The default timeout of a command.
+pub const DEFAULT_SHELL_TIMEOUT: Duration = Duration::from_secs(60);
+/// The default number of characters kept from each of stdout and stderr.
+pub const DEFAULT_MAX_OUTPUT_CHARS: usize = 10_000;
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ShellTool {
+    pub tool: BaseTool,
+    /// The directory commands run in. Defaults to the current directory.
+    pub working_directory: Option<PathBuf>,
+    /// Environment variables set for every command.
+    pub env: Vec<(String, String)>,
+    /// Whether commands start from an empty environment instead of inheriting ours.
+    pub clear_env: bool,
+    /// If not empty, only commands starting with one of these are run, e.g. `cargo` or `git status`.
+    pub allowed_commands: Vec<String>,
+    /// Commands containing one of these are never run, e.g. `git push`. The deny list wins over the allow list, but
+    /// it is not a security boundary, see the module documentation.
+    pub denied_commands: Vec<String>,
+    pub timeout: Duration,
+    /// The number of characters kept from each of stdout and stderr. The middle of longer output is cut.
+    pub max_output_chars: usize,
+}
+
+impl Default for ShellTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellTool {
+    pub fn new() -> Self {
+        ShellTool {
+            tool: BaseTool {
+                name: "shell",
+                description: "Runs a command, such as 'cargo test' or 'git status', and returns its exit code, stdout and stderr. The command is run without a shell, so pipes, redirections and variables are not supported.",
+            },
+            working_directory: None,
+            env: Vec::new(),
+            clear_env: false,
+            allowed_commands: Vec::new(),
+            denied_commands: Vec::new(),
+            timeout: DEFAULT_SHELL_TIMEOUT,
+            max_output_chars: DEFAULT_MAX_OUTPUT_CHARS,
+        }
+    }
+
+    pub fn with_working_directory(mut self, working_directory: impl Into<PathBuf>) -> Self {
+        self.working_directory = Some(working_directory.into());
+        self
+    }
+
+    pub fn with_env(mut self, key: &str, value: &str) -> Self {
+        self.env.push((key.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn with_clear_env(mut self, clear_env: bool) -> Self {
+        self.clear_env = clear_env;
+        self
+    }
+
+    pub fn with_allowed_commands(mut self, commands: &[&str]) -> Self {
+        self.allowed_commands = commands.iter().map(|command| command.to_string()).collect();
+        self
+    }
+
+    pub fn with_denied_commands(mut self, commands: &[&str]) -> Self {
+        self.denied_commands = commands.iter().map(|command| command.to_string()).collect();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_max_output_chars(mut self, max_output_chars: usize) -> Self {
+        self.max_output_chars = max_output_chars;
+        self
+    }
+
+    /// Checks the arguments against the command allow and deny lists.
+    pub fn check_command(&self, args: &[String]) -> Result<()> {
+        let command = args.join(" ");
+        if self
+            .denied_commands
+            .iter()
+            .any(|denied| command_denied(args, denied))
+        {
+            return Err(anyhow!("The command '{}' is not allowed", command));
+        }
+        if !self.allowed_commands.is_empty()
+            && !self
+                .allowed_commands
+                .iter()
+                .any(|allowed| command_matches(args, allowed))
+        {
+            return Err(anyhow!(
+                "The command '{}' is not allowed. Allowed commands: {}",
+                command,
+                self.allowed_commands.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs the command and returns its exit status, or `None` if it timed out, with its stdout and stderr cut to
+    /// `max_output_chars` characters each.
+    pub fn run(&self, command: &str) -> Result<(Option<ExitStatus>, String, String)> {
+        let args = split_command(command)?;
+        self.check_command(&args)?;
+
+        let mut process = Command::new(&args[0]);
+        process
+            .args(&args[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        if self.clear_env {
+            process.env_clear();
+        }
+        process.envs(self.env.iter().map(|(key, value)| (key, value)));
+        if let Some(directory) = &self.working_directory {
+            process.current_dir(directory);
+        }
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut process, 0);
+
+        let mut child = process
+            .spawn()
+            .map_err(|e| anyhow!("Failed to run '{}': {}", args[0], e))?;
+        // Read both pipes while waiting, so a command with a lot of output does not block.
+        let max_chars = self.max_output_chars;
+        let read = |pipe: Option<Box<dyn Read + Send>>| {
+            thread::spawn(move || match pipe {
+                Some(pipe) => read_output(pipe, max_chars),
+                None => String::new(),
+            })
+        };
+        let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+
+        let started = Instant::now();
+        let exited = loop {
+            if has_exited(&mut child)? {
+                break true;
+            }
+            if started.elapsed() >= self.timeout {
+                break false;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        // Kill what is left of the process group before the command is reaped: the command itself if it timed out,
+        // and otherwise any background process it started, which would keep the output pipes open.
+        #[cfg(unix)]
+        // SAFETY: `kill` has no memory safety requirements. The command has not been reaped yet, so its pid, which
+        // is also the id of its process group, can not have been reused by another process.
+        unsafe {
+            libc::kill(-(child.id() as i32), libc::SIGKILL);
+        }
+        #[cfg(not(unix))]
+        if !exited {
+            let _ = child.kill();
+        }
+        let status = child.wait()?;
+        let status = exited.then_some(status);
+        let stdout = stdout.join().unwrap_or_default();
+        let stderr = stderr.join().unwrap_or_default();
+        Ok((status, stdout, stderr))
+    }
+}
+
+/// Whether the child exited. On unix the child is not reaped, so its pid stays reserved until [`Child::wait`].
+#[cfg(unix)]
+fn has_exited(child: &mut Child) -> Result<bool> {
+    // SAFETY: `siginfo_t` is plain data, so all zeros is a valid value. `waitid` only writes into `info`, and with
+    // `WNOWAIT` it leaves the child waitable. `si_pid` is set by `waitid` for `WEXITED`, and stays zero if the
+    // child is still running.
+    unsafe {
+        let mut info: libc::siginfo_t = std::mem::zeroed();
+        let flags = libc::WEXITED | libc::WNOHANG | libc::WNOWAIT;
+        if libc::waitid(libc::P_PID, child.id() as libc::id_t, &mut info, flags) != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        Ok(info.si_pid() != 0)
+    }
+}
+
+#[cfg(not(unix))]
+fn has_exited(child: &mut Child) -> Result<bool> {
+    Ok(child.try_wait()?.is_some())
+}
+
+/// The file name of a program, so `/bin/rm` is compared as `rm`.
+fn program_name(program: &str) -> String {
+    std::path::Path::new(program)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+/// Whether the arguments start with the words of the command.
+fn command_matches(args: &[String], command: &str) -> bool {
+    let words = command.split_whitespace().collect::<Vec<_>>();
+    if words.is_empty() || words.len() > args.len() {
+        return false;
+    }
+    (program_name(&args[0]) == words[0] || args[0] == words[0])
+        && words[1..]
+            .iter()
+            .zip(&args[1..])
+            .all(|(word, arg)| word == arg)
+}
+
+/// Whether the arguments may run the denied command: its program appears anywhere in the arguments, so wrappers such
+/// as `env` or `sudo` are seen through, followed by its other words in order, skipping options and their values.
+///
+/// This errs on the side of denying, e.g. `git log --grep push` is denied by `git push`.
+fn command_denied(args: &[String], command: &str) -> bool {
+    let words = command.split_whitespace().collect::<Vec<_>>();
+    let Some((program, subcommand)) = words.split_first() else {
+        return false;
+    };
+    args.iter().enumerate().any(|(i, arg)| {
+        if program_name(arg) != *program && arg != program {
+            return false;
+        }
+        let mut rest = args[i + 1..].iter().filter(|arg| !arg.starts_with('-'));
+        subcommand.iter().all(|word| rest.any(|arg| arg == word))
+    })
+}
+
+/// Splits a command into arguments like a POSIX shell, with single quotes, double quotes and backslash escapes.
+///
+/// Unquoted shell operators are refused, since the command is not run by a shell.
+pub fn split_command(command: &str) -> Result<Vec<String>> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut in_arg = false;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("Unterminated single quote in: {}", command)),
+                    }
+                }
+            }
+            '"' => {
+                in_arg = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => current.push(c),
+                            Some(c) => {
+                                current.push('\\');
+                                current.push(c);
+                            }
+                            None => break,
+                        },
+                        Some(c) => current.push(c),
+                        None => return Err(anyhow!("Unterminated double quote in: {}", command)),
+                    }
+                }
+            }
+            '\\' => {
+                in_arg = true;
+                if let Some(c) = chars.next() {
+                    current.push(c);
+                }
+            }
+            '|' | '&' | ';' | '<' | '>' | '`' => {
+                return Err(anyhow!(
+                    "Shell operators such as '{}' are not supported. Run one command at a time.",
+                    c
+                ));
+            }
+            c if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            c => {
+                in_arg = true;
+                current.push(c);
+            }
+        }
+    }
+    if in_arg {
+        args.push(current);
+    }
+    if args.is_empty() {
+        return Err(anyhow!("The command is empty"));
+    }
+    Ok(args)
+}
+
+/// Keeps the start and the end of long output, which usually hold the most useful lines.
+pub fn truncate_output(output: &str, max_chars: usize) -> String {
+    let length = output.chars().count();
+    if length <= max_chars {
+        return output.to_string();
+    }
+    cut_middle(output, output, length, max_chars)
+}
+
+/// Joins the start of `head` and the end of `tail`, for output of `length` characters in total.
+fn cut_middle(head: &str, tail: &str, length: usize, max_chars: usize) -> String {
+    let tail_chars = max_chars - max_chars / 2;
+    let head = head.chars().take(max_chars / 2).collect::<String>();
+    let tail = tail
+        .chars()
+        .skip(tail.chars().count().saturating_sub(tail_chars))
+        .collect::<String>();
+    format!(
+        "{}\n[... {} characters truncated ...]\n{}",
+        head,
+        length - max_chars,
+        tail
+    )
+}
+
+/// Reads the output of a command and truncates it like [`truncate_output`], only keeping its start and end in memory.
+fn read_output(mut pipe: impl Read, max_chars: usize) -> String {
+    // Both parts hold at least `max_chars` characters, however many bytes each character takes.
+    let limit = max_chars.saturating_mul(4);
+    let is_char_start = |byte: u8| byte & 0xC0 != 0x80;
+    let mut head = Vec::new();
+    let mut tail = VecDeque::new();
+    let mut skipped_chars = 0;
+    let mut buffer = [0; 8192];
+    loop {
+        let read = match pipe.read(&mut buffer) {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+        for &byte in &buffer[..read] {
+            if head.len() < limit {
+                head.push(byte);
+                continue;
+            }
+            tail.push_back(byte);
+            if tail.len() > limit && tail.pop_front().is_some_and(is_char_start) {
+                skipped_chars += 1;
+            }
+        }
+    }
+    if skipped_chars == 0 {
+        head.extend(tail);
+        return truncate_output(&String::from_utf8_lossy(&head), max_chars);
+    }
+    let tail = Vec::from(tail);
+    let length = head
+        .iter()
+        .chain(&tail)
+        .filter(|&&byte| is_char_start(byte))
+        .count()
+        + skipped_chars;
+    cut_middle(
+        &String::from_utf8_lossy(&head),
+        &String::from_utf8_lossy(&tail),
+        length,
+        max_chars,
+    )
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "ShellToolParams")]
+pub struct ShellToolParams {
+    #[schemars(description = "The command to run, e.g. 'cargo test'")]
+    command: String,
+}
+
+impl Tool for ShellTool {
+    type Params = ShellToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn forward(&self, arguments: ShellToolParams) -> Result<String> {
+        let (status, stdout, stderr) = self.run(&arguments.command)?;
+        let mut output = match status {
+            None => format!(
+                "The command timed out after {} seconds and was killed.",
+                self.timeout.as_secs_f64()
+            ),
+            Some(status) => match status.code() {
+                Some(code) => format!("Exit code: {}", code),
+                None => format!("The command was terminated by a signal ({}).", status),
+            },
+        };
+        for (name, text) in [("stdout", stdout), ("stderr", stderr)] {
+            if !text.trim().is_empty() {
+                output.push_str(&format!("\n\n{}:\n{}", name, text.trim_end()));
+            }
+        }
+        Ok(output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_command() {
+        assert_eq!(
+            split_command(r#"git commit -m "fix \"parser\"" 'it''s' a\ b"#).unwrap(),
+            vec!["git", "commit", "-m", "fix \"parser\"", "its", "a b"]
+        );
+        assert_eq!(split_command("echo ''").unwrap(), vec!["echo", ""]);
+        assert!(split_command("cat file | grep x").is_err());
+        assert!(split_command("echo 'unterminated").is_err());
+        assert!(split_command("   ").is_err());
+        assert_eq!(
+            split_command("echo 'a | b'").unwrap(),
+            vec!["echo", "a | b"]
+        );
+    }
+
+    #[test]
+    fn test_check_command() {
+        let tool = ShellTool::new()
+            .with_allowed_commands(&["cargo", "git status", "git diff"])
+            .with_denied_commands(&["cargo publish"]);
+        let check = |command: &str| tool.check_command(&split_command(command).unwrap());
+        assert!(check("cargo test --workspace").is_ok());
+        assert!(check("/usr/bin/cargo build").is_ok());
+        assert!(check("git status --short").is_ok());
+        assert!(check("git push").is_err());
+        assert!(check("rm -rf /").is_err());
+        let error = check("cargo publish").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The command 'cargo publish' is not allowed"
+        );
+
+        let tool = ShellTool::new().with_denied_commands(&["git push", "rm"]);
+        let check = |command: &str| tool.check_command(&split_command(command).unwrap());
+        for command in [
+            "git push",
+            "git -c x=y push origin",
+            "git -C . push",
+            "env git push",
+            "sudo -u me /usr/bin/git push",
+            "/bin/rm -rf build",
+            "nice rm file",
+        ] {
+            assert!(check(command).is_err(), "{}", command);
+        }
+        assert!(check("git pull").is_ok());
+        assert!(check("git status").is_ok());
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        assert_eq!(truncate_output("short", 10), "short");
+        assert_eq!(
+            truncate_output("abcdefghij", 4),
+            "ab\n[... 6 characters truncated ...]\nij"
+        );
+    }
+
+    #[test]
+    fn test_read_output_only_keeps_the_start_and_end() {
+        let output = "héllo wörld, ".repeat(1000);
+        for max_chars in [0, 1, 7, 100, 20_000] {
+            assert_eq!(
+                read_output(std::io::Cursor::new(output.as_bytes()), max_chars),
+                truncate_output(&output, max_chars),
+                "{}",
+                max_chars
+            );
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_tool_output_and_exit_code() {
+        let dir = tempfile::tempdir().unwrap();
+        let tool = ShellTool::new()
+            .with_working_directory(dir.path())
+            .with_env("GREETING", "hello");
+        let run = |command: &str| {
+            Tool::forward(
+                &tool,
+                ShellToolParams {
+                    command: command.to_string(),
+                },
+            )
+        };
+        let output = run("sh -c 'echo $GREETING; pwd -P; echo oops >&2; exit 3'").unwrap();
+        assert_eq!(
+            output,
+            format!(
+                "Exit code: 3\n\nstdout:\nhello\n{}\n\nstderr:\noops",
+                dir.path().canonicalize().unwrap().display()
+            )
+        );
+        assert!(run("definitely-not-a-command").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_shell_tool_timeout_kills_process_group() {
+        let tool = ShellTool::new().with_timeout(Duration::from_millis(200));
+        let started = Instant::now();
+        // The background sleep holds the output pipe open, so this only returns once the group is killed.
+        let output = Tool::forward(
+            &tool,
+            ShellToolParams {
+                command: "sh -c 'sleep 5 & echo started; wait'".to_string(),
+            },
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert!(output.starts_with("The command timed out after 0.2 seconds and was killed."));
+        assert!(output.contains("started"));
+
+        let started = Instant::now();
+        let output = Tool::forward(
+            &tool.with_timeout(Duration::from_secs(5)),
+            ShellToolParams {
+                command: "sh -c 'sleep 5 & echo done'".to_string(),
+            },
+        )
+        .unwrap();
+        assert!(started.elapsed() < Duration::from_secs(3));
+        assert_eq!(output, "Exit code: 0\n\nstdout:\ndone");
+    }
+}
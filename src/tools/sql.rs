@@ -0,0 +1,407 @@
+//! This module contains the SQL tool. The model uses this tool to query a SQLite database.
+//!
+//! The description of the tool lists the schema of every table, read from `sqlite_master`, so the model can write
+//! queries without exploring the database first, and is refreshed after queries that change the database. The
+//! database is opened read-only unless write access is enabled, and queries running longer than the timeout are
+//! interrupted. This tool needs the `sql` feature.
+
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rusqlite::types::ValueRef;
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::documents::markdown_table;
+use super::tool_traits::Tool;
+use anyhow::{anyhow, Result};
+
+/// The default number of rows returned by a query.
+pub const DEFAULT_SQL_MAX_ROWS: usize = 50;
+
+/// The default time a query can run before it is interrupted.
+pub const DEFAULT_SQL_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize, Clone)]
+pub struct SqlTool {
+    pub tool: BaseTool,
+    /// The path of the SQLite database file.
+    pub path: PathBuf,
+    /// Whether queries may change the database.
+    pub allow_writes: bool,
+    /// The largest number of rows returned by a query.
+    pub max_rows: usize,
+    /// The time a query can run before it is interrupted.
+    pub timeout: Duration,
+    /// The description with the current schema. Clones of the tool share it.
+    #[serde(skip)]
+    description: Arc<Mutex<Arc<str>>>,
+}
+
+impl SqlTool {
+    /// Creates the tool for an existing database file, with the current schema in its description.
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let tool = SqlTool {
+            tool: BaseTool {
+                name: "sql",
+                description: "Runs one SQL query on a SQLite database and returns the result as a markdown table.",
+            },
+            path: path.as_ref().to_path_buf(),
+            allow_writes: false,
+            max_rows: DEFAULT_SQL_MAX_ROWS,
+            timeout: DEFAULT_SQL_TIMEOUT,
+            description: Arc::new(Mutex::new(Arc::from(""))),
+        };
+        tool.refresh_description()?;
+        Ok(tool)
+    }
+
+    pub fn with_write_access(mut self, allow_writes: bool) -> Result<Self> {
+        self.allow_writes = allow_writes;
+        self.refresh_description()?;
+        Ok(self)
+    }
+
+    pub fn with_max_rows(mut self, max_rows: usize) -> Self {
+        self.max_rows = max_rows;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    fn connect(&self) -> Result<Connection> {
+        let flags = match self.allow_writes {
+            true => OpenFlags::SQLITE_OPEN_READ_WRITE,
+            false => OpenFlags::SQLITE_OPEN_READ_ONLY,
+        };
+        Connection::open_with_flags(&self.path, flags | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .map_err(|e| anyhow!("Failed to open the database {}: {}", self.path.display(), e))
+    }
+
+    /// The `CREATE` statements of the tables, views and indexes in the database.
+    pub fn schema(&self) -> Result<String> {
+        schema(&self.connect()?)
+    }
+
+    /// Regenerates the description from the current schema, e.g. after the database was changed outside the tool.
+    pub fn refresh_description(&self) -> Result<()> {
+        self.update_description(&self.connect()?)
+    }
+
+    fn update_description(&self, connection: &Connection) -> Result<()> {
+        let schema = schema(connection)?;
+        let access = match self.allow_writes {
+            true => "Queries can read and change the database.",
+            false => "Only read-only queries are allowed.",
+        };
+        let schema = match schema.is_empty() {
+            true => "The database has no tables.".to_string(),
+            false => format!("The database has this schema:\n{}", schema),
+        };
+        let description = format!("{} {}\n{}", self.tool.description, access, schema);
+        *self.description.lock().unwrap() = Arc::from(description);
+        Ok(())
+    }
+
+    /// Runs the query and returns its result as a markdown table, or the number of changed rows.
+    pub fn query(&self, sql: &str) -> Result<String> {
+        if has_multiple_statements(sql) {
+            return Err(anyhow!("Only one statement can be run at a time"));
+        }
+        let connection = self.connect()?;
+        let deadline = Instant::now() + self.timeout;
+        connection.progress_handler(1000, Some(move || Instant::now() >= deadline));
+        let (output, changes_database) = self.run(&connection, sql).map_err(|e| {
+            match e.downcast_ref::<rusqlite::Error>() {
+                Some(error) if error.sqlite_error_code() == Some(ErrorCode::OperationInterrupted) => {
+                    anyhow!(
+                        "The query was interrupted after {} seconds. Make it cheaper, e.g. with a filter on an indexed column or a LIMIT.",
+                        self.timeout.as_secs_f64()
+                    )
+                }
+                _ => e,
+            }
+        })?;
+        if changes_database {
+            connection.progress_handler(0, None::<fn() -> bool>);
+            self.update_description(&connection)?;
+        }
+        Ok(output)
+    }
+
+    /// Runs the query and returns its output, and whether it may have changed the database.
+    fn run(&self, connection: &Connection, sql: &str) -> Result<(String, bool)> {
+        let mut statement = connection
+            .prepare(sql)
+            .map_err(|e| anyhow!("Invalid query: {}", e))?;
+        if !self.allow_writes && !statement.readonly() {
+            return Err(anyhow!(
+                "Only read-only queries are allowed on this database"
+            ));
+        }
+        let changes_database = !statement.readonly();
+        let column_count = statement.column_count();
+        if column_count == 0 {
+            let changed = statement.execute([])?;
+            return Ok((
+                format!("The query changed {} rows.", changed),
+                changes_database,
+            ));
+        }
+
+        let headers = statement
+            .column_names()
+            .into_iter()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        let mut rows = statement.query([])?;
+        let mut table = Vec::new();
+        let mut truncated = false;
+        while let Some(row) = rows.next()? {
+            if table.len() >= self.max_rows {
+                truncated = true;
+                break;
+            }
+            let cells = (0..column_count)
+                .map(|i| row.get_ref(i).map(cell_text))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            table.push(cells);
+        }
+        if table.is_empty() {
+            let output = format!(
+                "The query returned no rows. Columns: {}",
+                headers.join(", ")
+            );
+            return Ok((output, changes_database));
+        }
+        let mut output = markdown_table(&headers, &table);
+        if truncated {
+            output.push_str(&format!(
+                "\n\n[Showing the first {} rows. Add a LIMIT, a filter or an aggregate to see the others.]",
+                self.max_rows
+            ));
+        }
+        Ok((output, changes_database))
+    }
+}
+
+/// The `CREATE` statements of the tables, views and indexes in the database.
+fn schema(connection: &Connection) -> Result<String> {
+    let mut statement = connection.prepare(
+        "SELECT sql FROM sqlite_master WHERE sql IS NOT NULL AND name NOT LIKE 'sqlite_%' \
+             ORDER BY CASE type WHEN 'table' THEN 0 WHEN 'view' THEN 1 ELSE 2 END, name",
+    )?;
+    let statements = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(statements
+        .iter()
+        .map(|sql| format!("{};", sql.trim()))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Whether there is another statement after the first `;`, ignoring string literals, quoted names and comments.
+fn has_multiple_statements(sql: &str) -> bool {
+    let mut chars = sql.chars().peekable();
+    let mut ended = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' | '`' => {
+                if ended {
+                    return true;
+                }
+                for next in chars.by_ref() {
+                    if next == c {
+                        break;
+                    }
+                }
+            }
+            '-' if chars.peek() == Some(&'-') => {
+                for next in chars.by_ref() {
+                    if next == '\n' {
+                        break;
+                    }
+                }
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut previous = ' ';
+                for next in chars.by_ref() {
+                    if previous == '*' && next == '/' {
+                        break;
+                    }
+                    previous = next;
+                }
+            }
+            ';' => ended = true,
+            c if c.is_whitespace() => {}
+            _ if ended => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn cell_text(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(integer) => integer.to_string(),
+        ValueRef::Real(real) => real.to_string(),
+        ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+        ValueRef::Blob(blob) => format!("<blob of {} bytes>", blob.len()),
+    }
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "SqlToolParams")]
+pub struct SqlToolParams {
+    #[schemars(description = "The SQL query to run. Only one statement is allowed")]
+    query: String,
+}
+
+impl Tool for SqlTool {
+    type Params = SqlToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn current_description(&self) -> Cow<'static, str> {
+        Cow::Owned(self.description.lock().unwrap().to_string())
+    }
+    fn forward(&self, arguments: SqlToolParams) -> Result<String> {
+        self.query(&arguments.query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn database() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cities.db");
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE cities (name TEXT NOT NULL, country TEXT, population INTEGER, area REAL);
+                 CREATE INDEX cities_country ON cities (country);
+                 INSERT INTO cities VALUES ('Paris', 'France', 2100000, 105.4);
+                 INSERT INTO cities VALUES ('Lyon', 'France', 520000, 47.9);
+                 INSERT INTO cities VALUES ('Berlin', 'Germany', 3600000, NULL);",
+            )
+            .unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn test_sql_tool_description_has_schema() {
+        let (_dir, path) = database();
+        let tool = SqlTool::new(&path).unwrap();
+        assert_eq!(
+            Tool::current_description(&tool),
+            "Runs one SQL query on a SQLite database and returns the result as a markdown table. Only read-only queries are allowed.\nThe database has this schema:\nCREATE TABLE cities (name TEXT NOT NULL, country TEXT, population INTEGER, area REAL);\nCREATE INDEX cities_country ON cities (country);"
+        );
+        assert!(SqlTool::new(path.with_file_name("missing.db")).is_err());
+    }
+
+    #[test]
+    fn test_sql_tool_queries() {
+        let (_dir, path) = database();
+        let tool = SqlTool::new(&path).unwrap().with_max_rows(2);
+        let query = |sql: &str| {
+            Tool::forward(
+                &tool,
+                SqlToolParams {
+                    query: sql.to_string(),
+                },
+            )
+        };
+        assert_eq!(
+            query("SELECT name, population, area FROM cities ORDER BY name").unwrap(),
+            "| name | population | area |\n| --- | --- | --- |\n| Berlin | 3600000 | NULL |\n| Lyon | 520000 | 47.9 |\n\n[Showing the first 2 rows. Add a LIMIT, a filter or an aggregate to see the others.]"
+        );
+        assert_eq!(
+            query("SELECT country, COUNT(*) AS n FROM cities GROUP BY country").unwrap(),
+            "| country | n |\n| --- | --- |\n| France | 2 |\n| Germany | 1 |"
+        );
+        assert_eq!(
+            query("SELECT name FROM cities WHERE country = 'Spain'").unwrap(),
+            "The query returned no rows. Columns: name"
+        );
+        assert!(query("SELECT * FROM missing")
+            .unwrap_err()
+            .to_string()
+            .starts_with("Invalid query"));
+        assert_eq!(
+            query("SELECT 1; SELECT 2").unwrap_err().to_string(),
+            "Only one statement can be run at a time"
+        );
+        assert_eq!(
+            query("SELECT ';' AS a; -- done").unwrap(),
+            "| a |\n| --- |\n| ; |"
+        );
+    }
+
+    #[test]
+    fn test_sql_tool_write_access() {
+        let (_dir, path) = database();
+        let read_only = SqlTool::new(&path).unwrap();
+        let error = read_only.query("DELETE FROM cities").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Only read-only queries are allowed on this database"
+        );
+
+        let tool = SqlTool::new(&path)
+            .unwrap()
+            .with_write_access(true)
+            .unwrap();
+        let clone = tool.clone();
+        assert!(
+            Tool::current_description(&tool).contains("Queries can read and change the database.")
+        );
+        assert_eq!(
+            tool.query("DELETE FROM cities WHERE country = 'France'")
+                .unwrap(),
+            "The query changed 2 rows."
+        );
+        tool.query("CREATE TABLE countries (name TEXT)").unwrap();
+        // Clones share the refreshed description.
+        assert!(Tool::current_description(&clone).contains("CREATE TABLE countries (name TEXT);"));
+        let description = Tool::current_description(&tool);
+        tool.refresh_description().unwrap();
+        assert_eq!(description, Tool::current_description(&tool));
+        assert_eq!(
+            read_only.query("SELECT COUNT(*) FROM cities").unwrap(),
+            "| COUNT(*) |\n| --- |\n| 1 |"
+        );
+    }
+
+    #[test]
+    fn test_sql_tool_timeout() {
+        let (_dir, path) = database();
+        let tool = SqlTool::new(&path)
+            .unwrap()
+            .with_timeout(Duration::from_millis(100));
+        let error = tool
+            .query("WITH RECURSIVE n(i) AS (SELECT 1 UNION ALL SELECT i + 1 FROM n) SELECT COUNT(*) FROM n")
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("The query was interrupted after 0.1 seconds."));
+        assert_eq!(
+            tool.query("SELECT COUNT(*) FROM cities").unwrap(),
+            "| COUNT(*) |\n| --- |\n| 3 |"
+        );
+    }
+}
@@ -7,8 +7,11 @@ use schemars::JsonSchema;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use serde_json::json;
+use std::borrow::Cow;
 use std::fmt::Debug;
 
+use super::output::ToolOutput;
+use super::validation::validate_arguments;
 use crate::errors::{AgentError, AgentExecutionError};
 use crate::models::openai::FunctionCall;
 
@@ -22,33 +25,50 @@ pub trait Tool: Debug {
     fn name(&self) -> &'static str;
     /// The description of the tool.
     fn description(&self) -> &'static str;
+    /// The description shown to the model. Defaults to [`Tool::description`]; tools whose description changes at
+    /// runtime, such as the SQL tool listing the current schema, return it here.
+    fn current_description(&self) -> Cow<'static, str> {
+        Cow::Borrowed(self.description())
+    }
     /// The function to call when the tool is used.
     fn forward(&self, arguments: Self::Params) -> Result<String>;
+    /// The function to call when the tool is used, for tools that return structured data, images or files.
+    /// Defaults to the text returned by [`Tool::forward`].
+    fn forward_output(&self, arguments: Self::Params) -> Result<ToolOutput> {
+        self.forward(arguments).map(ToolOutput::Text)
+    }
+    /// Whether calls with the same arguments return the same output for a while, so a [`ToolCache`] may reuse
+    /// them. Tools with side effects must keep the default.
+    ///
+    /// [`ToolCache`]: super::cache::ToolCache
+    fn cacheable(&self) -> bool {
+        false
+    }
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub enum ToolType {
     #[serde(rename = "function")]
     Function,
 }
 
 /// A struct that contains information about a tool. This is used to serialize the tool for the API.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ToolInfo {
     #[serde(rename = "type")]
     tool_type: ToolType,
     pub function: ToolFunctionInfo,
 }
 /// This struct contains information about the function to call when the tool is used.
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ToolFunctionInfo {
     pub name: &'static str,
-    pub description: &'static str,
+    pub description: Cow<'static, str>,
     pub parameters: RootSchema,
 }
 
 impl ToolInfo {
-    pub fn new<P: Parameters, T: AnyTool>(tool: &T) -> Self {
+    pub fn new<P: Parameters, T: Tool>(tool: &T) -> Self {
         let mut settings = SchemaSettings::draft07();
         settings.inline_subschemas = true;
         let generator = settings.into_generator();
@@ -59,7 +79,7 @@ impl ToolInfo {
             tool_type: ToolType::Function,
             function: ToolFunctionInfo {
                 name: tool.name(),
-                description: tool.description(),
+                description: tool.current_description(),
                 parameters,
             },
         }
@@ -79,6 +99,7 @@ pub fn get_json_schema(tool: &ToolInfo) -> serde_json::Value {
 
 pub trait ToolGroup: Debug {
     fn call(&self, arguments: &FunctionCall) -> Result<String, AgentExecutionError>;
+    fn call_output(&self, arguments: &FunctionCall) -> Result<ToolOutput, AgentExecutionError>;
     fn tool_info(&self) -> Vec<ToolInfo>;
 }
 
@@ -91,20 +112,56 @@ impl ToolGroup for Vec<Box<dyn AnyTool>> {
         }
         Err(AgentError::Execution("Tool not found".to_string()))
     }
+    fn call_output(&self, arguments: &FunctionCall) -> Result<ToolOutput, AgentError> {
+        let tool = self.iter().find(|tool| tool.name() == arguments.name);
+        if let Some(tool) = tool {
+            return tool.forward_json_output(arguments.arguments.clone());
+        }
+        Err(AgentError::Execution("Tool not found".to_string()))
+    }
     fn tool_info(&self) -> Vec<ToolInfo> {
         self.iter().map(|tool| tool.tool_info()).collect()
     }
 }
 
-pub trait AnyTool: Debug {
+pub trait AnyTool: Debug + Send {
     fn name(&self) -> &'static str;
     fn description(&self) -> &'static str;
     fn forward_json(&self, json_args: serde_json::Value) -> Result<String, AgentError>;
+    /// Like [`AnyTool::forward_json`], keeping structured data, images and files.
+    fn forward_json_output(&self, json_args: serde_json::Value) -> Result<ToolOutput, AgentError> {
+        self.forward_json(json_args).map(ToolOutput::Text)
+    }
     fn tool_info(&self) -> ToolInfo;
     fn clone_box(&self) -> Box<dyn AnyTool>;
+    /// Clears the state a tool keeps for the current run, such as an open circuit breaker. Agents call this when
+    /// they start a task.
+    fn reset(&self) {}
+    /// Whether the outputs of the tool may be cached, see [`Tool::cacheable`].
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// Validates the JSON arguments of a call against the tool's schema and deserializes them into its parameters.
+#[allow(clippy::to_string_in_format_args)]
+pub(crate) fn parse_arguments<T: Tool + AnyTool>(
+    tool: &T,
+    json_args: &serde_json::Value,
+) -> Result<T::Params, AgentError> {
+    let json_args = &validate_arguments(&tool.tool_info(), json_args)?;
+    serde_json::from_value::<T::Params>(json_args.clone()).map_err(|e| {
+        AgentError::Parsing(format!(
+            "Error when executing tool with arguments: {:?}: {}. As a reminder, this tool's description is: {} and takes inputs: {}",
+            json_args,
+            e.to_string(),
+            tool.current_description(),
+            json!(&tool.tool_info().function.parameters.schema)["properties"].to_string()
+        ))
+    })
 }
 
-impl<T: Tool + Clone + 'static> AnyTool for T {
+impl<T: Tool + Clone + Send + 'static> AnyTool for T {
     fn name(&self) -> &'static str {
         Tool::name(self)
     }
@@ -114,16 +171,13 @@ impl<T: Tool + Clone + 'static> AnyTool for T {
     }
 
     fn forward_json(&self, json_args: serde_json::Value) -> Result<String, AgentError> {
-        let params = serde_json::from_value::<T::Params>(json_args.clone()).map_err(|e| {
-            AgentError::Parsing(format!(
-                "Error when executing tool with arguments: {:?}: {}. As a reminder, this tool's description is: {} and takes inputs: {}",
-                json_args,
-                e.to_string(),
-                self.description(),
-                json!(&self.tool_info().function.parameters.schema)["properties"].to_string()
-            ))
-        })?;
-        Tool::forward(self, params).map_err(|e| AgentError::Execution(e.to_string()))
+        self.forward_json_output(json_args)
+            .map(|output| output.render())
+    }
+
+    fn forward_json_output(&self, json_args: serde_json::Value) -> Result<ToolOutput, AgentError> {
+        let params = parse_arguments(self, &json_args)?;
+        Tool::forward_output(self, params).map_err(|e| AgentError::Execution(e.to_string()))
     }
 
     fn tool_info(&self) -> ToolInfo {
@@ -133,4 +187,8 @@ impl<T: Tool + Clone + 'static> AnyTool for T {
     fn clone_box(&self) -> Box<dyn AnyTool> {
         Box::new(self.clone())
     }
+
+    fn cacheable(&self) -> bool {
+        Tool::cacheable(self)
+    }
 }
@@ -0,0 +1,614 @@
+//! This module validates the arguments of a tool call against the tool's JSON schema before the tool runs.
+//!
+//! Serde only reports the first problem it finds, in terms of Rust types, and ignores constraints such as
+//! `#[schemars(range(min = 1))]`. The validator checks the subset of JSON schema that schemars generates: types,
+//! required and unknown properties, `enum` and `const`, numeric ranges, string lengths and patterns, array items
+//! and `anyOf`/`oneOf`/`allOf`. It lists every offending field, so the model can fix them all at once.
+//!
+//! Common mismatches are coerced where the schema allows it: numeric and boolean strings become numbers and booleans,
+//! numbers become strings, whole floats become integers, JSON-encoded strings become arrays and objects, and `None`
+//! or `null` become null.
+
+use std::fmt;
+
+use regex::Regex;
+use serde_json::{json, Map, Value};
+
+use super::tool_traits::ToolInfo;
+use crate::errors::AgentError;
+
+/// A problem with one field of the arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArgumentError {
+    /// The path of the field, such as `filters.tags[1]`, or empty for the arguments themselves.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for ArgumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "The arguments {}", self.message)
+        } else {
+            write!(f, "`{}` {}", self.path, self.message)
+        }
+    }
+}
+
+/// Validates the arguments of a call to the tool, returning them with the mismatches coerced.
+pub fn validate_arguments(tool: &ToolInfo, arguments: &Value) -> Result<Value, AgentError> {
+    let schema = json!(&tool.function.parameters);
+    let mut arguments = arguments.clone();
+    let errors = validate(&schema, &mut arguments);
+    if errors.is_empty() {
+        return Ok(arguments);
+    }
+    let errors = errors
+        .iter()
+        .map(|error| format!("- {}", error))
+        .collect::<Vec<_>>();
+    Err(AgentError::Parsing(format!(
+        "Invalid arguments for the tool {}:\n{}\nAs a reminder, this tool takes inputs: {}",
+        tool.function.name,
+        errors.join("\n"),
+        schema["properties"]
+    )))
+}
+
+/// Validates a value against a schema, coercing it in place, and returns the problems found.
+pub fn validate(schema: &Value, value: &mut Value) -> Vec<ArgumentError> {
+    let mut errors = Vec::new();
+    Validator { root: schema }.validate(schema, value, "", &mut errors);
+    errors
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+}
+
+impl Validator<'_> {
+    fn validate(
+        &self,
+        schema: &Value,
+        value: &mut Value,
+        path: &str,
+        errors: &mut Vec<ArgumentError>,
+    ) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => return errors.push(error(path, "is not allowed")),
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            if let Some(target) = self.resolve(reference) {
+                self.validate(target, value, path, errors);
+            }
+        }
+
+        let types = schema_types(schema);
+        if !types.is_empty() && !types.iter().any(|t| has_type(value, t)) {
+            match types.iter().find_map(|t| coerce(value, t)) {
+                Some(coerced) => *value = coerced,
+                None => {
+                    return errors.push(error(
+                        path,
+                        &format!("must be {}, got {}", describe_types(&types), show(value)),
+                    ))
+                }
+            }
+        }
+
+        for (keyword, alternatives) in [("anyOf", false), ("oneOf", true)] {
+            if let Some(Value::Array(schemas)) = schema.get(keyword) {
+                self.validate_alternatives(schemas, value, path, errors, alternatives);
+            }
+        }
+        if let Some(Value::Array(schemas)) = schema.get("allOf") {
+            for schema in schemas {
+                self.validate(schema, value, path, errors);
+            }
+        }
+
+        // schemars lists the variants of an optional enum without null.
+        let nullable = value.is_null() && types.iter().any(|t| t == "null");
+        if let Some(Value::Array(allowed)) = schema.get("enum") {
+            if !nullable && !allowed.contains(value) {
+                let allowed = allowed.iter().map(show).collect::<Vec<_>>();
+                errors.push(error(
+                    path,
+                    &format!("must be one of {}, got {}", allowed.join(", "), show(value)),
+                ));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                errors.push(error(
+                    path,
+                    &format!("must be {}, got {}", show(expected), show(value)),
+                ));
+            }
+        }
+
+        match value {
+            Value::Number(number) => {
+                check_number(schema, number.as_f64().unwrap_or_default(), path, errors)
+            }
+            Value::String(string) => check_string(schema, string, path, errors),
+            Value::Array(items) => self.validate_array(schema, items, path, errors),
+            Value::Object(object) => self.validate_object(schema, object, path, errors),
+            _ => {}
+        }
+    }
+
+    /// Resolves a local reference such as `#/definitions/Filter`.
+    fn resolve(&self, reference: &str) -> Option<&Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+
+    /// Keeps the value coerced by the first matching alternative, or reports the problems of the closest one.
+    fn validate_alternatives(
+        &self,
+        schemas: &[Value],
+        value: &mut Value,
+        path: &str,
+        errors: &mut Vec<ArgumentError>,
+        exactly_one: bool,
+    ) {
+        let mut closest: Option<Vec<ArgumentError>> = None;
+        let mut matched = Vec::new();
+        for schema in schemas {
+            let mut candidate = value.clone();
+            let mut candidate_errors = Vec::new();
+            self.validate(schema, &mut candidate, path, &mut candidate_errors);
+            if candidate_errors.is_empty() {
+                matched.push(candidate);
+            } else if closest
+                .as_ref()
+                .is_none_or(|closest| candidate_errors.len() < closest.len())
+            {
+                closest = Some(candidate_errors);
+            }
+        }
+        match matched.len() {
+            0 => errors.extend(closest.unwrap_or_default()),
+            n if n > 1 && exactly_one => errors.push(error(
+                path,
+                &format!("is ambiguous, it matches {} of the allowed forms", n),
+            )),
+            _ => *value = matched.swap_remove(0),
+        }
+    }
+
+    fn validate_array(
+        &self,
+        schema: &Map<String, Value>,
+        items: &mut [Value],
+        path: &str,
+        errors: &mut Vec<ArgumentError>,
+    ) {
+        let count = |keyword| schema.get(keyword).and_then(Value::as_u64);
+        if let Some(min) = count("minItems").filter(|&min| (items.len() as u64) < min) {
+            errors.push(error(
+                path,
+                &format!("must have at least {} items, got {}", min, items.len()),
+            ));
+        }
+        if let Some(max) = count("maxItems").filter(|&max| (items.len() as u64) > max) {
+            errors.push(error(
+                path,
+                &format!("must have at most {} items, got {}", max, items.len()),
+            ));
+        }
+        for (i, item) in items.iter_mut().enumerate() {
+            let item_schema = match schema.get("items") {
+                Some(Value::Array(schemas)) => schemas.get(i).or(schema.get("additionalItems")),
+                items => items,
+            };
+            if let Some(item_schema) = item_schema {
+                self.validate(item_schema, item, &format!("{}[{}]", path, i), errors);
+            }
+        }
+    }
+
+    fn validate_object(
+        &self,
+        schema: &Map<String, Value>,
+        object: &mut Map<String, Value>,
+        path: &str,
+        errors: &mut Vec<ArgumentError>,
+    ) {
+        let field_path = |key: &str| match path {
+            "" => key.to_string(),
+            path => format!("{}.{}", path, key),
+        };
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !object.contains_key(key) {
+                    errors.push(error(&field_path(key), "is required"));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, value) in object.iter_mut() {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => self.validate(property, value, &field_path(key), errors),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => {
+                        let known = properties
+                            .map(|properties| {
+                                properties
+                                    .keys()
+                                    .map(|key| format!("`{}`", key))
+                                    .collect::<Vec<_>>()
+                            })
+                            .unwrap_or_default();
+                        errors.push(error(
+                            &field_path(key),
+                            &format!("is not a known field, expected one of {}", known.join(", ")),
+                        ))
+                    }
+                    Some(additional) => self.validate(additional, value, &field_path(key), errors),
+                    None => {}
+                },
+            }
+        }
+    }
+}
+
+fn error(path: &str, message: &str) -> ArgumentError {
+    ArgumentError {
+        path: path.to_string(),
+        message: message.to_string(),
+    }
+}
+
+/// A short rendering of a value for error messages.
+fn show(value: &Value) -> String {
+    let text = value.to_string();
+    match text.char_indices().nth(80) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+fn schema_types(schema: &Map<String, Value>) -> Vec<String> {
+    match schema.get("type") {
+        Some(Value::String(t)) => vec![t.clone()],
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(|t| t.as_str().map(str::to_string))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+fn describe_types(types: &[String]) -> String {
+    let names = types
+        .iter()
+        .map(|t| match t.as_str() {
+            "integer" => "an integer",
+            "number" => "a number",
+            "string" => "a string",
+            "boolean" => "a boolean",
+            "array" => "an array",
+            "object" => "an object",
+            "null" => "null",
+            other => other,
+        })
+        .collect::<Vec<_>>();
+    names.join(" or ")
+}
+
+fn has_type(value: &Value, t: &str) -> bool {
+    match t {
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+/// Converts the value to the type, if it is an unambiguous spelling of a value of that type.
+fn coerce(value: &Value, t: &str) -> Option<Value> {
+    match (t, value) {
+        ("integer", Value::String(s)) => s.trim().parse::<i64>().ok().map(Value::from),
+        ("integer", Value::Number(n)) => n
+            .as_f64()
+            .filter(|f| f.fract() == 0.0 && f.abs() < i64::MAX as f64)
+            .map(|f| Value::from(f as i64)),
+        ("number", Value::String(s)) => s
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|f| f.is_finite())
+            .map(Value::from),
+        ("boolean", Value::String(s)) => match s.trim().to_lowercase().as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            _ => None,
+        },
+        ("string", Value::Number(_) | Value::Bool(_)) => Some(Value::String(value.to_string())),
+        ("array", Value::String(s)) => serde_json::from_str(s).ok().filter(Value::is_array),
+        ("object", Value::String(s)) => serde_json::from_str(s).ok().filter(Value::is_object),
+        ("null", Value::String(s)) if matches!(s.trim(), "None" | "null") => Some(Value::Null),
+        _ => None,
+    }
+}
+
+fn check_number(
+    schema: &Map<String, Value>,
+    number: f64,
+    path: &str,
+    errors: &mut Vec<ArgumentError>,
+) {
+    let bound = |keyword| schema.get(keyword).and_then(Value::as_f64);
+    let checks = [
+        (
+            "minimum",
+            "at least",
+            bound("minimum").is_some_and(|min| number < min),
+        ),
+        (
+            "maximum",
+            "at most",
+            bound("maximum").is_some_and(|max| number > max),
+        ),
+        (
+            "exclusiveMinimum",
+            "greater than",
+            bound("exclusiveMinimum").is_some_and(|min| number <= min),
+        ),
+        (
+            "exclusiveMaximum",
+            "less than",
+            bound("exclusiveMaximum").is_some_and(|max| number >= max),
+        ),
+    ];
+    for (keyword, relation, failed) in checks {
+        if failed {
+            errors.push(error(
+                path,
+                &format!(
+                    "must be {} {}, got {}",
+                    relation,
+                    bound(keyword).unwrap_or_default(),
+                    number
+                ),
+            ));
+        }
+    }
+}
+
+fn check_string(
+    schema: &Map<String, Value>,
+    string: &str,
+    path: &str,
+    errors: &mut Vec<ArgumentError>,
+) {
+    let length = string.chars().count() as u64;
+    if let Some(min) = schema
+        .get("minLength")
+        .and_then(Value::as_u64)
+        .filter(|&min| length < min)
+    {
+        errors.push(error(
+            path,
+            &format!("must be at least {} characters long, got {}", min, length),
+        ));
+    }
+    if let Some(max) = schema
+        .get("maxLength")
+        .and_then(Value::as_u64)
+        .filter(|&max| length > max)
+    {
+        errors.push(error(
+            path,
+            &format!("must be at most {} characters long, got {}", max, length),
+        ));
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        // An invalid pattern is a mistake in the tool, not in the call, so it is not enforced.
+        if Regex::new(pattern).is_ok_and(|regex| !regex.is_match(string)) {
+            errors.push(error(
+                path,
+                &format!(
+                    "must match the pattern {}, got {}",
+                    pattern,
+                    show(&Value::from(string))
+                ),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::{AnyTool, BaseTool, Tool};
+    use anyhow::Result;
+    use schemars::JsonSchema;
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, JsonSchema, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum Order {
+        Newest,
+        Oldest,
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    #[serde(deny_unknown_fields)]
+    struct Filter {
+        tags: Vec<String>,
+        verified: Option<bool>,
+    }
+
+    #[derive(Debug, Deserialize, JsonSchema)]
+    struct SearchParams {
+        #[schemars(length(min = 2))]
+        query: String,
+        #[schemars(range(min = 1, max = 50))]
+        count: Option<usize>,
+        order: Option<Order>,
+        #[schemars(regex(pattern = r"^\d{4}$"))]
+        year: Option<String>,
+        filter: Option<Filter>,
+    }
+
+    #[derive(Debug, Clone)]
+    struct SearchTool {
+        tool: BaseTool,
+    }
+
+    impl Tool for SearchTool {
+        type Params = SearchParams;
+        fn name(&self) -> &'static str {
+            self.tool.name
+        }
+        fn description(&self) -> &'static str {
+            self.tool.description
+        }
+        fn forward(&self, arguments: SearchParams) -> Result<String> {
+            Ok(format!(
+                "{} {:?} {:?} {:?} {:?}",
+                arguments.query,
+                arguments.count,
+                arguments.order,
+                arguments.year,
+                arguments
+                    .filter
+                    .map(|filter| (filter.tags, filter.verified))
+            ))
+        }
+    }
+
+    fn tool() -> SearchTool {
+        SearchTool {
+            tool: BaseTool {
+                name: "search",
+                description: "Searches.",
+            },
+        }
+    }
+
+    fn messages(arguments: Value) -> Vec<String> {
+        let schema = json!(tool().tool_info().function.parameters);
+        let mut arguments = arguments;
+        validate(&schema, &mut arguments)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn test_coercion() {
+        let tool = tool();
+        let arguments = validate_arguments(
+            &tool.tool_info(),
+            &json!({
+                "query": 2024,
+                "count": "10",
+                "year": 2024,
+                "filter": "{\"tags\": [\"rust\"], \"verified\": \"true\"}"
+            }),
+        )
+        .unwrap();
+        assert_eq!(
+            arguments,
+            json!({
+                "query": "2024",
+                "count": 10,
+                "year": "2024",
+                "filter": {"tags": ["rust"], "verified": true}
+            })
+        );
+        assert_eq!(
+            tool.forward_json(json!({"query": "rust", "count": 5.0, "order": null}))
+                .unwrap(),
+            "rust Some(5) None None None"
+        );
+        assert_eq!(
+            tool.forward_json(json!({"query": "rust", "count": "None"}))
+                .unwrap(),
+            "rust None None None None"
+        );
+    }
+
+    #[test]
+    fn test_error_messages() {
+        assert_eq!(
+            messages(json!({
+                "count": 0,
+                "order": "best",
+                "year": "24",
+                "filter": {"tags": "rust", "stars": 5}
+            })),
+            vec![
+                "`query` is required",
+                "`count` must be at least 1, got 0",
+                "`filter.stars` is not a known field, expected one of `tags`, `verified`",
+                "`filter.tags` must be an array, got \"rust\"",
+                "`order` must be one of \"newest\", \"oldest\", got \"best\"",
+                "`year` must match the pattern ^\\d{4}$, got \"24\"",
+            ]
+        );
+        assert_eq!(
+            messages(json!({"query": "a", "count": "many", "filter": {"tags": [1, [2]]}})),
+            vec![
+                "`count` must be an integer or null, got \"many\"",
+                "`filter.tags[1]` must be a string, got [2]",
+                "`query` must be at least 2 characters long, got 1",
+            ]
+        );
+        assert_eq!(
+            messages(json!(["rust"])),
+            vec!["The arguments must be an object, got [\"rust\"]"]
+        );
+    }
+
+    #[test]
+    fn test_alternatives() {
+        let schema = json!({
+            "anyOf": [
+                {"type": "integer", "minimum": 0},
+                {"type": "string", "enum": ["all"]}
+            ]
+        });
+        let mut value = json!("3");
+        assert!(validate(&schema, &mut value).is_empty());
+        assert_eq!(value, json!(3));
+        let mut value = json!("some");
+        assert_eq!(
+            validate(&schema, &mut value)[0].to_string(),
+            "The arguments must be an integer, got \"some\""
+        );
+
+        let schema = json!({
+            "definitions": {"Limit": {"type": "integer", "maximum": 10}},
+            "type": "object",
+            "properties": {"limit": {"$ref": "#/definitions/Limit"}}
+        });
+        let mut value = json!({"limit": 20});
+        assert_eq!(
+            validate(&schema, &mut value)[0].to_string(),
+            "`limit` must be at most 10, got 20"
+        );
+    }
+
+    #[test]
+    fn test_forward_json_reports_invalid_arguments() {
+        let error = tool()
+            .forward_json(json!({"query": "rust", "count": 100, "order": "best"}))
+            .unwrap_err();
+        assert!(matches!(error, AgentError::Parsing(_)));
+        assert!(error.message().starts_with(
+            "Invalid arguments for the tool search:\n- `count` must be at most 50, got 100\n- `order` must be one of \"newest\", \"oldest\", got \"best\"\nAs a reminder"
+        ));
+    }
+}
@@ -1,16 +1,122 @@
 //! This module contains the visit website tool. The model uses this tool to visit a webpage and read its content as a markdown string.
+//!
+//! With main content extraction enabled, only the article is kept, see [`super::readability`].
+//! PDFs, JSON, CSV and plain text are read according to their `Content-Type`, see [`super::documents`].
+//!
+//! Long pages are split into pages of at most `max_chars` characters. The model can read them one page at a time
+//! or search the page for a keyword. Pages are cached per url, so reading the next page does not fetch the url again.
+//! The cache keeps the most recently read pages for a limited time, see [`VisitWebsiteTool::with_page_cache`].
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use htmd::HtmlToMarkdown;
 use reqwest::Url;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::{base::BaseTool, tool_traits::Tool};
-use anyhow::Result;
+use super::documents::{
+    csv_to_markdown, document_kind, json_to_markdown, pdf_to_text, DocumentKind, CSV_PREVIEW_ROWS,
+};
+use super::{
+    base::BaseTool,
+    policy::{is_retryable, RetryableError},
+    readability::extract_article,
+    tool_traits::Tool,
+    web_client::{decode_text, WebClient},
+};
+use anyhow::{anyhow, Result};
+
+/// The default number of characters in a page.
+pub const DEFAULT_PAGE_CHARS: usize = 10_000;
+/// The largest page size, so a page is never truncated by the agent.
+pub const MAX_PAGE_CHARS: usize = 30_000;
+/// The number of characters shown on each side of a keyword match.
+const FIND_CONTEXT_CHARS: usize = 200;
+/// The maximum number of keyword matches returned.
+const MAX_FIND_MATCHES: usize = 10;
+/// The default number of pages kept in the cache.
+pub const DEFAULT_CACHED_PAGES: usize = 32;
+/// The default time a page is kept in the cache.
+pub const DEFAULT_PAGE_TTL: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug)]
+struct CachedPage {
+    markdown: String,
+    fetched_at: Instant,
+    last_read: Instant,
+}
+
+/// The markdown of the visited pages, keyed by url. The least recently read page is dropped when the cache is full.
+#[derive(Debug)]
+struct PageCache {
+    pages: HashMap<String, CachedPage>,
+    max_pages: usize,
+    ttl: Duration,
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        PageCache {
+            pages: HashMap::new(),
+            max_pages: DEFAULT_CACHED_PAGES,
+            ttl: DEFAULT_PAGE_TTL,
+        }
+    }
+}
+
+impl PageCache {
+    fn get(&mut self, url: &str) -> Option<String> {
+        let page = self.pages.get_mut(url)?;
+        if page.fetched_at.elapsed() >= self.ttl {
+            self.pages.remove(url);
+            return None;
+        }
+        page.last_read = Instant::now();
+        Some(page.markdown.clone())
+    }
+
+    fn insert(&mut self, url: String, markdown: String) {
+        if self.max_pages == 0 {
+            return;
+        }
+        let ttl = self.ttl;
+        self.pages.retain(|_, page| page.fetched_at.elapsed() < ttl);
+        while self.pages.len() >= self.max_pages {
+            let oldest = self
+                .pages
+                .iter()
+                .min_by_key(|(_, page)| page.last_read)
+                .map(|(url, _)| url.clone());
+            match oldest {
+                Some(oldest) => self.pages.remove(&oldest),
+                None => break,
+            };
+        }
+        let now = Instant::now();
+        self.pages.insert(
+            url,
+            CachedPage {
+                markdown,
+                fetched_at: now,
+                last_read: now,
+            },
+        );
+    }
+}
 
 #[derive(Debug, Serialize, Default, Clone)]
 pub struct VisitWebsiteTool {
     pub tool: BaseTool,
+    /// Whether to keep only the main content of the page and its metadata.
+    pub extract_main_content: bool,
+    /// The markdown of the visited pages. Clones of the tool share the cache.
+    #[serde(skip)]
+    cache: Arc<Mutex<PageCache>>,
+    #[serde(skip)]
+    client: WebClient,
 }
 
 impl VisitWebsiteTool {
@@ -18,49 +124,217 @@ impl VisitWebsiteTool {
         VisitWebsiteTool {
             tool: BaseTool {
                 name: "visit_website",
-                description: "Visits a webpage at the given url and reads its content as a markdown string. Use this to browse webpages",
+                description: "Visits a webpage at the given url and reads its content as a markdown string. Use this to browse webpages and read PDF, JSON, CSV and text files. Long pages are split into pages: pass `page` to read further, or `find` to search the page for a keyword.",
             },
+            extract_main_content: false,
+            cache: Arc::default(),
+            client: WebClient::default(),
         }
     }
 
+    /// Fetches pages with this client instead of the default one.
+    pub fn with_web_client(mut self, client: WebClient) -> Self {
+        self.client = client;
+        let (max_pages, ttl) = {
+            let cache = self.cache.lock().unwrap();
+            (cache.max_pages, cache.ttl)
+        };
+        self.with_page_cache(max_pages, ttl)
+    }
+
+    /// Keeps only the main article of each page, with its title, byline and publication date.
+    /// Pages without a clear article are converted in full.
+    pub fn with_main_content_extraction(mut self, extract_main_content: bool) -> Self {
+        self.extract_main_content = extract_main_content;
+        self.clear_cache();
+        self
+    }
+
+    /// Keeps at most `max_pages` pages in the cache, each for at most `ttl`. Zero pages disables the cache.
+    pub fn with_page_cache(mut self, max_pages: usize, ttl: Duration) -> Self {
+        self.cache = Arc::new(Mutex::new(PageCache {
+            pages: HashMap::new(),
+            max_pages,
+            ttl,
+        }));
+        self
+    }
+
+    /// Returns the markdown of the whole page, or a message explaining why it could not be fetched.
     pub fn forward(&self, url: &str) -> String {
-        let client = reqwest::blocking::Client::builder()
-            .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
-            .build()
-            .unwrap_or_else(|_| reqwest::blocking::Client::new());
-        let url = match Url::parse(url) {
+        match self.fetch(url) {
+            Ok(markdown) => markdown,
+            Err(e) => e.to_string(),
+        }
+    }
+
+    /// Drops all cached pages.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().pages.clear();
+    }
+
+    fn to_markdown(&self, html: &str) -> std::io::Result<String> {
+        if self.extract_main_content {
+            if let Some(article) = extract_article(html) {
+                return Ok(article.to_markdown());
+            }
+        }
+        let converter = HtmlToMarkdown::builder()
+            .skip_tags(vec!["script", "style", "header", "nav", "footer"])
+            .build();
+        converter.convert(html)
+    }
+
+    /// Converts the response body to text according to its kind of document.
+    fn to_text(
+        &self,
+        url: &Url,
+        content_type: Option<&str>,
+        bytes: &[u8],
+    ) -> std::result::Result<String, String> {
+        let kind = if bytes.starts_with(b"%PDF-") {
+            DocumentKind::Pdf
+        } else {
+            document_kind(content_type, url)
+        };
+        let text = decode_text(bytes, content_type);
+        match kind {
+            DocumentKind::Html => self
+                .to_markdown(&text)
+                .map_err(|e| format!("Failed to convert {} to markdown: {}", url, e)),
+            DocumentKind::Pdf => pdf_to_text(bytes).map_err(|e| format!("{} ({})", e, url)),
+            DocumentKind::Json => Ok(json_to_markdown(&text)),
+            DocumentKind::Csv(delimiter) => csv_to_markdown(&text, delimiter, CSV_PREVIEW_ROWS)
+                .map_err(|e| format!("{} ({})", e, url)),
+            DocumentKind::Text => Ok(text),
+            DocumentKind::Binary(mime) => Err(format!(
+                "{} is a binary file of type {} ({} bytes) and cannot be read as text.",
+                url,
+                mime,
+                bytes.len()
+            )),
+        }
+    }
+
+    /// Fetches the page as markdown, using the cache if the url was visited before. Failures that may go away,
+    /// such as rate limits, server errors and timeouts, are [`RetryableError`]s.
+    fn fetch(&self, url: &str) -> Result<String> {
+        let url = match Url::parse(url).or_else(|_| Url::parse(&format!("https://{}", url))) {
             Ok(url) => url,
-            Err(_) => Url::parse(&format!("https://{}", url)).unwrap(),
+            Err(e) => return Err(anyhow!("Invalid url {}: {}", url, e)),
         };
+        if let Some(markdown) = self.cache.lock().unwrap().get(url.as_str()) {
+            return Ok(markdown);
+        }
 
-        let response = client.get(url.clone()).send();
-
-        match response {
-            Ok(resp) => {
-                if resp.status().is_success() {
-                    match resp.text() {
-                        Ok(text) => {
-                            let converter = HtmlToMarkdown::builder()
-                                .skip_tags(vec!["script", "style", "header", "nav", "footer"])
-                                .build();
-                            converter.convert(&text).unwrap()
-                        }
-                        Err(_) => "Failed to read response text".to_string(),
-                    }
-                } else if resp.status().as_u16() == 999 {
-                    "The website appears to be blocking automated access. Try visiting the URL directly in your browser.".to_string()
-                } else {
-                    format!(
-                        "Failed to fetch the webpage {}: HTTP {} - {}",
-                        url,
-                        resp.status(),
-                        resp.status().canonical_reason().unwrap_or("Unknown Error")
-                    )
+        let request = self.client.get(url.clone());
+        let markdown = match self.client.send(request) {
+            Ok(response) if response.status.is_success() => self
+                .to_text(
+                    &response.url,
+                    response.content_type.as_deref(),
+                    &response.body,
+                )
+                .map_err(|message| anyhow!(message))?,
+            Ok(response) if response.status.as_u16() == 999 => {
+                return Err(anyhow!("The website appears to be blocking automated access. Try visiting the URL directly in your browser."));
+            }
+            Ok(response) => {
+                let message = format!(
+                    "Failed to fetch the webpage {}: HTTP {} - {}",
+                    url,
+                    response.status,
+                    response
+                        .status
+                        .canonical_reason()
+                        .unwrap_or("Unknown Error")
+                );
+                if response.status.as_u16() == 429 || response.status.is_server_error() {
+                    return Err(RetryableError(message).into());
+                }
+                return Err(anyhow!(message));
+            }
+            Err(e) => {
+                let message = format!("Failed to make the request to {}: {:#}", url, e);
+                if is_retryable(&e) {
+                    return Err(RetryableError(message).into());
                 }
+                return Err(anyhow!(message));
             }
-            Err(e) => format!("Failed to make the request to {}: {}", url, e),
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .insert(url.to_string(), markdown.clone());
+        Ok(markdown)
+    }
+}
+
+/// Splits the text into pages of at most `max_chars` characters, preferring to break at a newline.
+///
+/// Returns the byte range of each page.
+pub fn split_pages(text: &str, max_chars: usize) -> Vec<Range<usize>> {
+    let max_chars = max_chars.max(1);
+    let mut pages = Vec::new();
+    let mut start = 0;
+    while start < text.len() {
+        let rest = &text[start..];
+        let end = match rest.char_indices().nth(max_chars) {
+            None => text.len(),
+            Some((limit, _)) => {
+                // Break at the last newline in the second half of the page, if there is one.
+                let window = &rest[..limit];
+                match window.rfind('\n') {
+                    Some(newline) if newline + 1 >= limit / 2 => start + newline + 1,
+                    _ => start + limit,
+                }
+            }
+        };
+        pages.push(start..end);
+        start = end;
+    }
+    pages
+}
+
+/// Finds the keyword in the text, ignoring case, and returns each match with its surrounding context.
+///
+/// Each match is returned with the number of the page it is on.
+pub fn find_in_page(text: &str, keyword: &str, max_chars: usize) -> Vec<(usize, String)> {
+    let keyword = keyword.trim().to_lowercase();
+    if keyword.is_empty() {
+        return Vec::new();
+    }
+    // Lowercasing can change byte lengths, so search char by char on the original text.
+    let chars = text.char_indices().collect::<Vec<_>>();
+    let lowered = chars
+        .iter()
+        .map(|(_, c)| c.to_lowercase().next().unwrap_or(*c))
+        .collect::<Vec<_>>();
+    let needle = keyword.chars().collect::<Vec<_>>();
+    let pages = split_pages(text, max_chars);
+
+    let mut matches = Vec::new();
+    let mut i = 0;
+    while i + needle.len() <= lowered.len() && matches.len() < MAX_FIND_MATCHES {
+        if lowered[i..i + needle.len()] != needle[..] {
+            i += 1;
+            continue;
         }
+        let byte_offset = chars[i].0;
+        let page = pages
+            .iter()
+            .position(|range| range.contains(&byte_offset))
+            .unwrap_or(0)
+            + 1;
+        let context_start = chars[i.saturating_sub(FIND_CONTEXT_CHARS)].0;
+        let context_end = chars
+            .get(i + needle.len() + FIND_CONTEXT_CHARS)
+            .map_or(text.len(), |(offset, _)| *offset);
+        matches.push((page, text[context_start..context_end].trim().to_string()));
+        // Skip past the context so overlapping matches are not repeated.
+        i += needle.len() + FIND_CONTEXT_CHARS;
     }
+    matches
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -68,6 +342,14 @@ impl VisitWebsiteTool {
 pub struct VisitWebsiteToolParams {
     #[schemars(description = "The url of the website to visit")]
     url: String,
+    #[schemars(description = "Optionally the page of the content to read, starting at 1")]
+    page: Option<usize>,
+    #[schemars(description = "Optionally the number of characters per page, at most 30000")]
+    max_chars: Option<usize>,
+    #[schemars(
+        description = "Optionally a keyword to search for in the page. Returns the matches with surrounding context instead of a page"
+    )]
+    find: Option<String>,
 }
 
 impl Tool for VisitWebsiteTool {
@@ -80,17 +362,273 @@ impl Tool for VisitWebsiteTool {
         self.tool.description
     }
 
+    fn cacheable(&self) -> bool {
+        true
+    }
+
     fn forward(&self, arguments: VisitWebsiteToolParams) -> Result<String> {
         let url = arguments.url;
-        Ok(self.forward(&url))
+        // Failures are errors, so that they are retried by policies and never cached.
+        let markdown = self.fetch(&url)?;
+        let max_chars = arguments
+            .max_chars
+            .unwrap_or(DEFAULT_PAGE_CHARS)
+            .clamp(1, MAX_PAGE_CHARS);
+
+        if let Some(keyword) = arguments.find {
+            let matches = find_in_page(&markdown, &keyword, max_chars);
+            if matches.is_empty() {
+                return Ok(format!("No matches found for '{}' in {}.", keyword, url));
+            }
+            let matches = matches
+                .iter()
+                .enumerate()
+                .map(|(i, (page, context))| {
+                    format!("Match {} (page {}):\n...{}...", i + 1, page, context)
+                })
+                .collect::<Vec<_>>();
+            return Ok(format!(
+                "Found {} matches for '{}' in {}:\n\n{}",
+                matches.len(),
+                keyword,
+                url,
+                matches.join("\n\n")
+            ));
+        }
+
+        let pages = split_pages(&markdown, max_chars);
+        let page = arguments.page.unwrap_or(1).max(1);
+        if pages.len() <= 1 && page == 1 {
+            return Ok(markdown);
+        }
+        let Some(range) = pages.get(page - 1) else {
+            return Ok(format!(
+                "Page {} does not exist. {} has {} pages.",
+                page,
+                url,
+                pages.len()
+            ));
+        };
+        let mut output = format!(
+            "Page {} of {} of {}:\n\n{}",
+            page,
+            pages.len(),
+            url,
+            &markdown[range.clone()]
+        );
+        if page < pages.len() {
+            output.push_str(&format!(
+                "\n\n[Call visit_website with page={} to read more.]",
+                page + 1
+            ));
+        }
+        Ok(output)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::retry::RetryPolicy;
+    use crate::test_utils::MockServer;
+    use crate::tools::{AnyTool, MemoryCache, PolicyTool, ToolCache, ToolPolicy, WebClientConfig};
+
+    #[test]
+    fn test_split_pages() {
+        let text = "first line\nsecond line\nthird";
+        let pages = split_pages(text, 15);
+        let pages = pages
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(pages, vec!["first line\n", "second line\n", "third"]);
+        assert_eq!(pages.concat(), text);
+
+        let text = "ééééé";
+        assert_eq!(split_pages(text, 2).len(), 3);
+        assert!(split_pages("", 10).is_empty());
+    }
+
+    #[test]
+    fn test_find_in_page() {
+        let text = format!("{}Rust is fast.{}", "a".repeat(300), "b".repeat(300));
+        let matches = find_in_page(&text, "RUST", 250);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].0, 2);
+        assert!(matches[0].1.contains("Rust is fast."));
+        assert_eq!(matches[0].1.len(), 200 + "Rust".len() + 200);
+        assert!(find_in_page(&text, "python", 250).is_empty());
+    }
+
+    #[test]
+    fn test_visit_website_tool_pages_and_cache() {
+        let body = format!("<p>{}</p><p>{} keyword</p>", "a".repeat(40), "b".repeat(40));
+        let server = MockServer::start(vec![MockServer::response(
+            200,
+            &[("Content-Type", "text/html")],
+            &body,
+        )]);
+        let tool = VisitWebsiteTool::new();
+        let params = |page: Option<usize>, find: Option<&str>| VisitWebsiteToolParams {
+            url: server.url(),
+            page,
+            max_chars: Some(50),
+            find: find.map(str::to_string),
+        };
+
+        let first = Tool::forward(&tool, params(None, None)).unwrap();
+        assert!(first.starts_with("Page 1 of 2"));
+        assert!(first.contains(&"a".repeat(40)));
+        assert!(first.ends_with("[Call visit_website with page=2 to read more.]"));
+
+        // The server only answers once, so these are served from the cache.
+        let second = Tool::forward(&tool.clone(), params(Some(2), None)).unwrap();
+        assert!(second.starts_with("Page 2 of 2"));
+        assert!(second.contains("keyword"));
+        let missing = Tool::forward(&tool, params(Some(3), None)).unwrap();
+        assert!(missing.contains("has 2 pages"));
+        let found = Tool::forward(&tool, params(None, Some("keyword"))).unwrap();
+        assert!(found.starts_with("Found 1 matches for 'keyword'"));
+        assert!(found.contains("Match 1 (page 2)"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn test_visit_website_tool_cache_limits() {
+        let page = |text: &str| MockServer::response(200, &[("Content-Type", "text/plain")], text);
+        let server =
+            MockServer::start(vec![page("a"), page("b"), page("a again"), page("b again")]);
+        let url = |path: &str| format!("{}/{}", server.url(), path);
+
+        // Only the most recently read page is kept.
+        let tool = VisitWebsiteTool::new().with_page_cache(1, DEFAULT_PAGE_TTL);
+        assert_eq!(tool.forward(&url("a")), "a");
+        assert_eq!(tool.forward(&url("b")), "b");
+        assert_eq!(tool.forward(&url("b")), "b");
+        assert_eq!(tool.forward(&url("a")), "a again");
+
+        // Expired pages are fetched again.
+        let tool = VisitWebsiteTool::new().with_page_cache(10, Duration::ZERO);
+        assert_eq!(tool.forward(&url("b")), "b again");
+        assert_eq!(server.requests().len(), 4);
+    }
+
+    #[test]
+    fn test_visit_website_tool_failures_are_not_cached() {
+        let server = MockServer::start(vec![
+            MockServer::response(503, &[], "busy"),
+            MockServer::response(200, &[("Content-Type", "text/plain")], "back"),
+        ]);
+        let cached = ToolCache::new(MemoryCache::new()).wrap(Box::new(VisitWebsiteTool::new()));
+        let error = cached
+            .forward_json(serde_json::json!({"url": server.url()}))
+            .unwrap_err();
+        assert!(error
+            .message()
+            .contains("HTTP 503 Service Unavailable - Service Unavailable"));
+        assert_eq!(
+            cached
+                .forward_json(serde_json::json!({"url": server.url()}))
+                .unwrap(),
+            "back"
+        );
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn test_visit_website_tool_policy_retries() {
+        let server = MockServer::start(vec![
+            MockServer::response(503, &[], "busy"),
+            MockServer::response(200, &[("Content-Type", "text/plain")], "back"),
+            MockServer::response(404, &[], "missing"),
+        ]);
+        let policy = ToolPolicy::new()
+            .with_retry_policy(RetryPolicy::new(2).initial_backoff(Duration::from_millis(1)))
+            .with_failure_threshold(Some(1));
+        let tool = PolicyTool::new(VisitWebsiteTool::new(), policy);
+        assert_eq!(
+            tool.forward_json(serde_json::json!({"url": server.url()}))
+                .unwrap(),
+            "back"
+        );
+        assert_eq!(server.requests().len(), 2);
+
+        // Pages that do not exist are not retried, and trip the circuit breaker.
+        tool.tool().clear_cache();
+        let error = tool
+            .forward_json(serde_json::json!({"url": server.url()}))
+            .unwrap_err();
+        assert!(error.message().contains("HTTP 404 Not Found"));
+        assert!(error.message().contains("disabled for the rest of the run"));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[test]
+    fn test_visit_website_tool_main_content() {
+        let paragraph = "Editions are opt-in, so existing crates keep compiling with the edition they declare, and migrating is mostly automatic.";
+        let body = format!(
+            "<html><head><title>Rust 2024</title><meta name='author' content='Jane Doe'></head><body><div class='cookie-banner'><p>Please accept all of our cookies to continue reading.</p></div><article><p>{0}</p><p>{0}</p></article></body></html>",
+            paragraph
+        );
+        let server = MockServer::start(vec![MockServer::response(200, &[], &body)]);
+        let tool = VisitWebsiteTool::new().with_main_content_extraction(true);
+        let markdown = tool.forward(&server.url());
+        assert!(markdown.starts_with("Title: Rust 2024\nByline: Jane Doe"));
+        assert!(markdown.contains(paragraph));
+        assert!(!markdown.contains("cookies"));
+    }
+
+    #[test]
+    fn test_visit_website_tool_documents() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[("Content-Type", "application/json")], r#"{"a":1}"#),
+            MockServer::response(200, &[("Content-Type", "text/csv")], "x,y\n1,2\n"),
+            MockServer::response(200, &[("Content-Type", "text/plain")], "<p>plain</p>"),
+            MockServer::response(200, &[("Content-Type", "image/png")], "\u{1}PNG"),
+        ]);
+        let tool = VisitWebsiteTool::new();
+        let url = |path: &str| format!("{}/{}", server.url(), path);
+        assert_eq!(tool.forward(&url("data")), "```json\n{\n  \"a\": 1\n}\n```");
+        assert_eq!(
+            tool.forward(&url("table")),
+            "| x | y |\n| --- | --- |\n| 1 | 2 |"
+        );
+        assert_eq!(tool.forward(&url("notes")), "<p>plain</p>");
+        let image = tool.forward(&url("logo"));
+        assert!(image.contains("binary file of type image/png (4 bytes)"));
+    }
+
+    #[test]
+    fn test_visit_website_tool_charsets() {
+        let tool = VisitWebsiteTool::new();
+        let url = Url::parse("https://example.com/notes.txt").unwrap();
+        assert_eq!(
+            tool.to_text(
+                &url,
+                Some("text/plain; charset=windows-1252"),
+                b"\x93hi\x94"
+            ),
+            Ok("“hi”".to_string())
+        );
+        let html =
+            b"<html><head><meta charset='iso-8859-1'></head><body><p>caf\xe9</p></body></html>";
+        assert_eq!(
+            tool.to_text(&url, Some("text/html"), html),
+            Ok("café".to_string())
+        );
+    }
+
+    #[test]
+    fn test_visit_website_tool_web_client() {
+        let client =
+            WebClient::new(WebClientConfig::new().with_allowed_domains(&["example.com"])).unwrap();
+        let tool = VisitWebsiteTool::new().with_web_client(client);
+        let message = tool.forward("http://127.0.0.1:1/page");
+        assert!(message.contains("Access to 127.0.0.1 is not in the domain allow list"));
+    }
 
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn test_visit_website_tool() {
         let tool = VisitWebsiteTool::new();
         let url = "https://finance.yahoo.com/quote/NVDA";
@@ -0,0 +1,595 @@
+//! This module contains the HTTP client shared by the web tools.
+//!
+//! A [`WebClientConfig`] sets the user agent, proxy, timeouts, maximum response size and redirect policy,
+//! restricts the domains the tools may access and optionally makes them respect robots.txt.
+//! Pass a [`WebClient`] built from it to the search and visit website tools with `with_web_client`.
+
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use encoding_rs::{Encoding, UTF_8};
+use regex::bytes::Regex;
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::HeaderMap;
+use reqwest::redirect::Policy;
+use reqwest::{IntoUrl, Method, StatusCode, Url};
+
+use anyhow::{anyhow, Result};
+
+/// The user agent sent by default. Some websites block requests without a browser user agent.
+pub const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36";
+
+#[derive(Debug, Clone)]
+pub struct WebClientConfig {
+    pub user_agent: String,
+    /// An HTTP, HTTPS or SOCKS5 proxy url, e.g. `http://proxy.corp:8080` or `socks5://127.0.0.1:1080`.
+    /// Without a proxy, the `HTTP_PROXY` and `HTTPS_PROXY` environment variables are used.
+    pub proxy: Option<String>,
+    pub connect_timeout: Option<Duration>,
+    /// The timeout of the whole request, including reading the response.
+    pub timeout: Option<Duration>,
+    /// The largest response body read, in bytes. Larger responses are an error.
+    pub max_response_bytes: Option<usize>,
+    /// The number of redirects followed. With 0, redirect responses are returned as they are.
+    pub max_redirects: usize,
+    /// If not empty, only these domains and their subdomains can be accessed.
+    pub allowed_domains: Vec<String>,
+    /// These domains and their subdomains can never be accessed. The deny list wins over the allow list.
+    pub denied_domains: Vec<String>,
+    /// Whether to check the robots.txt of a site before fetching a page from it.
+    pub respect_robots_txt: bool,
+}
+
+impl Default for WebClientConfig {
+    fn default() -> Self {
+        Self {
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            proxy: None,
+            connect_timeout: Some(Duration::from_secs(10)),
+            timeout: Some(Duration::from_secs(30)),
+            max_response_bytes: Some(10 * 1024 * 1024),
+            max_redirects: 10,
+            allowed_domains: Vec::new(),
+            denied_domains: Vec::new(),
+            respect_robots_txt: false,
+        }
+    }
+}
+
+impl WebClientConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_proxy(mut self, proxy: &str) -> Self {
+        self.proxy = Some(proxy.to_string());
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn with_max_response_bytes(mut self, max_response_bytes: usize) -> Self {
+        self.max_response_bytes = Some(max_response_bytes);
+        self
+    }
+
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn with_allowed_domains(mut self, domains: &[&str]) -> Self {
+        self.allowed_domains = domains.iter().map(|domain| domain.to_string()).collect();
+        self
+    }
+
+    pub fn with_denied_domains(mut self, domains: &[&str]) -> Self {
+        self.denied_domains = domains.iter().map(|domain| domain.to_string()).collect();
+        self
+    }
+
+    pub fn with_robots_txt(mut self, respect_robots_txt: bool) -> Self {
+        self.respect_robots_txt = respect_robots_txt;
+        self
+    }
+
+    /// Checks the host of the url against the domain allow and deny lists.
+    pub fn check_domain(&self, url: &Url) -> Result<()> {
+        let host = url.host_str().unwrap_or_default().to_lowercase();
+        if self
+            .denied_domains
+            .iter()
+            .any(|domain| domain_matches(&host, domain))
+        {
+            return Err(anyhow!(
+                "Access to {} is blocked by the domain deny list",
+                host
+            ));
+        }
+        if !self.allowed_domains.is_empty()
+            && !self
+                .allowed_domains
+                .iter()
+                .any(|domain| domain_matches(&host, domain))
+        {
+            return Err(anyhow!(
+                "Access to {} is not in the domain allow list",
+                host
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Whether the host is the domain or one of its subdomains. A leading `*.` or `.` in the domain is ignored.
+pub(crate) fn domain_matches(host: &str, domain: &str) -> bool {
+    let domain = domain
+        .trim()
+        .trim_start_matches("*.")
+        .trim_start_matches('.')
+        .to_lowercase();
+    !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+}
+
+/// A response read in full by the [`WebClient`].
+#[derive(Debug, Clone)]
+pub struct WebResponse {
+    /// The final url, after redirects.
+    pub url: Url,
+    pub status: StatusCode,
+    pub content_type: Option<String>,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+}
+
+impl WebResponse {
+    /// The body decoded with the charset of the response, see [`decode_text`].
+    pub fn text(&self) -> String {
+        decode_text(&self.body, self.content_type.as_deref())
+    }
+}
+
+/// Decodes a response body with the charset of its `Content-Type`, its byte order mark or the `<meta charset>` of an
+/// HTML page, in that order, falling back to UTF-8. Invalid sequences are replaced.
+pub fn decode_text(bytes: &[u8], content_type: Option<&str>) -> String {
+    let encoding = content_type
+        .and_then(content_type_charset)
+        .and_then(|charset| Encoding::for_label(charset.as_bytes()))
+        .or_else(|| Encoding::for_bom(bytes).map(|(encoding, _)| encoding))
+        .or_else(|| meta_charset(bytes))
+        .unwrap_or(UTF_8);
+    let (text, _, _) = encoding.decode(bytes);
+    text.into_owned()
+}
+
+/// The `charset` parameter of a `Content-Type` header.
+fn content_type_charset(content_type: &str) -> Option<String> {
+    content_type.split(';').skip(1).find_map(|parameter| {
+        let (name, value) = parameter.split_once('=')?;
+        name.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The charset declared by a `<meta>` tag in the first kilobytes of an HTML page.
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+    let head = &bytes[..bytes.len().min(4096)];
+    let pattern = Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?([a-z0-9_:.-]+)"#).ok()?;
+    let label = pattern.captures(head)?.get(1)?.as_bytes();
+    Encoding::for_label(label)
+}
+
+/// An HTTP client that applies a [`WebClientConfig`] to every request. Clones share the connection pool
+/// and the robots.txt cache.
+#[derive(Debug, Clone)]
+pub struct WebClient {
+    config: WebClientConfig,
+    client: Client,
+    /// The robots.txt rules for our user agent, keyed by origin.
+    robots: Arc<Mutex<HashMap<String, RobotsTxt>>>,
+}
+
+impl Default for WebClient {
+    fn default() -> Self {
+        WebClient::new(WebClientConfig::default()).expect("the default web client config is valid")
+    }
+}
+
+impl WebClient {
+    pub fn new(config: WebClientConfig) -> Result<Self> {
+        let mut builder = Client::builder().user_agent(config.user_agent.clone());
+        if let Some(proxy) = &config.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| anyhow!("Invalid proxy url {}: {}", proxy, e))?;
+            builder = builder.proxy(proxy);
+        }
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        builder = builder.timeout(config.timeout);
+
+        let redirect_config = config.clone();
+        builder = builder.redirect(Policy::custom(move |attempt| {
+            if redirect_config.max_redirects == 0 {
+                attempt.stop()
+            } else if attempt.previous().len() > redirect_config.max_redirects {
+                attempt.error(format!(
+                    "Too many redirects, the limit is {}",
+                    redirect_config.max_redirects
+                ))
+            } else if let Err(e) = redirect_config.check_domain(attempt.url()) {
+                let message = format!("Redirect to {} refused: {}", attempt.url(), e);
+                attempt.error(message)
+            } else {
+                attempt.follow()
+            }
+        }));
+
+        Ok(Self {
+            config,
+            client: builder.build()?,
+            robots: Arc::default(),
+        })
+    }
+
+    pub fn config(&self) -> &WebClientConfig {
+        &self.config
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.get(url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client.post(url)
+    }
+
+    pub fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Checks that the url can be accessed under the domain lists and, if enabled, robots.txt.
+    pub fn check_url(&self, url: &Url) -> Result<()> {
+        self.config.check_domain(url)?;
+        if self.config.respect_robots_txt && !self.robots_txt(url).is_allowed(&robots_path(url)) {
+            return Err(anyhow!(
+                "Fetching {} is disallowed by the site's robots.txt",
+                url
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the url of the request, sends it and reads the response, up to the maximum size.
+    pub fn send(&self, request: RequestBuilder) -> Result<WebResponse> {
+        let request = request.build()?;
+        self.check_url(request.url())?;
+        let mut response = self.client.execute(request)?;
+
+        let url = response.url().clone();
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+        let headers = response.headers().clone();
+        let mut body = Vec::new();
+        match self.config.max_response_bytes {
+            Some(max_bytes) => {
+                let too_large = || {
+                    anyhow!(
+                        "The response from {} is larger than the limit of {} bytes",
+                        url,
+                        max_bytes
+                    )
+                };
+                if response
+                    .content_length()
+                    .is_some_and(|length| length > max_bytes as u64)
+                {
+                    return Err(too_large());
+                }
+                (&mut response)
+                    .take(max_bytes as u64 + 1)
+                    .read_to_end(&mut body)?;
+                if body.len() > max_bytes {
+                    return Err(too_large());
+                }
+            }
+            None => {
+                response.read_to_end(&mut body)?;
+            }
+        }
+        Ok(WebResponse {
+            url,
+            status,
+            content_type,
+            headers,
+            body,
+        })
+    }
+
+    /// Returns the robots.txt rules of the url's site, fetching them on first use.
+    ///
+    /// A missing or unreadable robots.txt allows everything.
+    fn robots_txt(&self, url: &Url) -> RobotsTxt {
+        let origin = url.origin().ascii_serialization();
+        if let Some(robots) = self.robots.lock().unwrap().get(&origin) {
+            return robots.clone();
+        }
+        let robots = self
+            .client
+            .get(format!("{}/robots.txt", origin))
+            .send()
+            .ok()
+            .filter(|response| response.status().is_success())
+            .and_then(|response| response.text().ok())
+            .map(|text| RobotsTxt::parse(&text, &self.config.user_agent))
+            .unwrap_or_default();
+        self.robots.lock().unwrap().insert(origin, robots.clone());
+        robots
+    }
+}
+
+/// The path and query of the url, as matched by robots.txt rules.
+fn robots_path(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
+
+/// Whether a robots.txt rule allows, and its path pattern.
+type RobotsRule = (bool, String);
+
+/// The allow and disallow rules of a robots.txt that apply to one user agent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsTxt {
+    rules: Vec<RobotsRule>,
+}
+
+impl RobotsTxt {
+    /// Parses the rules of the groups that name the user agent, or of the `*` group if none does.
+    pub fn parse(text: &str, user_agent: &str) -> Self {
+        let user_agent = user_agent.to_lowercase();
+        let mut groups: Vec<(Vec<String>, Vec<RobotsRule>)> = Vec::new();
+        let mut in_user_agents = false;
+        for line in text.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let key = key.trim().to_lowercase();
+            let value = value.trim();
+            match key.as_str() {
+                "user-agent" => {
+                    if !in_user_agents {
+                        groups.push((Vec::new(), Vec::new()));
+                    }
+                    if let Some((agents, _)) = groups.last_mut() {
+                        agents.push(value.to_lowercase());
+                    }
+                    in_user_agents = true;
+                }
+                "allow" | "disallow" => {
+                    in_user_agents = false;
+                    // An empty disallow allows everything, so it adds no rule.
+                    if let (Some((_, rules)), false) = (groups.last_mut(), value.is_empty()) {
+                        rules.push((key == "allow", value.to_string()));
+                    }
+                }
+                _ => in_user_agents = false,
+            }
+        }
+
+        let names_user_agent = |agents: &[String]| {
+            agents
+                .iter()
+                .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+        };
+        let specific = groups.iter().any(|(agents, _)| names_user_agent(agents));
+        let rules = groups
+            .into_iter()
+            .filter(|(agents, _)| match specific {
+                true => names_user_agent(agents),
+                false => agents.iter().any(|agent| agent == "*"),
+            })
+            .flat_map(|(_, rules)| rules)
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether the path may be fetched. The longest matching rule wins, and allow wins a tie.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+/// Matches a robots.txt path pattern, where `*` matches any characters and a trailing `$` anchors the end.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let parts = pattern.split('*').collect::<Vec<_>>();
+    let Some(mut rest) = path.strip_prefix(parts[0]) else {
+        return false;
+    };
+    if parts.len() == 1 {
+        return !anchored || rest.is_empty();
+    }
+    let last = parts.len() - 1;
+    for (i, part) in parts.iter().enumerate().skip(1) {
+        if i == last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+
+    #[test]
+    fn test_decode_text() {
+        assert_eq!(
+            decode_text(b"caf\xe9", Some("text/html; charset=ISO-8859-1")),
+            "café"
+        );
+        assert_eq!(
+            decode_text(
+                b"\x93\xfa\x96\x7b",
+                Some("text/plain; charset=\"Shift_JIS\"")
+            ),
+            "日本"
+        );
+        assert_eq!(
+            decode_text(
+                b"<html><head><meta charset=\"windows-1252\"></head><p>\x93quoted\x94</p>",
+                Some("text/html")
+            ),
+            "<html><head><meta charset=\"windows-1252\"></head><p>“quoted”</p>"
+        );
+        assert_eq!(
+            decode_text(
+                b"<meta http-equiv='Content-Type' content='text/html; charset=iso-8859-1'>\xe9",
+                None
+            ),
+            "<meta http-equiv='Content-Type' content='text/html; charset=iso-8859-1'>é"
+        );
+        assert_eq!(decode_text("café".as_bytes(), None), "café");
+        assert_eq!(decode_text(b"caf\xe9", Some("text/plain")), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn test_check_domain() {
+        let config = WebClientConfig::new()
+            .with_allowed_domains(&["example.com", "*.rust-lang.org"])
+            .with_denied_domains(&["private.example.com"]);
+        let check = |url: &str| config.check_domain(&Url::parse(url).unwrap());
+        assert!(check("https://example.com/page").is_ok());
+        assert!(check("https://docs.example.com/page").is_ok());
+        assert!(check("https://doc.rust-lang.org/std").is_ok());
+        assert!(check("https://notexample.com")
+            .unwrap_err()
+            .to_string()
+            .contains("not in the domain allow list"));
+        assert!(check("https://a.private.example.com")
+            .unwrap_err()
+            .to_string()
+            .contains("blocked by the domain deny list"));
+        assert!(WebClientConfig::new()
+            .check_domain(&Url::parse("https://anything.org").unwrap())
+            .is_ok());
+    }
+
+    #[test]
+    fn test_robots_txt() {
+        let text = "# comment\nUser-agent: *\nDisallow: /private\nAllow: /private/public\n\nUser-agent: BadBot\nUser-agent: OtherBot\nDisallow: /\n\nUser-agent: *\nDisallow: /*.pdf$\n";
+        let robots = RobotsTxt::parse(text, "Mozilla/5.0 (compatible; MyTool/1.0)");
+        assert!(robots.is_allowed("/"));
+        assert!(!robots.is_allowed("/private/page"));
+        assert!(robots.is_allowed("/private/public/page"));
+        assert!(!robots.is_allowed("/files/report.pdf"));
+        assert!(robots.is_allowed("/files/report.pdf?download=1"));
+
+        let robots = RobotsTxt::parse(text, "otherbot/2.0");
+        assert!(!robots.is_allowed("/"));
+        assert!(RobotsTxt::parse("User-agent: *\nDisallow:\n", "any").is_allowed("/"));
+    }
+
+    #[test]
+    fn test_web_client_robots_txt_and_size_limit() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], "User-agent: *\nDisallow: /private\n"),
+            MockServer::response(200, &[("Content-Type", "text/plain")], "hello"),
+            MockServer::response(200, &[], "too long"),
+        ]);
+        let client = WebClient::new(
+            WebClientConfig::new()
+                .with_robots_txt(true)
+                .with_max_response_bytes(5),
+        )
+        .unwrap();
+
+        let error = client
+            .send(client.get(format!("{}/private/page", server.url())))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("disallowed by the site's robots.txt"));
+
+        // The robots.txt is cached, so it is only fetched once.
+        let response = client
+            .send(client.get(format!("{}/page", server.url())))
+            .unwrap();
+        assert_eq!(response.text(), "hello");
+        assert_eq!(response.content_type.as_deref(), Some("text/plain"));
+        let error = client
+            .send(client.get(format!("{}/long", server.url())))
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("larger than the limit of 5 bytes"));
+
+        let paths = server
+            .requests()
+            .into_iter()
+            .map(|request| request.path)
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/robots.txt", "/page", "/long"]);
+    }
+
+    #[test]
+    fn test_web_client_redirects_and_user_agent() {
+        let server = MockServer::start(vec![MockServer::response(
+            302,
+            &[("Location", "https://blocked.example/")],
+            "",
+        )]);
+        let client = WebClient::new(
+            WebClientConfig::new()
+                .with_user_agent("test-agent/1.0")
+                .with_denied_domains(&["blocked.example"]),
+        )
+        .unwrap();
+        let error = client.send(client.get(server.url())).unwrap_err();
+        assert!(format!("{:?}", error).contains("blocked by the domain deny list"));
+        assert_eq!(
+            server.requests()[0].header("user-agent"),
+            Some("test-agent/1.0")
+        );
+
+        assert!(WebClient::new(WebClientConfig::new().with_proxy("not a url")).is_err());
+        assert!(
+            WebClient::new(WebClientConfig::new().with_proxy("socks5://127.0.0.1:1080")).is_ok()
+        );
+    }
+}
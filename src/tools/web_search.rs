@@ -0,0 +1,378 @@
+//! This module contains the web search tool. The tool wraps a [`SearchProvider`] so every search
+//! backend returns the same [`SearchResult`]s and the model sees the same result format.
+
+use std::fmt::Debug;
+
+use reqwest::blocking::RequestBuilder;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::output::ToolOutput;
+use super::policy::RetryableError;
+use super::tool_traits::Tool;
+use super::web_client::WebClient;
+use anyhow::{anyhow, Result};
+
+/// A single search result, as returned by every provider.
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct SearchResult {
+    pub title: String,
+    pub snippet: String,
+    pub url: String,
+    /// The publication date, if the provider reports one.
+    pub date: Option<String>,
+    /// The name of the site or engine the result came from, if the provider reports one.
+    pub source: Option<String>,
+}
+
+/// How strictly explicit results are filtered.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SafeSearch {
+    Off,
+    Moderate,
+    Strict,
+}
+
+/// How recent the results must be.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeRange {
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl TimeRange {
+    /// The first letter of the range, as used by the DuckDuckGo and Google recency filters.
+    pub fn letter(&self) -> &'static str {
+        match self {
+            TimeRange::Day => "d",
+            TimeRange::Week => "w",
+            TimeRange::Month => "m",
+            TimeRange::Year => "y",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, JsonSchema, Default)]
+#[schemars(title = "WebSearchToolParams")]
+pub struct SearchQuery {
+    #[schemars(description = "The query to search for")]
+    pub query: String,
+    #[schemars(description = "Optionally restrict results to a certain year")]
+    pub filter_year: Option<String>,
+    #[schemars(description = "Optionally set the maximum number of results to return")]
+    pub count: Option<usize>,
+    #[schemars(description = "Optionally request a later page of results, starting at 1")]
+    pub page: Option<usize>,
+    #[schemars(
+        description = "Optionally prefer results from a country, as a two-letter code such as 'us' or 'de'"
+    )]
+    pub region: Option<String>,
+    #[schemars(
+        description = "Optionally prefer results in a language, as a two-letter code such as 'en' or 'fr'"
+    )]
+    pub language: Option<String>,
+    #[schemars(description = "Optionally set how strictly explicit results are filtered")]
+    pub safe_search: Option<SafeSearch>,
+    #[schemars(
+        description = "Optionally only return results from the past day, week, month or year"
+    )]
+    pub time_range: Option<TimeRange>,
+    #[schemars(
+        description = "Optionally only return results from this site, e.g. 'wikipedia.org'"
+    )]
+    pub site: Option<String>,
+}
+
+impl SearchQuery {
+    pub fn new(query: &str) -> Self {
+        Self {
+            query: query.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_filter_year(mut self, year: &str) -> Self {
+        self.filter_year = Some(year.to_string());
+        self
+    }
+
+    pub fn with_count(mut self, count: usize) -> Self {
+        self.count = Some(count);
+        self
+    }
+
+    pub fn with_page(mut self, page: usize) -> Self {
+        self.page = Some(page);
+        self
+    }
+
+    pub fn with_region(mut self, region: &str) -> Self {
+        self.region = Some(region.to_string());
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = Some(language.to_string());
+        self
+    }
+
+    pub fn with_safe_search(mut self, safe_search: SafeSearch) -> Self {
+        self.safe_search = Some(safe_search);
+        self
+    }
+
+    pub fn with_time_range(mut self, time_range: TimeRange) -> Self {
+        self.time_range = Some(time_range);
+        self
+    }
+
+    pub fn with_site(mut self, site: &str) -> Self {
+        self.site = Some(site.to_string());
+        self
+    }
+
+    /// The query text, restricted to the site if one is set.
+    pub fn query_text(&self) -> String {
+        match &self.site {
+            Some(site) => format!("site:{} {}", site, self.query),
+            None => self.query.clone(),
+        }
+    }
+
+    /// The query text with the year appended, for providers that cannot filter by date.
+    pub fn query_with_year(&self) -> String {
+        match &self.filter_year {
+            Some(year) => format!("{} {}", self.query_text(), year),
+            None => self.query_text(),
+        }
+    }
+
+    /// The index of the first result on the requested page, given the page size.
+    pub fn offset(&self, page_size: usize) -> usize {
+        self.page.unwrap_or(1).saturating_sub(1) * page_size
+    }
+}
+
+/// A search backend such as DuckDuckGo or Bing.
+pub trait SearchProvider: Debug {
+    /// The name of the tool that wraps this provider, e.g. `duckduckgo_search`.
+    fn name(&self) -> &'static str;
+    /// The description of the tool that wraps this provider.
+    fn description(&self) -> &'static str;
+    /// Searches using the client, so the tool's web client config applies to the request.
+    fn search(&self, query: &SearchQuery, client: &WebClient) -> Result<Vec<SearchResult>>;
+}
+
+/// A tool that searches the web with any [`SearchProvider`].
+#[derive(Debug, Serialize, Clone)]
+pub struct WebSearchTool<P> {
+    pub tool: BaseTool,
+    #[serde(skip)]
+    pub provider: P,
+    #[serde(skip)]
+    pub client: WebClient,
+}
+
+impl<P: SearchProvider> WebSearchTool<P> {
+    pub fn with_provider(provider: P) -> Self {
+        WebSearchTool {
+            tool: BaseTool {
+                name: provider.name(),
+                description: provider.description(),
+            },
+            provider,
+            client: WebClient::default(),
+        }
+    }
+
+    /// Sends the searches with this client instead of the default one.
+    pub fn with_web_client(mut self, client: WebClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Searches with the provider, keeping at most `query.count` results.
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        let mut results = self.provider.search(query, &self.client)?;
+        if let Some(count) = query.count {
+            results.truncate(count);
+        }
+        Ok(results)
+    }
+}
+
+impl<P: SearchProvider + Clone> Tool for WebSearchTool<P> {
+    type Params = SearchQuery;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn cacheable(&self) -> bool {
+        true
+    }
+    fn forward(&self, arguments: SearchQuery) -> Result<String> {
+        let results = self.search(&arguments)?;
+        Ok(format_search_results(&arguments, &results))
+    }
+    /// Returns the results as a list of objects, so the code agent can index them, while the model is shown the
+    /// same markdown as [`Tool::forward`]. An empty result is explained in text.
+    fn forward_output(&self, arguments: SearchQuery) -> Result<ToolOutput> {
+        let results = self.search(&arguments)?;
+        let text = format_search_results(&arguments, &results);
+        if results.is_empty() {
+            return Ok(ToolOutput::Text(text));
+        }
+        Ok(ToolOutput::JsonWithText {
+            json: serde_json::to_value(results)?,
+            text,
+        })
+    }
+}
+
+/// Formats the results as the markdown list shown to the model.
+pub fn format_search_results(query: &SearchQuery, results: &[SearchResult]) -> String {
+    if results.is_empty() {
+        let filter = match &query.filter_year {
+            Some(_) => ", or remove the year filter",
+            None => "",
+        };
+        return format!(
+            "No results found for '{}'. Try with a more general query{}.",
+            query.query, filter
+        );
+    }
+    let web_snippets = results
+        .iter()
+        .enumerate()
+        .map(|(idx, result)| {
+            let date_published = result
+                .date
+                .as_ref()
+                .map_or("".to_string(), |d| format!("\nDate published: {}", d));
+            let source = result
+                .source
+                .as_ref()
+                .map_or("".to_string(), |s| format!("\nSource: {}", s));
+            format!(
+                "{}. [{}]({}){}{}\n{}",
+                idx, result.title, result.url, date_published, source, result.snippet
+            )
+        })
+        .collect::<Vec<_>>();
+    format!("## Search Results\n{}", web_snippets.join("\n\n"))
+}
+
+/// Sends a search request and returns the response body, failing on non-success statuses.
+pub(crate) fn fetch(client: &WebClient, request: RequestBuilder) -> Result<String> {
+    let response = client.send(request)?;
+    if !response.status.is_success() {
+        let message = format!(
+            "Failed to fetch search results: HTTP {}, Error: {}",
+            response.status,
+            response.text()
+        );
+        if response.status.as_u16() == 429 || response.status.is_server_error() {
+            return Err(RetryableError(message).into());
+        }
+        return Err(anyhow!(message));
+    }
+    Ok(response.text())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tools::AnyTool;
+    use serde_json::json;
+
+    #[test]
+    fn test_search_query_params() {
+        let query: SearchQuery = serde_json::from_value(json!({
+            "query": "rust",
+            "page": 3,
+            "safe_search": "strict",
+            "time_range": "week",
+            "site": "rust-lang.org"
+        }))
+        .unwrap();
+        assert_eq!(query.safe_search, Some(SafeSearch::Strict));
+        assert_eq!(query.time_range, Some(TimeRange::Week));
+        assert_eq!(query.query_text(), "site:rust-lang.org rust");
+        assert_eq!(query.offset(10), 20);
+        assert_eq!(SearchQuery::new("rust").offset(10), 0);
+    }
+
+    #[derive(Debug, Clone)]
+    struct StaticProvider(Vec<SearchResult>);
+
+    impl SearchProvider for StaticProvider {
+        fn name(&self) -> &'static str {
+            "static_search"
+        }
+        fn description(&self) -> &'static str {
+            "Returns fixed results."
+        }
+        fn search(&self, _query: &SearchQuery, _client: &WebClient) -> Result<Vec<SearchResult>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[test]
+    fn test_web_search_tool_formats_results() {
+        let tool = WebSearchTool::with_provider(StaticProvider(vec![SearchResult {
+            title: "Paris".to_string(),
+            snippet: "Paris is the capital of France.".to_string(),
+            url: "https://en.wikipedia.org/wiki/Paris".to_string(),
+            date: Some("2024".to_string()),
+            source: None,
+        }]));
+        assert_eq!(Tool::name(&tool), "static_search");
+        let output = Tool::forward(&tool, SearchQuery::new("capital of France")).unwrap();
+        assert_eq!(
+            output,
+            "## Search Results\n0. [Paris](https://en.wikipedia.org/wiki/Paris)\nDate published: 2024\nParis is the capital of France."
+        );
+
+        let results = tool
+            .search(&SearchQuery::new("capital of France").with_count(0))
+            .unwrap();
+        assert!(results.is_empty());
+
+        let output = Tool::forward_output(&tool, SearchQuery::new("capital of France")).unwrap();
+        assert_eq!(
+            output,
+            ToolOutput::JsonWithText {
+                json: json!([{
+                    "title": "Paris",
+                    "snippet": "Paris is the capital of France.",
+                    "url": "https://en.wikipedia.org/wiki/Paris",
+                    "date": "2024",
+                    "source": null
+                }]),
+                text: Tool::forward(&tool, SearchQuery::new("capital of France")).unwrap(),
+            }
+        );
+        // Function calling agents are shown the same markdown as before.
+        assert_eq!(
+            AnyTool::forward_json(&tool, json!({"query": "capital of France"})).unwrap(),
+            Tool::forward(&tool, SearchQuery::new("capital of France")).unwrap()
+        );
+
+        let tool = WebSearchTool::with_provider(StaticProvider(vec![]));
+        let output =
+            Tool::forward(&tool, SearchQuery::new("nothing").with_filter_year("1900")).unwrap();
+        assert!(output.starts_with("No results found for 'nothing'"));
+        assert!(output.contains("remove the year filter"));
+        assert!(matches!(
+            Tool::forward_output(&tool, SearchQuery::new("nothing")).unwrap(),
+            ToolOutput::Text(_)
+        ));
+    }
+}
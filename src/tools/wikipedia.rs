@@ -0,0 +1,502 @@
+//! This module contains the Wikipedia tool. The model uses this tool to read encyclopedia articles.
+//!
+//! The tool searches and reads articles with the MediaWiki action API, so it works with Wikipedia in any language
+//! and with any other MediaWiki wiki, including one hosted locally:
+//!
+//! ```rust
+//! use smolagents_rs::tools::WikipediaTool;
+//!
+//! let wikipedia = WikipediaTool::new().with_language("fr");
+//! let local_wiki = WikipediaTool::new().with_api_url("http://localhost:8080/w/api.php");
+//! ```
+
+use htmd::HtmlToMarkdown;
+use regex::Regex;
+use reqwest::Url;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::base::BaseTool;
+use super::tool_traits::Tool;
+use super::web_client::WebClient;
+use anyhow::{anyhow, Result};
+
+/// The API of Wikipedia. `{language}` is replaced by the language of the request.
+pub const WIKIPEDIA_API_URL: &str = "https://{language}.wikipedia.org/w/api.php";
+/// The default number of characters of an article section shown to the model.
+pub const DEFAULT_WIKI_MAX_CHARS: usize = 10_000;
+/// The number of search results fetched to pick the article.
+const SEARCH_LIMIT: usize = 5;
+
+#[derive(Debug, Deserialize)]
+struct SearchResponse {
+    query: SearchResults,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchResults {
+    search: Vec<SearchHit>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchHit {
+    title: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParseResponse {
+    parse: ParsedPage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParsedPage {
+    title: String,
+    #[serde(default)]
+    sections: Vec<WikiSection>,
+    #[serde(default)]
+    text: String,
+}
+
+/// A section of an article, as listed by the MediaWiki API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WikiSection {
+    /// The title of the section, which may contain HTML.
+    pub line: String,
+    /// The position of the section in the outline, e.g. `2.1`.
+    pub number: String,
+    /// The index of the section used to fetch its text.
+    pub index: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct WikipediaTool {
+    pub tool: BaseTool,
+    /// The URL of the wiki's `api.php`, where `{language}` is replaced by the language.
+    pub api_url: String,
+    /// The language used when the model does not choose one.
+    pub language: String,
+    /// The largest number of characters of a summary or section shown to the model.
+    pub max_chars: usize,
+    #[serde(skip)]
+    client: WebClient,
+}
+
+impl Default for WikipediaTool {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WikipediaTool {
+    pub fn new() -> Self {
+        WikipediaTool {
+            tool: BaseTool {
+                name: "wikipedia",
+                description: "Looks up an article on Wikipedia and returns its summary and the list of its sections, or the text of one section. Use this for factual questions about people, places, events and concepts.",
+            },
+            api_url: WIKIPEDIA_API_URL.to_string(),
+            language: "en".to_string(),
+            max_chars: DEFAULT_WIKI_MAX_CHARS,
+            client: WebClient::default(),
+        }
+    }
+
+    /// Reads another MediaWiki wiki, e.g. `http://localhost:8080/w/api.php`.
+    pub fn with_api_url(mut self, api_url: &str) -> Self {
+        self.api_url = api_url.to_string();
+        self
+    }
+
+    pub fn with_language(mut self, language: &str) -> Self {
+        self.language = language.to_string();
+        self
+    }
+
+    pub fn with_max_chars(mut self, max_chars: usize) -> Self {
+        self.max_chars = max_chars;
+        self
+    }
+
+    /// Sends the requests with this client instead of the default one.
+    pub fn with_web_client(mut self, client: WebClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// The API url for the language.
+    fn api(&self, language: &str) -> Result<Url> {
+        let valid = !language.is_empty()
+            && language
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(anyhow!("Invalid language code: {}", language));
+        }
+        let url = self.api_url.replace("{language}", &language.to_lowercase());
+        Url::parse(&url).map_err(|e| anyhow!("Invalid wiki API url {}: {}", url, e))
+    }
+
+    /// Sends an API request and parses its result, reporting API errors such as a missing page.
+    fn query<T: DeserializeOwned>(&self, language: &str, params: &[(&str, &str)]) -> Result<T> {
+        let mut url = self.api(language)?;
+        url.query_pairs_mut()
+            .extend_pairs(params)
+            .append_pair("format", "json")
+            .append_pair("formatversion", "2");
+        let response = self.client.send(self.client.get(url))?;
+        if !response.status.is_success() {
+            return Err(anyhow!(
+                "Failed to query the wiki: HTTP {}: {}",
+                response.status,
+                response.text()
+            ));
+        }
+        let json = serde_json::from_slice::<serde_json::Value>(&response.body)
+            .map_err(|e| anyhow!("Failed to parse the wiki response: {}", e))?;
+        if let Some(info) = json["error"]["info"].as_str() {
+            return Err(anyhow!("The wiki returned an error: {}", info));
+        }
+        serde_json::from_value(json).map_err(|e| anyhow!("Unexpected wiki response: {}", e))
+    }
+
+    /// The titles of the articles matching the query, best first.
+    pub fn search(&self, language: &str, query: &str) -> Result<Vec<String>> {
+        let limit = SEARCH_LIMIT.to_string();
+        let response = self.query::<SearchResponse>(
+            language,
+            &[
+                ("action", "query"),
+                ("list", "search"),
+                ("srsearch", query),
+                ("srlimit", &limit),
+                ("srprop", ""),
+            ],
+        )?;
+        Ok(response
+            .query
+            .search
+            .into_iter()
+            .map(|hit| hit.title)
+            .collect())
+    }
+
+    /// The title of the article, after redirects, and its sections.
+    pub fn sections(&self, language: &str, title: &str) -> Result<(String, Vec<WikiSection>)> {
+        let response = self.query::<ParseResponse>(
+            language,
+            &[
+                ("action", "parse"),
+                ("page", title),
+                ("prop", "sections"),
+                ("redirects", "1"),
+            ],
+        )?;
+        Ok((response.parse.title, response.parse.sections))
+    }
+
+    /// The text of a section as markdown. Section `0` is the introduction.
+    pub fn section_text(&self, language: &str, title: &str, index: &str) -> Result<String> {
+        let response = self.query::<ParseResponse>(
+            language,
+            &[
+                ("action", "parse"),
+                ("page", title),
+                ("prop", "text"),
+                ("section", index),
+                ("redirects", "1"),
+                ("disableeditsection", "1"),
+                ("disabletoc", "1"),
+            ],
+        )?;
+        Ok(self.truncate(html_to_markdown(&response.parse.text)))
+    }
+
+    /// The url of the article for a reader.
+    fn article_url(&self, language: &str, title: &str) -> Result<Url> {
+        let mut url = self.api(language)?;
+        let path = url.path().replace("api.php", "index.php");
+        url.set_path(&path);
+        url.set_query(None);
+        url.query_pairs_mut()
+            .append_pair("title", &title.replace(' ', "_"));
+        Ok(url)
+    }
+
+    fn truncate(&self, text: String) -> String {
+        let length = text.chars().count();
+        if length <= self.max_chars {
+            return text;
+        }
+        let mut truncated = text.chars().take(self.max_chars).collect::<String>();
+        truncated.push_str(&format!(
+            "\n\n[Truncated: showing the first {} of {} characters.]",
+            self.max_chars, length
+        ));
+        truncated
+    }
+
+    /// Looks up the article matching the query and returns its summary and outline, or the text of a section,
+    /// chosen by number or title.
+    pub fn lookup(
+        &self,
+        query: &str,
+        section: Option<&str>,
+        language: Option<&str>,
+    ) -> Result<String> {
+        let language = language.unwrap_or(&self.language);
+        let titles = self.search(language, query)?;
+        let Some(title) = titles
+            .iter()
+            .find(|title| title.eq_ignore_ascii_case(query.trim()))
+            .or(titles.first())
+        else {
+            return Ok(format!(
+                "No article found for '{}'. Try other keywords.",
+                query
+            ));
+        };
+        let (title, sections) = self.sections(language, title)?;
+        let url = self.article_url(language, &title)?;
+
+        if let Some(wanted) = section {
+            let wanted = wanted.trim();
+            let name = |section: &WikiSection| strip_tags(&section.line);
+            let found = sections
+                .iter()
+                .find(|section| {
+                    section.number == wanted || name(section).eq_ignore_ascii_case(wanted)
+                })
+                .or_else(|| {
+                    sections.iter().find(|section| {
+                        name(section)
+                            .to_lowercase()
+                            .contains(&wanted.to_lowercase())
+                    })
+                });
+            let Some(found) = found else {
+                return Err(anyhow!(
+                    "The article '{}' has no section '{}'. Its sections are:\n{}",
+                    title,
+                    wanted,
+                    outline(&sections)
+                ));
+            };
+            let text = self.section_text(language, &title, &found.index)?;
+            return Ok(format!(
+                "# {}: {}\nSource: {}\n\n{}",
+                title,
+                name(found),
+                url,
+                text
+            ));
+        }
+
+        let summary = self.section_text(language, &title, "0")?;
+        let mut output = format!("# {}\nSource: {}\n\n{}", title, url, summary);
+        if !sections.is_empty() {
+            output.push_str(&format!(
+                "\n\n## Sections\n{}\n\nPass `section` with a section number or title to read it.",
+                outline(&sections)
+            ));
+        }
+        let others = titles
+            .iter()
+            .filter(|other| **other != title)
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        if !others.is_empty() {
+            output.push_str(&format!(
+                "\n\nOther matching articles: {}",
+                others.join(", ")
+            ));
+        }
+        Ok(output)
+    }
+}
+
+/// The numbered list of sections, indented by level.
+fn outline(sections: &[WikiSection]) -> String {
+    sections
+        .iter()
+        .map(|section| {
+            let depth = section.number.matches('.').count();
+            format!(
+                "{}{} {}",
+                "  ".repeat(depth),
+                section.number,
+                strip_tags(&section.line)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn strip_tags(html: &str) -> String {
+    let tags = Regex::new(r"<[^>]*>").unwrap();
+    tags.replace_all(html, "").trim().to_string()
+}
+
+/// Converts the HTML of an article to markdown, without tables, images and reference markers.
+fn html_to_markdown(html: &str) -> String {
+    let converter = HtmlToMarkdown::builder()
+        .skip_tags(vec!["script", "style", "table", "figure", "img", "sup"])
+        .build();
+    let markdown = converter.convert(html).unwrap_or_default();
+    let blank_lines = Regex::new(r"\n{3,}").unwrap();
+    blank_lines.replace_all(markdown.trim(), "\n\n").to_string()
+}
+
+#[derive(Deserialize, JsonSchema)]
+#[schemars(title = "WikipediaToolParams")]
+pub struct WikipediaToolParams {
+    #[schemars(description = "The title of the article or keywords to search for")]
+    query: String,
+    #[schemars(
+        description = "The number or title of a section to read. Leave empty to get the summary and the list of sections"
+    )]
+    section: Option<String>,
+    #[schemars(description = "The language code of the wiki, e.g. 'en' or 'fr'")]
+    language: Option<String>,
+}
+
+impl Tool for WikipediaTool {
+    type Params = WikipediaToolParams;
+    fn name(&self) -> &'static str {
+        self.tool.name
+    }
+    fn description(&self) -> &'static str {
+        self.tool.description
+    }
+    fn cacheable(&self) -> bool {
+        true
+    }
+    fn forward(&self, arguments: WikipediaToolParams) -> Result<String> {
+        self.lookup(
+            &arguments.query,
+            arguments
+                .section
+                .as_deref()
+                .filter(|section| !section.trim().is_empty()),
+            arguments.language.as_deref(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockServer;
+
+    const SEARCH: &str = r#"{"batchcomplete": true, "query": {"searchinfo": {"totalhits": 2}, "search": [
+        {"ns": 0, "title": "Rust (programming language)", "pageid": 1},
+        {"ns": 0, "title": "Rust", "pageid": 2}
+    ]}}"#;
+    const SECTIONS: &str = r#"{"parse": {"title": "Rust", "pageid": 2, "sections": [
+        {"toclevel": 1, "level": "2", "line": "Chemistry", "number": "1", "index": "1", "anchor": "Chemistry"},
+        {"toclevel": 2, "level": "3", "line": "<i>Oxidation</i>", "number": "1.1", "index": "2", "anchor": "Oxidation"},
+        {"toclevel": 1, "level": "2", "line": "Prevention", "number": "2", "index": "3", "anchor": "Prevention"}
+    ]}}"#;
+
+    fn tool(server: &MockServer) -> WikipediaTool {
+        WikipediaTool::new().with_api_url(&format!("{}/w/api.php", server.url()))
+    }
+
+    fn lookup(tool: &WikipediaTool, query: &str, section: Option<&str>) -> Result<String> {
+        Tool::forward(
+            tool,
+            WikipediaToolParams {
+                query: query.to_string(),
+                section: section.map(str::to_string),
+                language: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_wikipedia_tool_summary() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], SEARCH),
+            MockServer::response(200, &[], SECTIONS),
+            MockServer::response(
+                200,
+                &[],
+                r#"{"parse": {"title": "Rust", "pageid": 2, "text": "<div class=\"mw-parser-output\"><p><b>Rust</b> is an iron oxide.<sup>[1]</sup></p><table><tr><td>Infobox</td></tr></table></div>"}}"#,
+            ),
+        ]);
+        let output = lookup(&tool(&server), "rust", None).unwrap();
+        let url = server.url();
+        assert_eq!(
+            output,
+            format!("# Rust\nSource: {url}/w/index.php?title=Rust\n\n**Rust** is an iron oxide.\n\n## Sections\n1 Chemistry\n  1.1 Oxidation\n2 Prevention\n\nPass `section` with a section number or title to read it.\n\nOther matching articles: Rust (programming language)")
+        );
+
+        let requests = server.requests();
+        assert!(requests[0]
+            .path
+            .starts_with("/w/api.php?action=query&list=search&srsearch=rust&srlimit=5"));
+        assert!(requests[0].path.ends_with("&format=json&formatversion=2"));
+        assert!(requests[1]
+            .path
+            .contains("action=parse&page=Rust&prop=sections"));
+        assert!(requests[2].path.contains("prop=text&section=0"));
+    }
+
+    #[test]
+    fn test_wikipedia_tool_section() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], SEARCH),
+            MockServer::response(200, &[], SECTIONS),
+            MockServer::response(
+                200,
+                &[],
+                r#"{"parse": {"title": "Rust", "pageid": 2, "text": "<h3>Oxidation</h3><p>Iron reacts with oxygen.</p>"}}"#,
+            ),
+            MockServer::response(200, &[], SEARCH),
+            MockServer::response(200, &[], SECTIONS),
+        ]);
+        let tool = tool(&server);
+        let output = lookup(&tool, "Rust", Some("oxidation")).unwrap();
+        assert!(output.starts_with("# Rust: Oxidation\nSource: "));
+        assert!(output.ends_with("### Oxidation\n\nIron reacts with oxygen."));
+        assert!(server.requests()[2].path.contains("section=2"));
+
+        let error = lookup(&tool, "Rust", Some("History")).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "The article 'Rust' has no section 'History'. Its sections are:\n1 Chemistry\n  1.1 Oxidation\n2 Prevention"
+        );
+    }
+
+    #[test]
+    fn test_wikipedia_tool_errors() {
+        let server = MockServer::start(vec![
+            MockServer::response(200, &[], r#"{"query": {"search": []}}"#),
+            MockServer::response(
+                200,
+                &[],
+                r#"{"error": {"code": "missingtitle", "info": "The page you specified doesn't exist."}}"#,
+            ),
+        ]);
+        let tool = tool(&server);
+        assert_eq!(
+            lookup(&tool, "qwxz", None).unwrap(),
+            "No article found for 'qwxz'. Try other keywords."
+        );
+        assert_eq!(
+            tool.sections("en", "Missing").unwrap_err().to_string(),
+            "The wiki returned an error: The page you specified doesn't exist."
+        );
+        assert_eq!(
+            WikipediaTool::new()
+                .api("en.evil.com/")
+                .unwrap_err()
+                .to_string(),
+            "Invalid language code: en.evil.com/"
+        );
+        assert_eq!(
+            WikipediaTool::new().api("fr").unwrap().as_str(),
+            "https://fr.wikipedia.org/w/api.php"
+        );
+    }
+}